@@ -0,0 +1,12 @@
+use std::{env, path::PathBuf};
+
+fn main() {
+    let dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("brs.h");
+    let config = cbindgen::Config::from_file(dir.join("cbindgen.toml")).unwrap();
+    cbindgen::generate_with_config(&dir, config)
+        .expect("Unable to generate C header")
+        .write_to_file(&out);
+    // The tests check that the committed header matches this one.
+    println!("cargo:rustc-env=BRS_GENERATED_HEADER={}", out.display());
+}
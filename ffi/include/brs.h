@@ -0,0 +1,322 @@
+#ifndef BRS_H
+#define BRS_H
+
+/* Generated by cbindgen from the brs-ffi crate. Don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// `BrsBrick::direction` of a brick facing positive X.
+#define BRS_DIRECTION_X_POSITIVE 0
+
+// `BrsBrick::direction` of a brick facing negative X.
+#define BRS_DIRECTION_X_NEGATIVE 1
+
+// `BrsBrick::direction` of a brick facing positive Y.
+#define BRS_DIRECTION_Y_POSITIVE 2
+
+// `BrsBrick::direction` of a brick facing negative Y.
+#define BRS_DIRECTION_Y_NEGATIVE 3
+
+// `BrsBrick::direction` of a brick facing positive Z, which is up.
+#define BRS_DIRECTION_Z_POSITIVE 4
+
+// `BrsBrick::direction` of a brick facing negative Z.
+#define BRS_DIRECTION_Z_NEGATIVE 5
+
+// `BrsBrick::rotation` of an unrotated brick.
+#define BRS_ROTATION_DEG_0 0
+
+// `BrsBrick::rotation` of a brick turned by 90 degrees.
+#define BRS_ROTATION_DEG_90 1
+
+// `BrsBrick::rotation` of a brick turned by 180 degrees.
+#define BRS_ROTATION_DEG_180 2
+
+// `BrsBrick::rotation` of a brick turned by 270 degrees.
+#define BRS_ROTATION_DEG_270 3
+
+// The result of a call.
+typedef enum BrsStatus {
+  // The call succeeded.
+  BRS_STATUS_OK = 0,
+  // There are no more bricks to read.
+  BRS_STATUS_END,
+  // A required pointer argument was null.
+  BRS_STATUS_NULL_ARGUMENT,
+  // A file couldn't be found.
+  BRS_STATUS_NOT_FOUND,
+  // A file couldn't be opened or created due to its permissions.
+  BRS_STATUS_PERMISSION_DENIED,
+  // The save file ended early.
+  BRS_STATUS_UNEXPECTED_EOF,
+  // The save file is invalid or of an unsupported version.
+  BRS_STATUS_INVALID_DATA,
+  // An argument is invalid, such as a string that isn't UTF-8
+  // or a brick with an unknown direction.
+  BRS_STATUS_INVALID_INPUT,
+  // Any other I/O error.
+  BRS_STATUS_IO,
+  // An internal error that should be reported as a bug.
+  BRS_STATUS_PANIC,
+} BrsStatus;
+
+// A save being read, created by `brs_reader_open_path`
+// or `brs_reader_open_buffer`.
+typedef struct BrsReader BrsReader;
+
+// A save being built, created by `brs_save_new`.
+typedef struct BrsSave BrsSave;
+
+// A user, such as the author of a save or the owner of bricks.
+typedef struct BrsUser {
+  // The UUID of the user, in big-endian byte order.
+  uint8_t id[16];
+  // The name of the user as a null-terminated UTF-8 string.
+  const char *name;
+} BrsUser;
+
+// The headers of a save, as returned by `brs_reader_headers`.
+//
+// All pointers are owned by the reader and stay valid until it's freed.
+typedef struct BrsHeaders {
+  // The save file version.
+  uint16_t version;
+  const char *map;
+  struct BrsUser author;
+  const char *description;
+  // Whether the save has a host in `host`.
+  bool has_host;
+  struct BrsUser host;
+  // Whether the save has a time in `save_time`.
+  bool has_save_time;
+  // When the save was created, in seconds since the Unix epoch.
+  int64_t save_time;
+  int32_t brick_count;
+  const char *const *mods;
+  size_t mod_count;
+  const char *const *brick_assets;
+  size_t brick_asset_count;
+  // Colors as `0xAARRGGBB`.
+  const uint32_t *colors;
+  size_t color_count;
+  const char *const *materials;
+  size_t material_count;
+  const struct BrsUser *brick_owners;
+  size_t brick_owner_count;
+} BrsHeaders;
+
+// A brick, mirroring `brs::Brick`.
+typedef struct BrsBrick {
+  // The index of the brick's asset in the brick asset table.
+  uint32_t asset_name_index;
+  // Half the size of the brick along each axis, or all zero for
+  // assets with a fixed size.
+  uint32_t size[3];
+  // The position of the brick's center.
+  int32_t position[3];
+  // One of the `BRS_DIRECTION_` values.
+  uint8_t direction;
+  // One of the `BRS_ROTATION_` values.
+  uint8_t rotation;
+  bool collision;
+  bool visibility;
+  // The index of the brick's material in the material table.
+  uint32_t material_index;
+  // Whether `color` is a custom color rather than an index
+  // into the color table.
+  bool custom_color;
+  // An index into the color table, or a custom color as `0xAARRGGBB`.
+  uint32_t color;
+  // Whether the brick has an owner in `owner_index`.
+  bool has_owner;
+  // The index of the brick's owner in the brick owner table.
+  uint32_t owner_index;
+} BrsBrick;
+
+// Bytes allocated by the library, which must be freed with `brs_buffer_free`.
+typedef struct BrsBuffer {
+  uint8_t *data;
+  size_t len;
+} BrsBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The message describing the last error on this thread, or null if there
+// was none. The message stays valid until the next failing call on this thread.
+const char *brs_last_error(void);
+
+// Open the save file at `path` and read its headers. On success, `*out`
+// is set to a reader that must be freed with `brs_reader_free`.
+//
+// # Safety
+//
+// `path` must be a null-terminated string and `out` must be valid for writes.
+enum BrsStatus brs_reader_open_path(const char *path, struct BrsReader **out);
+
+// Read the headers of the save file in the `len` bytes at `data`, which
+// are copied. On success, `*out` is set to a reader that must be freed
+// with `brs_reader_free`.
+//
+// # Safety
+//
+// `data` must be valid for reads of `len` bytes and `out` must be valid
+// for writes.
+enum BrsStatus brs_reader_open_buffer(const uint8_t *data, size_t len, struct BrsReader **out);
+
+// The headers of the save read by `reader`, which stay valid until
+// the reader is freed. Returns null if `reader` is null.
+//
+// # Safety
+//
+// `reader` must be null or a reader that hasn't been freed.
+const struct BrsHeaders *brs_reader_headers(const struct BrsReader *reader);
+
+// Read the next brick into `*out`. Returns `BRS_STATUS_END` once all
+// bricks have been read, and after an error.
+//
+// # Safety
+//
+// `reader` must be a reader that hasn't been freed and `out`
+// must be valid for writes.
+enum BrsStatus brs_reader_next_brick(struct BrsReader *reader, struct BrsBrick *out);
+
+// Free `reader` along with its headers. Does nothing if `reader` is null.
+//
+// # Safety
+//
+// `reader` must be null or a reader that hasn't been freed.
+void brs_reader_free(struct BrsReader *reader);
+
+// Create an empty save on the map `Plate`, by a user with a nil UUID
+// and an empty name, saved at the current time. It must be freed
+// with `brs_save_free`.
+struct BrsSave *brs_save_new(void);
+
+// Free `save`. Does nothing if `save` is null.
+//
+// # Safety
+//
+// `save` must be null or a save that hasn't been freed.
+void brs_save_free(struct BrsSave *save);
+
+// Set the map of `save`.
+//
+// # Safety
+//
+// `save` must be a save that hasn't been freed and `map`
+// a null-terminated string.
+enum BrsStatus brs_save_set_map(struct BrsSave *save, const char *map);
+
+// Set the description of `save`.
+//
+// # Safety
+//
+// `save` must be a save that hasn't been freed and `description`
+// a null-terminated string.
+enum BrsStatus brs_save_set_description(struct BrsSave *save, const char *description);
+
+// Set the author of `save`.
+//
+// # Safety
+//
+// `save` must be a save that hasn't been freed and `user` must point
+// to a user with a null-terminated name.
+enum BrsStatus brs_save_set_author(struct BrsSave *save, const struct BrsUser *user);
+
+// Set when `save` was created, in seconds since the Unix epoch.
+//
+// # Safety
+//
+// `save` must be a save that hasn't been freed.
+enum BrsStatus brs_save_set_save_time(struct BrsSave *save, int64_t save_time);
+
+// Add the mod `name` to `save`.
+//
+// # Safety
+//
+// `save` must be a save that hasn't been freed and `name`
+// a null-terminated string.
+enum BrsStatus brs_save_add_mod(struct BrsSave *save, const char *name);
+
+// Add the asset `name` to the brick asset table of `save`, and set
+// `*index` to its index unless `index` is null.
+//
+// # Safety
+//
+// `save` must be a save that hasn't been freed, `name` a null-terminated
+// string and `index` null or valid for writes.
+enum BrsStatus brs_save_add_brick_asset(struct BrsSave *save, const char *name, uint32_t *index);
+
+// Add the color `color`, as `0xAARRGGBB`, to the color table of `save`,
+// and set `*index` to its index unless `index` is null.
+//
+// # Safety
+//
+// `save` must be a save that hasn't been freed and `index`
+// null or valid for writes.
+enum BrsStatus brs_save_add_color(struct BrsSave *save, uint32_t color, uint32_t *index);
+
+// Add the material `name` to the material table of `save`, and set
+// `*index` to its index unless `index` is null.
+//
+// # Safety
+//
+// `save` must be a save that hasn't been freed, `name` a null-terminated
+// string and `index` null or valid for writes.
+enum BrsStatus brs_save_add_material(struct BrsSave *save, const char *name, uint32_t *index);
+
+// Add `user` to the brick owner table of `save`, and set `*index`
+// to its index unless `index` is null.
+//
+// # Safety
+//
+// `save` must be a save that hasn't been freed, `user` must point to
+// a user with a null-terminated name and `index` must be null or
+// valid for writes.
+enum BrsStatus brs_save_add_brick_owner(struct BrsSave *save,
+                                        const struct BrsUser *user,
+                                        uint32_t *index);
+
+// Add `brick` to `save`.
+//
+// # Safety
+//
+// `save` must be a save that hasn't been freed and `brick` must point
+// to a brick.
+enum BrsStatus brs_save_add_brick(struct BrsSave *save, const struct BrsBrick *brick);
+
+// Write `save` to a new file at `path`, replacing any existing file.
+//
+// # Safety
+//
+// `save` must be a save that hasn't been freed and `path`
+// a null-terminated string.
+enum BrsStatus brs_save_write_path(const struct BrsSave *save, const char *path);
+
+// Write `save` to a new buffer, which `*out` is set to. The buffer
+// must be freed with `brs_buffer_free`.
+//
+// # Safety
+//
+// `save` must be a save that hasn't been freed and `out`
+// must be valid for writes.
+enum BrsStatus brs_save_write_buffer(const struct BrsSave *save, struct BrsBuffer *out);
+
+// Free the contents of `buffer`. Does nothing if its data is null.
+//
+// # Safety
+//
+// `buffer` must have been filled in by the library and not freed before.
+void brs_buffer_free(struct BrsBuffer buffer);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BRS_H */
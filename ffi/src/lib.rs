@@ -0,0 +1,226 @@
+//! C bindings for reading and writing Brickadia save files.
+//!
+//! The header `include/brs.h` is generated from this crate by its build
+//! script, and the tests check that the committed copy is up to date.
+//!
+//! Functions that can fail return a `BrsStatus`. On failure, `brs_last_error`
+//! describes what went wrong. Objects created by the library are freed with
+//! the matching `_free` function.
+
+mod read;
+mod write;
+
+pub use read::*;
+pub use write::*;
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString},
+    io,
+    os::raw::c_char,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+/// The result of a call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrsStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// There are no more bricks to read.
+    End,
+    /// A required pointer argument was null.
+    NullArgument,
+    /// A file couldn't be found.
+    NotFound,
+    /// A file couldn't be opened or created due to its permissions.
+    PermissionDenied,
+    /// The save file ended early.
+    UnexpectedEof,
+    /// The save file is invalid or of an unsupported version.
+    InvalidData,
+    /// An argument is invalid, such as a string that isn't UTF-8
+    /// or a brick with an unknown direction.
+    InvalidInput,
+    /// Any other I/O error.
+    Io,
+    /// An internal error that should be reported as a bug.
+    Panic,
+}
+
+impl From<&io::Error> for BrsStatus {
+    fn from(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => BrsStatus::NotFound,
+            io::ErrorKind::PermissionDenied => BrsStatus::PermissionDenied,
+            io::ErrorKind::UnexpectedEof => BrsStatus::UnexpectedEof,
+            io::ErrorKind::InvalidData => BrsStatus::InvalidData,
+            io::ErrorKind::InvalidInput => BrsStatus::InvalidInput,
+            _ => BrsStatus::Io,
+        }
+    }
+}
+
+/// An error inside a call, before it's turned into a status.
+pub(crate) enum Error {
+    Null(&'static str),
+    Io(io::Error),
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', "")).unwrap();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Run `f`, turning errors and panics into a status and setting
+/// the last error message.
+pub(crate) fn guard(f: impl FnOnce() -> Result<BrsStatus, Error>) -> BrsStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(status)) => status,
+        Ok(Err(Error::Null(name))) => {
+            set_last_error(format!("Argument `{}` is null", name));
+            BrsStatus::NullArgument
+        }
+        Ok(Err(Error::Io(error))) => {
+            set_last_error(error.to_string());
+            BrsStatus::from(&error)
+        }
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            set_last_error(format!("Internal error: {}", message));
+            BrsStatus::Panic
+        }
+    }
+}
+
+/// Borrow the UTF-8 string `s`, which is named `name` in errors.
+///
+/// # Safety
+///
+/// `s` must be null or point to a null-terminated string that outlives `'a`.
+pub(crate) unsafe fn to_str<'a>(s: *const c_char, name: &'static str) -> Result<&'a str, Error> {
+    if s.is_null() {
+        return Err(Error::Null(name));
+    }
+    CStr::from_ptr(s).to_str().map_err(|_| {
+        Error::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Argument `{}` isn't UTF-8", name),
+        ))
+    })
+}
+
+/// A copy of `s` that can be handed out to C, leaving out any null bytes.
+pub(crate) fn to_c_string(s: &str) -> CString {
+    CString::new(s.replace('\0', "")).unwrap()
+}
+
+/// The message describing the last error on this thread, or null if there
+/// was none. The message stays valid until the next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn brs_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| match &*last.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// A user, such as the author of a save or the owner of bricks.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BrsUser {
+    /// The UUID of the user, in big-endian byte order.
+    pub id: [u8; 16],
+    /// The name of the user as a null-terminated UTF-8 string.
+    pub name: *const c_char,
+}
+
+/// `BrsBrick::direction` of a brick facing positive X.
+pub const BRS_DIRECTION_X_POSITIVE: u8 = 0;
+/// `BrsBrick::direction` of a brick facing negative X.
+pub const BRS_DIRECTION_X_NEGATIVE: u8 = 1;
+/// `BrsBrick::direction` of a brick facing positive Y.
+pub const BRS_DIRECTION_Y_POSITIVE: u8 = 2;
+/// `BrsBrick::direction` of a brick facing negative Y.
+pub const BRS_DIRECTION_Y_NEGATIVE: u8 = 3;
+/// `BrsBrick::direction` of a brick facing positive Z, which is up.
+pub const BRS_DIRECTION_Z_POSITIVE: u8 = 4;
+/// `BrsBrick::direction` of a brick facing negative Z.
+pub const BRS_DIRECTION_Z_NEGATIVE: u8 = 5;
+
+/// `BrsBrick::rotation` of an unrotated brick.
+pub const BRS_ROTATION_DEG_0: u8 = 0;
+/// `BrsBrick::rotation` of a brick turned by 90 degrees.
+pub const BRS_ROTATION_DEG_90: u8 = 1;
+/// `BrsBrick::rotation` of a brick turned by 180 degrees.
+pub const BRS_ROTATION_DEG_180: u8 = 2;
+/// `BrsBrick::rotation` of a brick turned by 270 degrees.
+pub const BRS_ROTATION_DEG_270: u8 = 3;
+
+/// A brick, mirroring `brs::Brick`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BrsBrick {
+    /// The index of the brick's asset in the brick asset table.
+    pub asset_name_index: u32,
+    /// Half the size of the brick along each axis, or all zero for
+    /// assets with a fixed size.
+    pub size: [u32; 3],
+    /// The position of the brick's center.
+    pub position: [i32; 3],
+    /// One of the `BRS_DIRECTION_` values.
+    pub direction: u8,
+    /// One of the `BRS_ROTATION_` values.
+    pub rotation: u8,
+    pub collision: bool,
+    pub visibility: bool,
+    /// The index of the brick's material in the material table.
+    pub material_index: u32,
+    /// Whether `color` is a custom color rather than an index
+    /// into the color table.
+    pub custom_color: bool,
+    /// An index into the color table, or a custom color as `0xAARRGGBB`.
+    pub color: u32,
+    /// Whether the brick has an owner in `owner_index`.
+    pub has_owner: bool,
+    /// The index of the brick's owner in the brick owner table.
+    pub owner_index: u32,
+}
+
+impl From<brs::Brick> for BrsBrick {
+    fn from(brick: brs::Brick) -> Self {
+        let (custom_color, color) = match brick.color {
+            brs::ColorMode::Set(index) => (false, index),
+            brs::ColorMode::Custom(color) => (true, u32::from(color)),
+        };
+        Self {
+            asset_name_index: brick.asset_name_index,
+            size: [brick.size.0, brick.size.1, brick.size.2],
+            position: [brick.position.0, brick.position.1, brick.position.2],
+            direction: brick.direction.into(),
+            rotation: brick.rotation.into(),
+            collision: brick.collision,
+            visibility: brick.visibility,
+            material_index: brick.material_index,
+            custom_color,
+            color,
+            has_owner: brick.owner_index.is_some(),
+            owner_index: brick.owner_index.unwrap_or(0),
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use crate::{guard, to_c_string, to_str, BrsBrick, BrsStatus, BrsUser, Error};
+use brs::{
+    read::{ReadBricks, ReaderAfterHeader2},
+    HasHeader1, HasHeader2, Reader, User,
+};
+use std::{
+    ffi::CString,
+    fs::File,
+    io::{prelude::*, BufReader, Cursor},
+    mem,
+    os::raw::c_char,
+    ptr, slice,
+};
+
+/// The headers of a save, as returned by `brs_reader_headers`.
+///
+/// All pointers are owned by the reader and stay valid until it's freed.
+#[repr(C)]
+pub struct BrsHeaders {
+    /// The save file version.
+    pub version: u16,
+    pub map: *const c_char,
+    pub author: BrsUser,
+    pub description: *const c_char,
+    /// Whether the save has a host in `host`.
+    pub has_host: bool,
+    pub host: BrsUser,
+    /// Whether the save has a time in `save_time`.
+    pub has_save_time: bool,
+    /// When the save was created, in seconds since the Unix epoch.
+    pub save_time: i64,
+    pub brick_count: i32,
+    pub mods: *const *const c_char,
+    pub mod_count: usize,
+    pub brick_assets: *const *const c_char,
+    pub brick_asset_count: usize,
+    /// Colors as `0xAARRGGBB`.
+    pub colors: *const u32,
+    pub color_count: usize,
+    pub materials: *const *const c_char,
+    pub material_count: usize,
+    pub brick_owners: *const BrsUser,
+    pub brick_owner_count: usize,
+}
+
+enum Bricks {
+    Pending(Box<ReaderAfterHeader2<Box<dyn Read>>>),
+    Reading(ReadBricks),
+    Done,
+}
+
+/// A save being read, created by `brs_reader_open_path`
+/// or `brs_reader_open_buffer`.
+pub struct BrsReader {
+    headers: BrsHeaders,
+    // Storage for what the headers point to. Moving the containers
+    // doesn't move their heap contents.
+    _strings: Vec<CString>,
+    _string_lists: Vec<Vec<*const c_char>>,
+    _colors: Vec<u32>,
+    _owners: Vec<BrsUser>,
+    bricks: Bricks,
+}
+
+#[derive(Default)]
+struct Storage {
+    strings: Vec<CString>,
+    string_lists: Vec<Vec<*const c_char>>,
+}
+
+impl Storage {
+    fn string(&mut self, s: &str) -> *const c_char {
+        let s = to_c_string(s);
+        let ptr = s.as_ptr();
+        self.strings.push(s);
+        ptr
+    }
+
+    fn string_list(&mut self, list: &[String]) -> *const *const c_char {
+        let list = list.iter().map(|s| self.string(s)).collect::<Vec<_>>();
+        let ptr = list.as_ptr();
+        self.string_lists.push(list);
+        ptr
+    }
+
+    fn user(&mut self, user: &User) -> BrsUser {
+        BrsUser {
+            id: *user.id.as_bytes(),
+            name: self.string(&user.name),
+        }
+    }
+}
+
+fn open(r: Box<dyn Read>) -> Result<BrsReader, Error> {
+    let reader = Reader::new(r)?;
+    let version = reader.version();
+    let reader = reader.read_header1()?.read_header2()?;
+
+    let mut storage = Storage::default();
+    let colors = reader
+        .colors()
+        .iter()
+        .map(|&c| u32::from(c))
+        .collect::<Vec<_>>();
+    let owners = reader
+        .brick_owners()
+        .iter()
+        .map(|owner| storage.user(owner))
+        .collect::<Vec<_>>();
+    let host = reader
+        .header1()
+        .host
+        .as_ref()
+        .map(|host| storage.user(host));
+
+    let headers = BrsHeaders {
+        version: version.into(),
+        map: storage.string(reader.map()),
+        author: storage.user(reader.author()),
+        description: storage.string(reader.description()),
+        has_host: host.is_some(),
+        host: host.unwrap_or(BrsUser {
+            id: [0; 16],
+            name: ptr::null(),
+        }),
+        has_save_time: reader.save_time().is_some(),
+        save_time: reader.save_time().map_or(0, |t| t.timestamp()),
+        brick_count: reader.brick_count(),
+        mods: storage.string_list(reader.mods()),
+        mod_count: reader.mods().len(),
+        brick_assets: storage.string_list(reader.brick_assets()),
+        brick_asset_count: reader.brick_assets().len(),
+        colors: colors.as_ptr(),
+        color_count: colors.len(),
+        materials: storage.string_list(reader.materials()),
+        material_count: reader.materials().len(),
+        brick_owners: owners.as_ptr(),
+        brick_owner_count: owners.len(),
+    };
+
+    Ok(BrsReader {
+        headers,
+        _strings: storage.strings,
+        _string_lists: storage.string_lists,
+        _colors: colors,
+        _owners: owners,
+        bricks: Bricks::Pending(Box::new(reader)),
+    })
+}
+
+/// Open the save file at `path` and read its headers. On success, `*out`
+/// is set to a reader that must be freed with `brs_reader_free`.
+///
+/// # Safety
+///
+/// `path` must be a null-terminated string and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn brs_reader_open_path(
+    path: *const c_char,
+    out: *mut *mut BrsReader,
+) -> BrsStatus {
+    guard(|| {
+        let out = out.as_mut().ok_or(Error::Null("out"))?;
+        *out = ptr::null_mut();
+        let file = File::open(to_str(path, "path")?)?;
+        *out = Box::into_raw(Box::new(open(Box::new(BufReader::new(file)))?));
+        Ok(BrsStatus::Ok)
+    })
+}
+
+/// Read the headers of the save file in the `len` bytes at `data`, which
+/// are copied. On success, `*out` is set to a reader that must be freed
+/// with `brs_reader_free`.
+///
+/// # Safety
+///
+/// `data` must be valid for reads of `len` bytes and `out` must be valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn brs_reader_open_buffer(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut BrsReader,
+) -> BrsStatus {
+    guard(|| {
+        let out = out.as_mut().ok_or(Error::Null("out"))?;
+        *out = ptr::null_mut();
+        let bytes = match len {
+            0 => Vec::new(),
+            _ if data.is_null() => return Err(Error::Null("data")),
+            _ => slice::from_raw_parts(data, len).to_vec(),
+        };
+        *out = Box::into_raw(Box::new(open(Box::new(Cursor::new(bytes)))?));
+        Ok(BrsStatus::Ok)
+    })
+}
+
+/// The headers of the save read by `reader`, which stay valid until
+/// the reader is freed. Returns null if `reader` is null.
+///
+/// # Safety
+///
+/// `reader` must be null or a reader that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn brs_reader_headers(reader: *const BrsReader) -> *const BrsHeaders {
+    match reader.as_ref() {
+        Some(reader) => &reader.headers,
+        None => ptr::null(),
+    }
+}
+
+/// Read the next brick into `*out`. Returns `BRS_STATUS_END` once all
+/// bricks have been read, and after an error.
+///
+/// # Safety
+///
+/// `reader` must be a reader that hasn't been freed and `out`
+/// must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn brs_reader_next_brick(
+    reader: *mut BrsReader,
+    out: *mut BrsBrick,
+) -> BrsStatus {
+    guard(|| {
+        let reader = reader.as_mut().ok_or(Error::Null("reader"))?;
+        let out = out.as_mut().ok_or(Error::Null("out"))?;
+
+        if let Bricks::Pending(_) = reader.bricks {
+            if let Bricks::Pending(pending) = mem::replace(&mut reader.bricks, Bricks::Done) {
+                reader.bricks = Bricks::Reading(pending.iter_bricks()?);
+            }
+        }
+        let bricks = match &mut reader.bricks {
+            Bricks::Reading(bricks) => bricks,
+            _ => return Ok(BrsStatus::End),
+        };
+        match bricks.next() {
+            Some(Ok(brick)) => {
+                *out = brick.into();
+                Ok(BrsStatus::Ok)
+            }
+            Some(Err(error)) => {
+                reader.bricks = Bricks::Done;
+                Err(error.into())
+            }
+            None => {
+                reader.bricks = Bricks::Done;
+                Ok(BrsStatus::End)
+            }
+        }
+    })
+}
+
+/// Free `reader` along with its headers. Does nothing if `reader` is null.
+///
+/// # Safety
+///
+/// `reader` must be null or a reader that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn brs_reader_free(reader: *mut BrsReader) {
+    if !reader.is_null() {
+        drop(Box::from_raw(reader));
+    }
+}
@@ -0,0 +1,390 @@
+use crate::{guard, to_str, BrsBrick, BrsStatus, BrsUser, Error};
+use brs::{
+    chrono::{prelude::*, LocalResult},
+    uuid::Uuid,
+    Brick, Color, ColorMode, Direction, Rotation, User, WriteData,
+};
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{self, BufWriter, Write},
+    mem,
+    os::raw::c_char,
+    ptr,
+};
+
+/// A save being built, created by `brs_save_new`.
+pub struct BrsSave {
+    data: WriteData,
+}
+
+/// Bytes allocated by the library, which must be freed with `brs_buffer_free`.
+#[repr(C)]
+pub struct BrsBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+fn invalid_input(message: &str) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidInput, message))
+}
+
+unsafe fn to_user(user: *const BrsUser) -> Result<User, Error> {
+    let user = user.as_ref().ok_or(Error::Null("user"))?;
+    Ok(User {
+        id: Uuid::from_bytes(user.id),
+        name: to_str(user.name, "user.name")?.to_string(),
+    })
+}
+
+fn to_brick(brick: &BrsBrick) -> Result<Brick, Error> {
+    let [sx, sy, sz] = brick.size;
+    let [x, y, z] = brick.position;
+    Ok(Brick {
+        asset_name_index: brick.asset_name_index,
+        size: (sx, sy, sz),
+        position: (x, y, z),
+        direction: Direction::try_from(brick.direction)
+            .map_err(|_| invalid_input("Invalid brick direction"))?,
+        rotation: Rotation::try_from(brick.rotation)
+            .map_err(|_| invalid_input("Invalid brick rotation"))?,
+        collision: brick.collision,
+        visibility: brick.visibility,
+        material_index: brick.material_index,
+        color: if brick.custom_color {
+            ColorMode::Custom(Color::from(brick.color))
+        } else {
+            ColorMode::Set(brick.color)
+        },
+        owner_index: if brick.has_owner {
+            Some(brick.owner_index)
+        } else {
+            None
+        },
+    })
+}
+
+/// Create an empty save on the map `Plate`, by a user with a nil UUID
+/// and an empty name, saved at the current time. It must be freed
+/// with `brs_save_free`.
+#[no_mangle]
+pub extern "C" fn brs_save_new() -> *mut BrsSave {
+    Box::into_raw(Box::new(BrsSave {
+        data: WriteData {
+            map: "Plate".to_string(),
+            author: User {
+                id: Uuid::nil(),
+                name: String::new(),
+            },
+            description: String::new(),
+            save_time: Utc::now(),
+            mods: Vec::new(),
+            brick_assets: Vec::new(),
+            colors: Vec::new(),
+            materials: Vec::new(),
+            brick_owners: Vec::new(),
+            bricks: Vec::new(),
+        },
+    }))
+}
+
+/// Free `save`. Does nothing if `save` is null.
+///
+/// # Safety
+///
+/// `save` must be null or a save that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn brs_save_free(save: *mut BrsSave) {
+    if !save.is_null() {
+        drop(Box::from_raw(save));
+    }
+}
+
+/// Run `f` on the save behind `save`.
+unsafe fn with_save(
+    save: *mut BrsSave,
+    f: impl FnOnce(&mut WriteData) -> Result<(), Error>,
+) -> BrsStatus {
+    guard(|| {
+        let save = save.as_mut().ok_or(Error::Null("save"))?;
+        f(&mut save.data)?;
+        Ok(BrsStatus::Ok)
+    })
+}
+
+/// Set `*index` to `value` if `index` isn't null.
+unsafe fn set_index(index: *mut u32, value: usize) {
+    if let Some(index) = index.as_mut() {
+        *index = value as u32;
+    }
+}
+
+/// Set the map of `save`.
+///
+/// # Safety
+///
+/// `save` must be a save that hasn't been freed and `map`
+/// a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn brs_save_set_map(save: *mut BrsSave, map: *const c_char) -> BrsStatus {
+    with_save(save, |data| {
+        data.map = to_str(map, "map")?.to_string();
+        Ok(())
+    })
+}
+
+/// Set the description of `save`.
+///
+/// # Safety
+///
+/// `save` must be a save that hasn't been freed and `description`
+/// a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn brs_save_set_description(
+    save: *mut BrsSave,
+    description: *const c_char,
+) -> BrsStatus {
+    with_save(save, |data| {
+        data.description = to_str(description, "description")?.to_string();
+        Ok(())
+    })
+}
+
+/// Set the author of `save`.
+///
+/// # Safety
+///
+/// `save` must be a save that hasn't been freed and `user` must point
+/// to a user with a null-terminated name.
+#[no_mangle]
+pub unsafe extern "C" fn brs_save_set_author(
+    save: *mut BrsSave,
+    user: *const BrsUser,
+) -> BrsStatus {
+    with_save(save, |data| {
+        data.author = to_user(user)?;
+        Ok(())
+    })
+}
+
+/// Set when `save` was created, in seconds since the Unix epoch.
+///
+/// # Safety
+///
+/// `save` must be a save that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn brs_save_set_save_time(save: *mut BrsSave, save_time: i64) -> BrsStatus {
+    with_save(save, |data| match Utc.timestamp_opt(save_time, 0) {
+        LocalResult::Single(time) => {
+            data.save_time = time;
+            Ok(())
+        }
+        _ => Err(invalid_input("Save time out of range")),
+    })
+}
+
+/// Add a table entry with `add`, setting `*index` to its index.
+unsafe fn add_entry(
+    save: *mut BrsSave,
+    index: *mut u32,
+    add: impl FnOnce(&mut WriteData) -> Result<usize, Error>,
+) -> BrsStatus {
+    with_save(save, |data| {
+        let len = add(data)?;
+        set_index(index, len - 1);
+        Ok(())
+    })
+}
+
+/// Add the mod `name` to `save`.
+///
+/// # Safety
+///
+/// `save` must be a save that hasn't been freed and `name`
+/// a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn brs_save_add_mod(save: *mut BrsSave, name: *const c_char) -> BrsStatus {
+    add_entry(save, ptr::null_mut(), |data| {
+        data.mods.push(to_str(name, "name")?.to_string());
+        Ok(data.mods.len())
+    })
+}
+
+/// Add the asset `name` to the brick asset table of `save`, and set
+/// `*index` to its index unless `index` is null.
+///
+/// # Safety
+///
+/// `save` must be a save that hasn't been freed, `name` a null-terminated
+/// string and `index` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn brs_save_add_brick_asset(
+    save: *mut BrsSave,
+    name: *const c_char,
+    index: *mut u32,
+) -> BrsStatus {
+    add_entry(save, index, |data| {
+        data.brick_assets.push(to_str(name, "name")?.to_string());
+        Ok(data.brick_assets.len())
+    })
+}
+
+/// Add the color `color`, as `0xAARRGGBB`, to the color table of `save`,
+/// and set `*index` to its index unless `index` is null.
+///
+/// # Safety
+///
+/// `save` must be a save that hasn't been freed and `index`
+/// null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn brs_save_add_color(
+    save: *mut BrsSave,
+    color: u32,
+    index: *mut u32,
+) -> BrsStatus {
+    add_entry(save, index, |data| {
+        data.colors.push(Color::from(color));
+        Ok(data.colors.len())
+    })
+}
+
+/// Add the material `name` to the material table of `save`, and set
+/// `*index` to its index unless `index` is null.
+///
+/// # Safety
+///
+/// `save` must be a save that hasn't been freed, `name` a null-terminated
+/// string and `index` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn brs_save_add_material(
+    save: *mut BrsSave,
+    name: *const c_char,
+    index: *mut u32,
+) -> BrsStatus {
+    add_entry(save, index, |data| {
+        data.materials.push(to_str(name, "name")?.to_string());
+        Ok(data.materials.len())
+    })
+}
+
+/// Add `user` to the brick owner table of `save`, and set `*index`
+/// to its index unless `index` is null.
+///
+/// # Safety
+///
+/// `save` must be a save that hasn't been freed, `user` must point to
+/// a user with a null-terminated name and `index` must be null or
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn brs_save_add_brick_owner(
+    save: *mut BrsSave,
+    user: *const BrsUser,
+    index: *mut u32,
+) -> BrsStatus {
+    add_entry(save, index, |data| {
+        data.brick_owners.push(to_user(user)?);
+        Ok(data.brick_owners.len())
+    })
+}
+
+/// Add `brick` to `save`.
+///
+/// # Safety
+///
+/// `save` must be a save that hasn't been freed and `brick` must point
+/// to a brick.
+#[no_mangle]
+pub unsafe extern "C" fn brs_save_add_brick(
+    save: *mut BrsSave,
+    brick: *const BrsBrick,
+) -> BrsStatus {
+    with_save(save, |data| {
+        let brick = brick.as_ref().ok_or(Error::Null("brick"))?;
+        data.bricks.push(to_brick(brick)?);
+        Ok(())
+    })
+}
+
+/// Check that the bricks of `data` only refer to entries in its tables,
+/// which the writer relies on.
+fn check_indices(data: &WriteData) -> Result<(), Error> {
+    for brick in &data.bricks {
+        if brick.asset_name_index as usize >= data.brick_assets.len() {
+            return Err(invalid_input("Brick asset index out of range"));
+        }
+        if let ColorMode::Set(index) = brick.color {
+            if index as usize >= data.colors.len() {
+                return Err(invalid_input("Brick color index out of range"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write `save` to a new file at `path`, replacing any existing file.
+///
+/// # Safety
+///
+/// `save` must be a save that hasn't been freed and `path`
+/// a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn brs_save_write_path(
+    save: *const BrsSave,
+    path: *const c_char,
+) -> BrsStatus {
+    guard(|| {
+        let save = save.as_ref().ok_or(Error::Null("save"))?;
+        check_indices(&save.data)?;
+        let mut w = BufWriter::new(File::create(to_str(path, "path")?)?);
+        brs::write_save(&mut w, &save.data)?;
+        w.flush()?;
+        Ok(BrsStatus::Ok)
+    })
+}
+
+/// Write `save` to a new buffer, which `*out` is set to. The buffer
+/// must be freed with `brs_buffer_free`.
+///
+/// # Safety
+///
+/// `save` must be a save that hasn't been freed and `out`
+/// must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn brs_save_write_buffer(
+    save: *const BrsSave,
+    out: *mut BrsBuffer,
+) -> BrsStatus {
+    guard(|| {
+        let out = out.as_mut().ok_or(Error::Null("out"))?;
+        *out = BrsBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        let save = save.as_ref().ok_or(Error::Null("save"))?;
+        check_indices(&save.data)?;
+        let mut bytes = Vec::new();
+        brs::write_save(&mut bytes, &save.data)?;
+        let mut bytes = bytes.into_boxed_slice();
+        *out = BrsBuffer {
+            data: bytes.as_mut_ptr(),
+            len: bytes.len(),
+        };
+        mem::forget(bytes);
+        Ok(BrsStatus::Ok)
+    })
+}
+
+/// Free the contents of `buffer`. Does nothing if its data is null.
+///
+/// # Safety
+///
+/// `buffer` must have been filled in by the library and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn brs_buffer_free(buffer: BrsBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
@@ -0,0 +1,55 @@
+use std::{env, fs, path::PathBuf, process::Command};
+
+fn manifest_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+#[test]
+fn header_is_up_to_date() {
+    let generated = fs::read_to_string(env!("BRS_GENERATED_HEADER")).unwrap();
+    let committed = fs::read_to_string(manifest_dir().join("include/brs.h")).unwrap();
+    assert!(
+        generated == committed,
+        "include/brs.h is out of date, replace it with {}",
+        env!("BRS_GENERATED_HEADER")
+    );
+}
+
+/// Compile `tests/smoke.c` against the header and the static library,
+/// then run it.
+#[test]
+#[cfg(unix)]
+fn c_smoke_test() {
+    // Tests don't build the static library, so build it here. It ends up
+    // next to the `deps` directory the test binaries are built in.
+    let mut build = Command::new(env!("CARGO"));
+    build.args(["build", "--lib", "-p", "brs-ffi"]);
+    if !cfg!(debug_assertions) {
+        build.arg("--release");
+    }
+    assert!(build.status().unwrap().success(), "building brs-ffi failed");
+    let exe = env::current_exe().unwrap();
+    let library = exe.parent().unwrap().with_file_name("libbrs_ffi.a");
+
+    let dir = env::temp_dir().join(format!("brs-ffi-smoke-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let program = dir.join("smoke");
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(compiler)
+        .arg(manifest_dir().join("tests/smoke.c"))
+        .arg("-I")
+        .arg(manifest_dir().join("include"))
+        .arg(&library)
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&program)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "compiling tests/smoke.c failed");
+
+    let status = Command::new(&program)
+        .arg(dir.join("smoke.brs"))
+        .status()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(status.success(), "tests/smoke.c failed");
+}
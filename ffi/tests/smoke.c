@@ -0,0 +1,114 @@
+/* Builds a save, writes it to a file and a buffer, and reads both back. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "brs.h"
+
+#define CHECK(call)                                                         \
+    do {                                                                    \
+        if ((call) != BRS_STATUS_OK) {                                      \
+            fprintf(stderr, "%s failed: %s\n", #call, brs_last_error());    \
+            return 1;                                                       \
+        }                                                                   \
+    } while (0)
+
+#define EXPECT(condition)                                                   \
+    do {                                                                    \
+        if (!(condition)) {                                                 \
+            fprintf(stderr, "expected %s\n", #condition);                   \
+            return 1;                                                       \
+        }                                                                   \
+    } while (0)
+
+static int check_save(BrsReader *reader) {
+    const BrsHeaders *headers = brs_reader_headers(reader);
+    EXPECT(strcmp(headers->map, "Smoke") == 0);
+    EXPECT(strcmp(headers->author.name, "Tester") == 0);
+    EXPECT(headers->brick_count == 2);
+    EXPECT(headers->brick_asset_count == 1);
+    EXPECT(strcmp(headers->brick_assets[0], "PB_DefaultBrick") == 0);
+    EXPECT(headers->color_count == 1 && headers->colors[0] == 0xFFFF0000);
+    EXPECT(headers->brick_owner_count == 1);
+
+    BrsBrick brick;
+    BrsStatus status;
+    int count = 0;
+    while ((status = brs_reader_next_brick(reader, &brick)) == BRS_STATUS_OK) {
+        EXPECT(brick.size[0] == 5 && brick.size[2] == 6);
+        EXPECT(brick.position[0] == count * 10);
+        EXPECT(brick.direction == BRS_DIRECTION_Z_POSITIVE);
+        EXPECT(brick.rotation == (count ? BRS_ROTATION_DEG_90 : BRS_ROTATION_DEG_0));
+        EXPECT(!brick.custom_color && brick.color == 0);
+        EXPECT(brick.has_owner == (count == 1));
+        count++;
+    }
+    EXPECT(status == BRS_STATUS_END);
+    EXPECT(count == 2);
+    return 0;
+}
+
+int main(int argc, char **argv) {
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s <save path>\n", argv[0]);
+        return 2;
+    }
+
+    BrsSave *save = brs_save_new();
+    EXPECT(save != NULL);
+    CHECK(brs_save_set_map(save, "Smoke"));
+    BrsUser author = {{1}, "Tester"};
+    CHECK(brs_save_set_author(save, &author));
+    CHECK(brs_save_set_save_time(save, 1600000000));
+
+    uint32_t asset, color, material, owner;
+    CHECK(brs_save_add_brick_asset(save, "PB_DefaultBrick", &asset));
+    CHECK(brs_save_add_color(save, 0xFFFF0000, &color));
+    CHECK(brs_save_add_material(save, "BMC_Plastic", &material));
+    BrsUser builder = {{2}, "Builder"};
+    CHECK(brs_save_add_brick_owner(save, &builder, &owner));
+
+    BrsBrick brick;
+    memset(&brick, 0, sizeof brick);
+    brick.asset_name_index = asset;
+    brick.size[0] = brick.size[1] = 5;
+    brick.size[2] = 6;
+    brick.direction = BRS_DIRECTION_Z_POSITIVE;
+    brick.rotation = BRS_ROTATION_DEG_0;
+    brick.collision = brick.visibility = true;
+    brick.material_index = material;
+    brick.color = color;
+    CHECK(brs_save_add_brick(save, &brick));
+    brick.position[0] = 10;
+    brick.rotation = BRS_ROTATION_DEG_90;
+    brick.has_owner = true;
+    brick.owner_index = owner;
+    CHECK(brs_save_add_brick(save, &brick));
+
+    /* Bad input is reported rather than written. */
+    brick.direction = 6;
+    EXPECT(brs_save_add_brick(save, &brick) == BRS_STATUS_INVALID_INPUT);
+    EXPECT(brs_last_error() != NULL);
+
+    BrsBuffer buffer;
+    CHECK(brs_save_write_buffer(save, &buffer));
+    CHECK(brs_save_write_path(save, argv[1]));
+    brs_save_free(save);
+
+    BrsReader *reader;
+    CHECK(brs_reader_open_buffer(buffer.data, buffer.len, &reader));
+    brs_buffer_free(buffer);
+    if (check_save(reader)) {
+        return 1;
+    }
+    brs_reader_free(reader);
+
+    CHECK(brs_reader_open_path(argv[1], &reader));
+    if (check_save(reader)) {
+        return 1;
+    }
+    brs_reader_free(reader);
+
+    EXPECT(brs_reader_open_buffer((const uint8_t *)"BRS", 3, &reader) != BRS_STATUS_OK);
+    return 0;
+}
@@ -0,0 +1,29 @@
+//! Reads arbitrary bytes as a save, through both headers and all bricks.
+//! Reading may fail, but must not panic or use unbounded memory.
+
+#![no_main]
+
+use brs::read::Limits;
+use libfuzzer_sys::fuzz_target;
+use std::io;
+
+// Small enough that a single input can't run the fuzzer out of memory.
+const LIMITS: Limits = Limits {
+    max_string_len: 1 << 16,
+    max_table_len: 1 << 16,
+    max_brick_count: 1 << 20,
+    max_section_size: 1 << 24,
+};
+
+fn read(data: &[u8]) -> io::Result<()> {
+    let reader = brs::Reader::with_limits(data, LIMITS)?;
+    let reader = reader.read_header1()?.read_header2()?;
+    for brick in reader.iter_bricks()? {
+        brick?;
+    }
+    Ok(())
+}
+
+fuzz_target!(|data: &[u8]| {
+    let _ = read(data);
+});
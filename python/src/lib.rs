@@ -0,0 +1,447 @@
+//! Python bindings for reading and writing Brickadia save files.
+//!
+//! The module is named `brs` and provides two functions:
+//!
+//! - `brs.read(path, numpy=False)` returns a save as a dict of its headers,
+//!   with its bricks in `"bricks"`. Bricks are a list of dicts, or with
+//!   `numpy=True` a dict of numpy arrays with one row per brick.
+//! - `brs.write(path, data)` writes a dict of the same shape to a save file.
+//!   Its bricks can be given either way, and their indices must refer to
+//!   entries of the tables.
+//!
+//! Field names match the JSON output of the `brs` command-line tool.
+//! User IDs are `uuid.UUID`s, the save time is a `datetime` and colors are
+//! `(r, g, b, a)` tuples. A brick's `"color"` is an index into `"colors"`
+//! or a custom color tuple.
+//!
+//! As numpy arrays, bricks have these columns:
+//!
+//! | Column            | Type    | Shape    |
+//! |-------------------|---------|----------|
+//! | `asset_name_index`| uint32  | (n,)     |
+//! | `size`            | uint32  | (n, 3)   |
+//! | `position`        | int32   | (n, 3)   |
+//! | `direction`       | uint8   | (n,)     |
+//! | `rotation`        | uint8   | (n,)     |
+//! | `collision`       | bool    | (n,)     |
+//! | `visibility`      | bool    | (n,)     |
+//! | `material_index`  | uint32  | (n,)     |
+//! | `color`           | uint8   | (n, 4)   |
+//! | `color_index`     | int64   | (n,)     |
+//! | `owner_index`     | int64   | (n,)     |
+//!
+//! `color` holds the RGBA color of each brick, looked up in the color table
+//! where needed, and `color_index` its index in the table or -1 for custom
+//! colors. `owner_index` is -1 for bricks without an owner. Directions and
+//! rotations are numbered in the order of `brs::Direction` and
+//! `brs::Rotation`.
+
+use brs::{
+    chrono::{DateTime, Utc},
+    uuid::Uuid,
+    Brick, Color, ColorMode, Direction, HasHeader1, HasHeader2, Reader, Rotation, User, WriteData,
+};
+use numpy::{PyArray1, PyArrayMethods};
+use pyo3::{
+    conversion::FromPyObjectOwned,
+    exceptions::PyValueError,
+    prelude::*,
+    types::{PyDict, PyList},
+};
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+/// Turn invalid files and data into `ValueError`s, and other errors
+/// into `OSError`s.
+fn to_py_err(error: io::Error) -> PyErr {
+    match error.kind() {
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof | io::ErrorKind::InvalidInput => {
+            PyValueError::new_err(error.to_string())
+        }
+        _ => error.into(),
+    }
+}
+
+fn user_dict<'py>(py: Python<'py>, user: &User) -> PyResult<Bound<'py, PyDict>> {
+    let uuid = py.import("uuid")?.getattr("UUID")?;
+    let dict = PyDict::new(py);
+    dict.set_item("id", uuid.call1((user.id.to_string(),))?)?;
+    dict.set_item("name", &user.name)?;
+    Ok(dict)
+}
+
+fn color_tuple(color: Color) -> (u8, u8, u8, u8) {
+    (color.r(), color.g(), color.b(), color.a())
+}
+
+fn direction_name(direction: Direction) -> String {
+    format!("{:?}", direction)
+}
+
+fn rotation_name(rotation: Rotation) -> String {
+    format!("{:?}", rotation)
+}
+
+fn brick_dict<'py>(py: Python<'py>, brick: &Brick) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("asset_name_index", brick.asset_name_index)?;
+    dict.set_item("size", brick.size)?;
+    dict.set_item("position", brick.position)?;
+    dict.set_item("direction", direction_name(brick.direction))?;
+    dict.set_item("rotation", rotation_name(brick.rotation))?;
+    dict.set_item("collision", brick.collision)?;
+    dict.set_item("visibility", brick.visibility)?;
+    dict.set_item("material_index", brick.material_index)?;
+    match brick.color {
+        ColorMode::Set(index) => dict.set_item("color", index)?,
+        ColorMode::Custom(color) => dict.set_item("color", color_tuple(color))?,
+    }
+    dict.set_item("owner_index", brick.owner_index)?;
+    Ok(dict)
+}
+
+fn brick_arrays<'py>(
+    py: Python<'py>,
+    bricks: &[Brick],
+    palette: &[Color],
+) -> PyResult<Bound<'py, PyDict>> {
+    // Raise an `ImportError` if numpy is missing, rather than panicking.
+    py.import("numpy")?;
+    let n = bricks.len();
+    let dict = PyDict::new(py);
+    let column = |f: fn(&Brick) -> u32| bricks.iter().map(f).collect::<Vec<_>>();
+
+    dict.set_item(
+        "asset_name_index",
+        PyArray1::from_vec(py, column(|b| b.asset_name_index)),
+    )?;
+    let sizes = bricks
+        .iter()
+        .flat_map(|b| [b.size.0, b.size.1, b.size.2])
+        .collect::<Vec<_>>();
+    dict.set_item("size", PyArray1::from_vec(py, sizes).reshape([n, 3])?)?;
+    let positions = bricks
+        .iter()
+        .flat_map(|b| [b.position.0, b.position.1, b.position.2])
+        .collect::<Vec<_>>();
+    dict.set_item(
+        "position",
+        PyArray1::from_vec(py, positions).reshape([n, 3])?,
+    )?;
+    let directions = bricks.iter().map(|b| u8::from(b.direction)).collect();
+    dict.set_item("direction", PyArray1::<u8>::from_vec(py, directions))?;
+    let rotations = bricks.iter().map(|b| u8::from(b.rotation)).collect();
+    dict.set_item("rotation", PyArray1::<u8>::from_vec(py, rotations))?;
+    let collision = bricks.iter().map(|b| b.collision).collect();
+    dict.set_item("collision", PyArray1::<bool>::from_vec(py, collision))?;
+    let visibility = bricks.iter().map(|b| b.visibility).collect();
+    dict.set_item("visibility", PyArray1::<bool>::from_vec(py, visibility))?;
+    dict.set_item(
+        "material_index",
+        PyArray1::from_vec(py, column(|b| b.material_index)),
+    )?;
+
+    let colors = bricks
+        .iter()
+        .flat_map(|b| {
+            let color = match b.color {
+                ColorMode::Set(index) => palette.get(index as usize).copied(),
+                ColorMode::Custom(color) => Some(color),
+            };
+            color.map_or([0; 4], <[u8; 4]>::from)
+        })
+        .collect::<Vec<_>>();
+    dict.set_item("color", PyArray1::from_vec(py, colors).reshape([n, 4])?)?;
+    let color_indices = bricks
+        .iter()
+        .map(|b| match b.color {
+            ColorMode::Set(index) => i64::from(index),
+            ColorMode::Custom(_) => -1,
+        })
+        .collect();
+    dict.set_item("color_index", PyArray1::<i64>::from_vec(py, color_indices))?;
+    let owner_indices = bricks
+        .iter()
+        .map(|b| b.owner_index.map_or(-1, i64::from))
+        .collect();
+    dict.set_item("owner_index", PyArray1::<i64>::from_vec(py, owner_indices))?;
+    Ok(dict)
+}
+
+/// Read the save file at `path`.
+#[pyfunction]
+#[pyo3(signature = (path, numpy = false))]
+fn read<'py>(py: Python<'py>, path: PathBuf, numpy: bool) -> PyResult<Bound<'py, PyDict>> {
+    let (version, reader, bricks) = py
+        .detach(|| {
+            let reader = Reader::new(BufReader::new(File::open(&path)?))?;
+            let version = reader.version();
+            let (reader, bricks) = reader
+                .read_header1()?
+                .read_header2()?
+                .iter_bricks_and_reader()?;
+            let bricks = bricks.collect::<io::Result<Vec<_>>>()?;
+            Ok((version, reader, bricks))
+        })
+        .map_err(to_py_err)?;
+
+    let dict = PyDict::new(py);
+    dict.set_item("version", u16::from(version))?;
+    dict.set_item("map", reader.map())?;
+    dict.set_item("author", user_dict(py, reader.author())?)?;
+    dict.set_item("description", reader.description())?;
+    let host = match &reader.header1.host {
+        Some(host) => Some(user_dict(py, host)?),
+        None => None,
+    };
+    dict.set_item("host", host)?;
+    dict.set_item("save_time", reader.save_time())?;
+    dict.set_item("brick_count", reader.brick_count())?;
+    dict.set_item("mods", reader.mods())?;
+    dict.set_item("brick_assets", reader.brick_assets())?;
+    let colors = reader.colors().iter().map(|&c| color_tuple(c));
+    dict.set_item("colors", PyList::new(py, colors)?)?;
+    dict.set_item("materials", reader.materials())?;
+    let owners = reader
+        .brick_owners()
+        .iter()
+        .map(|owner| user_dict(py, owner))
+        .collect::<PyResult<Vec<_>>>()?;
+    dict.set_item("brick_owners", owners)?;
+
+    if numpy {
+        dict.set_item("bricks", brick_arrays(py, &bricks, reader.colors())?)?;
+    } else {
+        let bricks = bricks
+            .iter()
+            .map(|brick| brick_dict(py, brick))
+            .collect::<PyResult<Vec<_>>>()?;
+        dict.set_item("bricks", bricks)?;
+    }
+    Ok(dict)
+}
+
+/// The value of `key` in `dict`, or `None` if it's missing or `None`.
+fn optional<'py, T: FromPyObjectOwned<'py>>(
+    dict: &Bound<'py, PyDict>,
+    key: &str,
+) -> PyResult<Option<T>> {
+    match dict.get_item(key)? {
+        Some(value) if !value.is_none() => Ok(Some(value.extract().map_err(Into::into)?)),
+        _ => Ok(None),
+    }
+}
+
+fn required<'py, T: FromPyObjectOwned<'py>>(dict: &Bound<'py, PyDict>, key: &str) -> PyResult<T> {
+    optional(dict, key)?.ok_or_else(|| PyValueError::new_err(format!("Missing `{}`", key)))
+}
+
+fn to_user(value: Option<Bound<'_, PyDict>>) -> PyResult<User> {
+    let value = match value {
+        Some(value) => value,
+        None => {
+            return Ok(User {
+                id: Uuid::nil(),
+                name: String::new(),
+            })
+        }
+    };
+    let id = match value.get_item("id")? {
+        Some(id) if !id.is_none() => Uuid::parse_str(&id.str()?.to_cow()?)
+            .map_err(|_| PyValueError::new_err("Invalid user ID"))?,
+        _ => Uuid::nil(),
+    };
+    Ok(User {
+        id,
+        name: optional(&value, "name")?.unwrap_or_default(),
+    })
+}
+
+fn to_color(color: (u8, u8, u8, u8)) -> Color {
+    Color::from_rgba(color.0, color.1, color.2, color.3)
+}
+
+fn parse_direction(name: &str) -> PyResult<Direction> {
+    (0..6)
+        .filter_map(|d| Direction::try_from(d).ok())
+        .find(|&d| direction_name(d) == name)
+        .ok_or_else(|| PyValueError::new_err(format!("Invalid direction {:?}", name)))
+}
+
+fn parse_rotation(name: &str) -> PyResult<Rotation> {
+    (0..4)
+        .filter_map(|r| Rotation::try_from(r).ok())
+        .find(|&r| rotation_name(r) == name)
+        .ok_or_else(|| PyValueError::new_err(format!("Invalid rotation {:?}", name)))
+}
+
+fn to_brick(dict: &Bound<'_, PyDict>) -> PyResult<Brick> {
+    let color = match dict.get_item("color")? {
+        Some(color) if !color.is_none() => match color.extract::<u32>() {
+            Ok(index) => ColorMode::Set(index),
+            Err(_) => ColorMode::Custom(to_color(color.extract()?)),
+        },
+        _ => ColorMode::Set(0),
+    };
+    let direction = optional::<String>(dict, "direction")?;
+    let rotation = optional::<String>(dict, "rotation")?;
+    Ok(Brick {
+        asset_name_index: optional(dict, "asset_name_index")?.unwrap_or(0),
+        size: required(dict, "size")?,
+        position: required(dict, "position")?,
+        direction: direction.map_or(Ok(Direction::ZPositive), |d| parse_direction(&d))?,
+        rotation: rotation.map_or(Ok(Rotation::Deg0), |r| parse_rotation(&r))?,
+        collision: optional(dict, "collision")?.unwrap_or(true),
+        visibility: optional(dict, "visibility")?.unwrap_or(true),
+        material_index: optional(dict, "material_index")?.unwrap_or(0),
+        color,
+        owner_index: optional(dict, "owner_index")?,
+    })
+}
+
+/// The column `key` of bricks given as arrays, which must have `n` rows
+/// if given. Numpy arrays are converted to lists first.
+fn column<'py, T: FromPyObjectOwned<'py>>(
+    columns: &Bound<'py, PyDict>,
+    key: &str,
+    n: Option<usize>,
+) -> PyResult<Option<Vec<T>>> {
+    let value = match columns.get_item(key)? {
+        Some(value) if !value.is_none() => value,
+        _ => return Ok(None),
+    };
+    let value = if value.hasattr("tolist")? {
+        value.call_method0("tolist")?
+    } else {
+        value
+    };
+    let values = value.extract::<Vec<T>>()?;
+    match n {
+        Some(n) if values.len() != n => Err(PyValueError::new_err(format!(
+            "Column `{}` has {} rows instead of {}",
+            key,
+            values.len(),
+            n
+        ))),
+        _ => Ok(Some(values)),
+    }
+}
+
+fn to_bricks_from_columns(columns: &Bound<'_, PyDict>) -> PyResult<Vec<Brick>> {
+    let missing = |key: &str| PyValueError::new_err(format!("Missing column `{}`", key));
+    let positions =
+        column::<[i32; 3]>(columns, "position", None)?.ok_or_else(|| missing("position"))?;
+    let n = Some(positions.len());
+    let sizes = column::<[u32; 3]>(columns, "size", n)?.ok_or_else(|| missing("size"))?;
+    let assets = column::<u32>(columns, "asset_name_index", n)?;
+    let directions = column::<u8>(columns, "direction", n)?;
+    let rotations = column::<u8>(columns, "rotation", n)?;
+    let collision = column::<bool>(columns, "collision", n)?;
+    let visibility = column::<bool>(columns, "visibility", n)?;
+    let materials = column::<u32>(columns, "material_index", n)?;
+    let colors = column::<[u8; 4]>(columns, "color", n)?;
+    let color_indices = column::<i64>(columns, "color_index", n)?;
+    let owners = column::<i64>(columns, "owner_index", n)?;
+
+    let invalid = |what: &str| PyValueError::new_err(format!("Invalid {} in bricks", what));
+    (0..positions.len())
+        .map(|i| {
+            let color = match (color_indices.as_ref().map(|c| c[i]), &colors) {
+                (Some(index), _) if index >= 0 => {
+                    ColorMode::Set(u32::try_from(index).map_err(|_| invalid("color index"))?)
+                }
+                (_, Some(colors)) => ColorMode::Custom(Color::from(colors[i])),
+                (Some(_), None) => return Err(missing("color")),
+                (None, None) => ColorMode::Set(0),
+            };
+            let owner_index = match owners.as_ref().map(|o| o[i]) {
+                Some(index) if index >= 0 => {
+                    Some(u32::try_from(index).map_err(|_| invalid("owner index"))?)
+                }
+                _ => None,
+            };
+            let [sx, sy, sz] = sizes[i];
+            let [x, y, z] = positions[i];
+            Ok(Brick {
+                asset_name_index: assets.as_ref().map_or(0, |a| a[i]),
+                size: (sx, sy, sz),
+                position: (x, y, z),
+                direction: match &directions {
+                    Some(d) => Direction::try_from(d[i]).map_err(|_| invalid("direction"))?,
+                    None => Direction::ZPositive,
+                },
+                rotation: match &rotations {
+                    Some(r) => Rotation::try_from(r[i]).map_err(|_| invalid("rotation"))?,
+                    None => Rotation::Deg0,
+                },
+                collision: collision.as_ref().is_none_or(|c| c[i]),
+                visibility: visibility.as_ref().is_none_or(|v| v[i]),
+                material_index: materials.as_ref().map_or(0, |m| m[i]),
+                color,
+                owner_index,
+            })
+        })
+        .collect()
+}
+
+/// Write `data`, shaped like the result of `read`, to a save file at `path`.
+/// Only the bricks' sizes and positions are required. Missing strings and
+/// tables are left empty, and `save_time` defaults to the current time.
+///
+/// Missing asset, material and color indices default to 0, so those tables
+/// need at least one entry then. A `ValueError` names the first brick with
+/// an index outside its table, and nothing is written.
+#[pyfunction]
+fn write(py: Python<'_>, path: PathBuf, data: &Bound<'_, PyDict>) -> PyResult<()> {
+    let bricks = match data.get_item("bricks")? {
+        Some(bricks) if bricks.is_instance_of::<PyDict>() => {
+            to_bricks_from_columns(&bricks.extract()?)?
+        }
+        Some(bricks) if !bricks.is_none() => bricks
+            .extract::<Vec<Bound<'_, PyDict>>>()?
+            .iter()
+            .map(to_brick)
+            .collect::<PyResult<_>>()?,
+        _ => Vec::new(),
+    };
+    let colors = optional::<Vec<(u8, u8, u8, u8)>>(data, "colors")?.unwrap_or_default();
+    let owners = optional::<Vec<Bound<'_, PyDict>>>(data, "brick_owners")?.unwrap_or_default();
+
+    let data = WriteData {
+        map: optional(data, "map")?.unwrap_or_default(),
+        author: to_user(optional(data, "author")?)?,
+        description: optional(data, "description")?.unwrap_or_default(),
+        save_time: optional::<DateTime<Utc>>(data, "save_time")?.unwrap_or_else(Utc::now),
+        mods: optional(data, "mods")?.unwrap_or_default(),
+        brick_assets: optional(data, "brick_assets")?.unwrap_or_default(),
+        colors: colors.into_iter().map(to_color).collect(),
+        materials: optional(data, "materials")?.unwrap_or_default(),
+        brick_owners: owners
+            .into_iter()
+            .map(|owner| to_user(Some(owner)))
+            .collect::<PyResult<_>>()?,
+        bricks,
+    };
+
+    data.check_indices().map_err(to_py_err)?;
+
+    py.detach(|| {
+        let mut w = BufWriter::new(File::create(&path)?);
+        brs::write_save(&mut w, &data)?;
+        w.flush()
+    })
+    .map_err(to_py_err)
+}
+
+/// Read and write Brickadia save files.
+#[pymodule]
+#[pyo3(name = "brs")]
+fn brs_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(read, m)?)?;
+    m.add_function(wrap_pyfunction!(write, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,224 @@
+//! Conversion of bricks to Apache Arrow record batches and Parquet files,
+//! for querying saves with analytics tools.
+//!
+//! Each brick becomes one row. Its asset, material and owner are dictionary
+//! encoded, with the lookup tables of the save as dictionaries, so the names
+//! are stored once per batch rather than once per brick. See
+//! [`schema`](fn.schema.html) for all columns.
+//!
+//! ```no_run
+//! # use std::fs::File;
+//! let reader = brs::Reader::new(File::open("village.brs")?)?;
+//! let batch = brs::arrow::from_reader(reader.read_header1()?.read_header2()?)?;
+//! brs::arrow::write_parquet(File::create("village.parquet")?, &[batch])?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use crate::{
+    read::{Header2, ReaderAfterHeader2},
+    BrickColumns, Color, ColorMode, User, WriteData,
+};
+use arrow_array::{
+    types::UInt32Type, ArrayRef, BooleanArray, DictionaryArray, Int32Array, RecordBatch,
+    StringArray, UInt32Array, UInt8Array,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use std::{
+    io::{self, Read, Write},
+    sync::Arc,
+};
+
+fn dictionary() -> DataType {
+    DataType::Dictionary(Box::new(DataType::UInt32), Box::new(DataType::Utf8))
+}
+
+/// The schema of all record batches created by this module.
+///
+/// | Column              | Type                  | Nullable | Contents                   |
+/// |---------------------|-----------------------|----------|----------------------------|
+/// | `asset`             | dictionary of strings | no       | Brick asset name           |
+/// | `size_x`, `_y`, `_z`| `UInt32`              | no       | Size along each axis       |
+/// | `x`, `y`, `z`       | `Int32`               | no       | Position of the center     |
+/// | `direction`         | `UInt8`               | no       | `Direction` as a number    |
+/// | `rotation`          | `UInt8`               | no       | `Rotation` as a number     |
+/// | `collision`         | `Boolean`             | no       |                            |
+/// | `visibility`        | `Boolean`             | no       |                            |
+/// | `material`          | dictionary of strings | no       | Material name              |
+/// | `color`             | `UInt32`              | no       | Color as `0xAARRGGBB`      |
+/// | `color_index`       | `UInt32`              | yes      | Index in the color table   |
+/// | `owner`             | dictionary of strings | yes      | Owner name                 |
+/// | `owner_id`          | dictionary of strings | yes      | Owner UUID                 |
+///
+/// `color_index` is null for bricks with custom colors, and the owner
+/// columns for bricks without an owner. Sizes are half extents, as in [`Brick`](../struct.Brick.html).
+pub fn schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("asset", dictionary(), false),
+        Field::new("size_x", DataType::UInt32, false),
+        Field::new("size_y", DataType::UInt32, false),
+        Field::new("size_z", DataType::UInt32, false),
+        Field::new("x", DataType::Int32, false),
+        Field::new("y", DataType::Int32, false),
+        Field::new("z", DataType::Int32, false),
+        Field::new("direction", DataType::UInt8, false),
+        Field::new("rotation", DataType::UInt8, false),
+        Field::new("collision", DataType::Boolean, false),
+        Field::new("visibility", DataType::Boolean, false),
+        Field::new("material", dictionary(), false),
+        Field::new("color", DataType::UInt32, false),
+        Field::new("color_index", DataType::UInt32, true),
+        Field::new("owner", dictionary(), true),
+        Field::new("owner_id", dictionary(), true),
+    ]))
+}
+
+/// Read the bricks of a save into a record batch, consuming the reader.
+pub fn from_reader<R: Read>(reader: ReaderAfterHeader2<R>) -> io::Result<RecordBatch> {
+    let (reader, bricks) = reader.iter_bricks_and_reader()?;
+    from_columns(&reader.header2, &bricks.read_columns()?)
+}
+
+/// Convert `bricks` to a record batch, using the lookup tables in `header2`.
+///
+/// Returns an `InvalidData` error if a brick refers to an entry that
+/// isn't in the tables.
+pub fn from_columns(header2: &Header2, bricks: &BrickColumns) -> io::Result<RecordBatch> {
+    batch(
+        &header2.brick_assets,
+        &header2.materials,
+        &header2.colors,
+        &header2.brick_owners,
+        bricks,
+    )
+}
+
+/// Convert the bricks of `data` to a record batch.
+///
+/// Returns an `InvalidData` error if a brick refers to an entry that
+/// isn't in the tables.
+pub fn from_write_data(data: &WriteData) -> io::Result<RecordBatch> {
+    batch(
+        &data.brick_assets,
+        &data.materials,
+        &data.colors,
+        &data.brick_owners,
+        &BrickColumns::from(&data.bricks[..]),
+    )
+}
+
+fn arrow_error(error: ArrowError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+/// A dictionary array of `keys` into `values`.
+fn dictionary_array(
+    keys: UInt32Array,
+    values: impl IntoIterator<Item = impl AsRef<str>>,
+) -> io::Result<ArrayRef> {
+    let values = StringArray::from_iter_values(values);
+    let array =
+        DictionaryArray::<UInt32Type>::try_new(keys, Arc::new(values)).map_err(arrow_error)?;
+    Ok(Arc::new(array))
+}
+
+fn batch(
+    assets: &[String],
+    materials: &[String],
+    colors: &[Color],
+    owners: &[User],
+    bricks: &BrickColumns,
+) -> io::Result<RecordBatch> {
+    bricks.check_lengths()?;
+    let n = bricks.len();
+
+    let size = |axis: fn(&(u32, u32, u32)) -> u32| -> ArrayRef {
+        Arc::new(bricks.sizes.iter().map(axis).collect::<UInt32Array>())
+    };
+    let position = |axis: fn(&(i32, i32, i32)) -> i32| -> ArrayRef {
+        Arc::new(bricks.positions.iter().map(axis).collect::<Int32Array>())
+    };
+    let flag = |flag: u8| -> ArrayRef {
+        Arc::new(
+            bricks
+                .flags
+                .iter()
+                .map(|f| Some(f & flag != 0))
+                .collect::<BooleanArray>(),
+        )
+    };
+
+    let mut color_values = Vec::with_capacity(n);
+    let mut color_indices = Vec::with_capacity(n);
+    for i in 0..n {
+        let (color, index) = match bricks.color(i) {
+            ColorMode::Set(index) => {
+                let color = colors.get(index as usize).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "Brick color index out of range")
+                })?;
+                (u32::from(*color), Some(index))
+            }
+            ColorMode::Custom(color) => (u32::from(color), None),
+        };
+        color_values.push(color);
+        color_indices.push(index);
+    }
+    let owner_indices = (0..n)
+        .map(|i| bricks.owner_index(i))
+        .collect::<UInt32Array>();
+
+    let columns = vec![
+        dictionary_array(UInt32Array::from(bricks.asset_name_indices.clone()), assets)?,
+        size(|s| s.0),
+        size(|s| s.1),
+        size(|s| s.2),
+        position(|p| p.0),
+        position(|p| p.1),
+        position(|p| p.2),
+        Arc::new(
+            bricks
+                .orientations
+                .iter()
+                .map(|o| u8::from(o.direction))
+                .collect::<UInt8Array>(),
+        ),
+        Arc::new(
+            bricks
+                .orientations
+                .iter()
+                .map(|o| u8::from(o.rotation))
+                .collect::<UInt8Array>(),
+        ),
+        flag(BrickColumns::COLLISION),
+        flag(BrickColumns::VISIBILITY),
+        dictionary_array(
+            UInt32Array::from(bricks.material_indices.clone()),
+            materials,
+        )?,
+        Arc::new(UInt32Array::from(color_values)),
+        Arc::new(UInt32Array::from(color_indices)),
+        dictionary_array(owner_indices.clone(), owners.iter().map(|o| &o.name))?,
+        dictionary_array(
+            owner_indices,
+            owners.iter().map(|o| o.id.to_hyphenated().to_string()),
+        )?,
+    ];
+    RecordBatch::try_new(schema(), columns).map_err(arrow_error)
+}
+
+/// Write `batches` to `w` as a Snappy-compressed Parquet file.
+///
+/// The batches may come from different saves, and are written as one
+/// row group each.
+pub fn write_parquet<W: Write + Send>(w: W, batches: &[RecordBatch]) -> io::Result<()> {
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(w, schema(), Some(properties))?;
+    for batch in batches {
+        writer.write(batch)?;
+        writer.flush()?;
+    }
+    writer.close()?;
+    Ok(())
+}
@@ -0,0 +1,684 @@
+//! Command-line tool for inspecting and editing Brickadia save files.
+//!
+//! Exits with status 1 on errors or when `validate` finds problems,
+//! and 2 on invalid usage.
+
+use brs::{
+    bls::{self, BlsMapping},
+    diff::{HeaderField, TableDiff},
+    export::{self, ExportOptions},
+    ldraw::{self, LdrawOptions},
+    optimize::{self, MergeOptions},
+    overlap,
+    read::{Header1, Header2, ReaderAfterHeader2},
+    render::{self, RenderOptions, View},
+    schem::{self, SchemOptions},
+    stats::Stats,
+    vox::{self, VoxOptions},
+    ColorMode, HasHeader1, HasHeader2, Orientation, Reader, User, Version, WriteData,
+};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    ffi::OsStr,
+    fs::{self, File},
+    io::{self, prelude::*, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Parser)]
+#[command(
+    name = "brs",
+    version,
+    about = "Inspect and edit Brickadia save files."
+)]
+struct Cli {
+    /// Print output as JSON instead of text.
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show the headers of a save.
+    Info { path: PathBuf },
+    /// Print the headers and bricks of a save.
+    Dump {
+        path: PathBuf,
+        /// Only print the first N bricks.
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+    },
+    /// Convert between .brs, .json and Blockland .bls saves, import
+    /// MagicaVoxel .vox models, .schem schematics and LDraw .ldr and .mpd
+    /// models or export .obj and .glb models and, when built with the `arrow`
+    /// feature, .parquet tables of bricks, based on the file extensions.
+    Convert { input: PathBuf, output: PathBuf },
+    /// Check that a save can be read and that all bricks are valid.
+    Validate { path: PathBuf },
+    /// Summarize the contents of a save.
+    Stats { path: PathBuf },
+    /// Show what changed between two saves.
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        /// List every added, removed and modified brick.
+        #[arg(long)]
+        bricks: bool,
+    },
+    /// Combine several saves into one. Headers are taken from the first save.
+    Merge {
+        /// Where to write the merged save.
+        #[arg(short, long)]
+        output: PathBuf,
+        #[arg(required = true, num_args = 1..)]
+        inputs: Vec<PathBuf>,
+    },
+    /// Move or rotate all bricks of a save.
+    ///
+    /// Rotation happens first, then centering, then translation.
+    Transform {
+        input: PathBuf,
+        output: PathBuf,
+        /// Rotate around the origin, given as DIRECTION:ROTATION,
+        /// such as `ZPositive:Deg90`.
+        #[arg(long, value_parser = parse_orientation)]
+        rotate: Option<Orientation>,
+        /// Move the build so that it's centered on the origin and rests on the ground.
+        #[arg(long)]
+        center: bool,
+        /// Move all bricks by X,Y,Z.
+        #[arg(long, value_parser = parse_vector, allow_hyphen_values = true)]
+        translate: Option<(i32, i32, i32)>,
+    },
+    /// Merge adjacent matching bricks into larger ones.
+    Optimize { input: PathBuf, output: PathBuf },
+    /// Render a save to a PNG image.
+    Render {
+        input: PathBuf,
+        output: PathBuf,
+        /// Look straight down instead of from an angle.
+        #[arg(long)]
+        top_down: bool,
+        /// Image width in pixels, up to 16384.
+        #[arg(long, default_value_t = 512, value_parser = clap::value_parser!(u32).range(1..=16384))]
+        width: u32,
+        /// Image height in pixels, up to 16384.
+        #[arg(long, default_value_t = 512, value_parser = clap::value_parser!(u32).range(1..=16384))]
+        height: u32,
+    },
+    /// Rewrite a save using the latest format version.
+    Recompress { input: PathBuf, output: PathBuf },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::from(1)
+        }
+    }
+}
+
+/// Run a command, returning whether it succeeded.
+fn run(cli: Cli) -> Result<bool> {
+    let json = cli.json;
+    match cli.command {
+        Command::Info { path } => info(&path, json)?,
+        Command::Dump { path, limit } => dump(&path, limit, json)?,
+        Command::Convert { input, output } => {
+            let data = load(&input)?;
+            save(&output, &data)?;
+        }
+        Command::Validate { path } => return validate(&path, json),
+        Command::Stats { path } => stats(&path, json)?,
+        Command::Diff { old, new, bricks } => diff(&old, &new, bricks, json)?,
+        Command::Merge { output, inputs } => {
+            let mut inputs = inputs.iter();
+            let mut data = load(inputs.next().unwrap())?;
+            for path in inputs {
+                data.merge(&load(path)?)?;
+            }
+            save(&output, &data)?;
+        }
+        Command::Transform {
+            input,
+            output,
+            rotate,
+            center,
+            translate,
+        } => {
+            let mut data = load(&input)?;
+            if let Some(orientation) = rotate {
+                data.rotate(orientation)?;
+            }
+            if center {
+                if let Some(bounds) = data.bounds() {
+                    let (x, y, _) = bounds.center();
+                    let offset = (x.checked_neg(), y.checked_neg(), bounds.min.2.checked_neg());
+                    match offset {
+                        (Some(x), Some(y), Some(z)) => data.translate((x, y, z))?,
+                        _ => return Err("build is too far from the origin to center".into()),
+                    }
+                }
+            }
+            if let Some(offset) = translate {
+                data.translate(offset)?;
+            }
+            save(&output, &data)?;
+        }
+        Command::Optimize { input, output } => {
+            let mut data = load(&input)?;
+            let report = optimize::merge_bricks(&mut data, &MergeOptions::default());
+            save(&output, &data)?;
+            if json {
+                print_json(&report)?;
+            } else {
+                println!("{} -> {} bricks", report.before, report.after);
+            }
+        }
+        Command::Render {
+            input,
+            output,
+            top_down,
+            width,
+            height,
+        } => {
+            let data = load(&input)?;
+            let options = RenderOptions {
+                width,
+                height,
+                view: if top_down {
+                    View::TopDown
+                } else {
+                    View::Isometric
+                },
+                ..Default::default()
+            };
+            let mut buf = Vec::new();
+            render::render(&data, &options).write_png(&mut buf)?;
+            fs::write(&output, &buf).map_err(|e| format!("{}: {}", output.display(), e))?;
+        }
+        Command::Recompress { input, output } => {
+            let before = fs::metadata(&input)?.len();
+            let data = load(&input)?;
+            let after = save(&output, &data)?;
+            if json {
+                print_json(&serde_json::json!({ "before": before, "after": after }))?;
+            } else {
+                println!("{} -> {} bytes", before, after);
+            }
+        }
+    }
+    Ok(true)
+}
+
+fn parse_orientation(s: &str) -> std::result::Result<Orientation, String> {
+    Orientation::all()
+        .find(|o| format!("{:?}:{:?}", o.direction, o.rotation).eq_ignore_ascii_case(s))
+        .ok_or_else(|| "expected DIRECTION:ROTATION, such as ZPositive:Deg90".to_string())
+}
+
+fn parse_vector(s: &str) -> std::result::Result<(i32, i32, i32), String> {
+    let parts = s
+        .split(',')
+        .map(|p| p.trim().parse::<i32>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    match parts[..] {
+        [x, y, z] => Ok((x, y, z)),
+        _ => Err("expected X,Y,Z".to_string()),
+    }
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension() == Some(OsStr::new(extension))
+}
+
+/// Open a save file and read both headers.
+fn open(path: &Path) -> Result<(Version, ReaderAfterHeader2<BufReader<File>>)> {
+    let read = || -> io::Result<_> {
+        let reader = Reader::new(BufReader::new(File::open(path)?))?;
+        let version = reader.version();
+        Ok((version, reader.read_header1()?.read_header2()?))
+    };
+    read().map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+/// Read a `.brs`, `.json` or `.bls` save, a `.vox`, `.ldr` or `.mpd` model
+/// or a `.schem` schematic, in full.
+fn load(path: &Path) -> Result<WriteData> {
+    if has_extension(path, "json") {
+        let file = BufReader::new(File::open(path)?);
+        return serde_json::from_reader(file)
+            .map_err(|e| format!("{}: {}", path.display(), e).into());
+    }
+    if has_extension(path, "bls") {
+        let file = BufReader::new(File::open(path)?);
+        let import = bls::read(file, &BlsMapping::default())
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        for (name, count) in &import.unmapped {
+            eprintln!("warning: skipped {} unsupported {} bricks", count, name);
+        }
+        return Ok(import.data);
+    }
+    if has_extension(path, "vox") {
+        let file = BufReader::new(File::open(path)?);
+        return vox::read(file, &VoxOptions::default())
+            .map_err(|e| format!("{}: {}", path.display(), e).into());
+    }
+    if has_extension(path, "schem") {
+        let file = BufReader::new(File::open(path)?);
+        let import = schem::read(file, &SchemOptions::default())
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        for (name, count) in &import.unmapped {
+            eprintln!("warning: skipped {} unsupported {} blocks", count, name);
+        }
+        return Ok(import.data);
+    }
+    if has_extension(path, "ldr") || has_extension(path, "mpd") {
+        let file = BufReader::new(File::open(path)?);
+        let import = ldraw::read(file, &LdrawOptions::default())
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        for (name, count) in &import.unmapped {
+            eprintln!("warning: skipped {} unsupported {} parts", count, name);
+        }
+        if import.unaligned > 0 {
+            eprintln!(
+                "warning: skipped {} parts that aren't axis-aligned",
+                import.unaligned
+            );
+        }
+        return Ok(import.data);
+    }
+    open(path)?
+        .1
+        .into_write_data()
+        .map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+/// Write a `.brs`, `.json` or `.bls` save, an `.obj` or `.glb` model or
+/// a `.parquet` table, returning its size in bytes.
+fn save(path: &Path, data: &WriteData) -> Result<usize> {
+    let mut buf = Vec::new();
+    #[cfg(feature = "arrow")]
+    if has_extension(path, "parquet") {
+        brs::arrow::write_parquet(&mut buf, &[brs::arrow::from_write_data(data)?])?;
+        fs::write(path, &buf).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return Ok(buf.len());
+    }
+    if has_extension(path, "obj") {
+        // The material library goes next to the model.
+        let mtl_path = path.with_extension("mtl");
+        let mtl_file = mtl_path.file_name().unwrap().to_string_lossy();
+        let mut mtl = Vec::new();
+        export::write_obj(
+            &mut buf,
+            &mut mtl,
+            &mtl_file,
+            data,
+            &ExportOptions::default(),
+        )?;
+        fs::write(&mtl_path, &mtl).map_err(|e| format!("{}: {}", mtl_path.display(), e))?;
+    } else if has_extension(path, "glb") {
+        export::write_glb(&mut buf, data, &ExportOptions::default())?;
+    } else if has_extension(path, "json") {
+        serde_json::to_writer_pretty(&mut buf, data)?;
+    } else if has_extension(path, "bls") {
+        let skipped = bls::write(&mut buf, data, &BlsMapping::default())?;
+        if !skipped.is_empty() {
+            eprintln!("warning: skipped {} unsupported bricks", skipped.len());
+        }
+    } else {
+        brs::write_save(&mut buf, data)?;
+    }
+    fs::write(path, &buf).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(buf.len())
+}
+
+fn print_json(value: &impl Serialize) -> Result<()> {
+    let stdout = io::stdout();
+    let mut w = BufWriter::new(stdout.lock());
+    serde_json::to_writer_pretty(&mut w, value)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+fn user(user: &User) -> String {
+    format!("{} ({})", user.name, user.id)
+}
+
+fn print_headers(version: Option<Version>, header1: &Header1, header2: &Header2) {
+    if let Some(version) = version {
+        println!("Version:      {} ({:?})", u16::from(version), version);
+    }
+    println!("Map:          {}", header1.map);
+    println!("Author:       {}", user(&header1.author));
+    println!("Description:  {}", header1.description);
+    if let Some(save_time) = header1.save_time {
+        println!("Saved:        {}", save_time);
+    }
+    println!("Bricks:       {}", header1.brick_count);
+    println!("Mods:         {}", header2.mods.join(", "));
+    println!("Brick assets: {}", header2.brick_assets.join(", "));
+    println!("Materials:    {}", header2.materials.join(", "));
+    println!("Colors:       {}", header2.colors.len());
+    println!("Owners:       {}", header2.brick_owners.len());
+    for owner in &header2.brick_owners {
+        println!("  {}", user(owner));
+    }
+}
+
+#[derive(Serialize)]
+struct Headers<'a> {
+    version: Option<u16>,
+    header1: &'a Header1,
+    header2: &'a Header2,
+}
+
+fn info(path: &Path, json: bool) -> Result<()> {
+    let (version, header1, header2) = if has_extension(path, "json") {
+        let data = load(path)?;
+        let (header1, header2) = headers(&data);
+        (None, header1, header2)
+    } else {
+        let (version, reader) = open(path)?;
+        (
+            Some(version),
+            reader.header1().clone(),
+            reader.header2().clone(),
+        )
+    };
+
+    if json {
+        print_json(&Headers {
+            version: version.map(u16::from),
+            header1: &header1,
+            header2: &header2,
+        })
+    } else {
+        print_headers(version, &header1, &header2);
+        Ok(())
+    }
+}
+
+/// Recreate the headers that `data` would be written with.
+fn headers(data: &WriteData) -> (Header1, Header2) {
+    let header1 = Header1 {
+        map: data.map.clone(),
+        author: data.author.clone(),
+        description: data.description.clone(),
+        host: None,
+        save_time: Some(data.save_time),
+        brick_count: data.bricks.len() as i32,
+    };
+    let header2 = Header2 {
+        mods: data.mods.clone(),
+        brick_assets: data.brick_assets.clone(),
+        colors: data.colors.clone(),
+        materials: data.materials.clone(),
+        brick_owners: data.brick_owners.clone(),
+    };
+    (header1, header2)
+}
+
+fn dump(path: &Path, limit: Option<usize>, json: bool) -> Result<()> {
+    let mut data = load(path)?;
+    data.bricks.truncate(limit.unwrap_or(usize::MAX));
+
+    if json {
+        return print_json(&data);
+    }
+
+    let (header1, header2) = headers(&data);
+    print_headers(None, &header1, &header2);
+    println!();
+
+    let name = |table: &[String], i: u32| {
+        table
+            .get(i as usize)
+            .cloned()
+            .unwrap_or_else(|| format!("<{}>", i))
+    };
+
+    let stdout = io::stdout();
+    let mut w = BufWriter::new(stdout.lock());
+    for (i, brick) in data.bricks.iter().enumerate() {
+        write!(
+            w,
+            "{}: {} {} pos={:?} size={:?} {:?}:{:?} color=",
+            i,
+            name(&data.brick_assets, brick.asset_name_index),
+            name(&data.materials, brick.material_index),
+            brick.position,
+            brick.size,
+            brick.direction,
+            brick.rotation,
+        )?;
+        match brick.color {
+            ColorMode::Set(c) => write!(w, "{}", c)?,
+            ColorMode::Custom(c) => write!(w, "{:?}", c)?,
+        }
+        if let Some(owner) = brick.owner_index {
+            let owner = data.brick_owners.get(owner as usize);
+            write!(w, " owner={}", owner.map_or("<invalid>", |o| &o.name))?;
+        }
+        if !brick.collision {
+            write!(w, " no-collision")?;
+        }
+        if !brick.visibility {
+            write!(w, " invisible")?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Validation {
+    valid: bool,
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+fn validate(path: &Path, json: bool) -> Result<bool> {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    match load(path) {
+        Err(err) => errors.push(err.to_string()),
+        Ok(data) => {
+            for (i, brick) in data.bricks.iter().enumerate() {
+                let mut check = |what: &str, index: u32, len: usize| {
+                    if index as usize >= len {
+                        errors.push(format!(
+                            "brick {}: {} index {} out of range ({} entries)",
+                            i, what, index, len
+                        ));
+                    }
+                };
+                check("asset", brick.asset_name_index, data.brick_assets.len());
+                check("material", brick.material_index, data.materials.len());
+                if let ColorMode::Set(c) = brick.color {
+                    check("color", c, data.colors.len());
+                }
+                if let Some(owner) = brick.owner_index {
+                    check("owner", owner, data.brick_owners.len());
+                }
+            }
+
+            let overlaps = overlap::find(&data);
+            for (what, pairs) in &[
+                ("duplicate", &overlaps.duplicates),
+                ("contained", &overlaps.contained),
+                ("overlapping", &overlaps.partial),
+            ] {
+                if !pairs.is_empty() {
+                    warnings.push(format!("{} {} brick pairs", pairs.len(), what));
+                }
+            }
+        }
+    }
+
+    let valid = errors.is_empty();
+    if json {
+        print_json(&Validation {
+            valid,
+            errors,
+            warnings,
+        })?;
+    } else {
+        for error in &errors {
+            println!("error: {}", error);
+        }
+        for warning in &warnings {
+            println!("warning: {}", warning);
+        }
+        println!("{}", if valid { "valid" } else { "invalid" });
+    }
+    Ok(valid)
+}
+
+fn stats(path: &Path, json: bool) -> Result<()> {
+    let stats = if has_extension(path, "json") {
+        Stats::from_write_data(&load(path)?)
+    } else {
+        Stats::from_reader(open(path)?.1).map_err(|e| format!("{}: {}", path.display(), e))?
+    };
+
+    if json {
+        return print_json(&stats);
+    }
+
+    let percent = |n: usize| 100.0 * n as f64 / stats.brick_count.max(1) as f64;
+    let row = |label: &str, n: usize| println!("  {:>10} {:>6.1}%  {}", n, percent(n), label);
+
+    println!("Bricks: {}", stats.brick_count);
+    if let Some(bounds) = stats.bounds {
+        println!("Bounds: {:?} to {:?}", bounds.min, bounds.max);
+        println!("Size:   {:?}", bounds.size());
+    }
+    if let Some(density) = stats.density() {
+        println!("Density: {:.3} bricks per 1x1 brick of volume", density);
+    }
+
+    println!("Kinds:");
+    row("procedural", stats.procedural);
+    row("fixed size", stats.fixed);
+    row("no collision", stats.no_collision);
+    row("invisible", stats.invisible);
+    if stats.invalid_indices > 0 {
+        row("invalid indices", stats.invalid_indices);
+    }
+
+    println!("Assets:");
+    for (name, n) in &stats.assets {
+        row(name, *n);
+    }
+    println!("Materials:");
+    for (name, n) in &stats.materials {
+        row(name, *n);
+    }
+    println!("Colors:");
+    row("custom", stats.custom_colors);
+    row("palette", stats.palette_colors);
+    for (i, (color, n)) in stats.colors.iter().enumerate() {
+        row(&format!("palette {} {:?}", i, color), *n);
+    }
+    println!("Owners:");
+    row("none", stats.unowned);
+    for (owner, n) in &stats.owners {
+        row(&user(owner), *n);
+    }
+    Ok(())
+}
+
+fn diff(old: &Path, new: &Path, bricks: bool, json: bool) -> Result<()> {
+    let old = load(old)?;
+    let new = load(new)?;
+    let diff = brs::diff(&old, &new);
+
+    if json {
+        return print_json(&diff);
+    }
+
+    if diff.is_empty() {
+        println!("No changes");
+        return Ok(());
+    }
+
+    for field in &diff.header {
+        let (a, b) = match field {
+            HeaderField::Map => (old.map.clone(), new.map.clone()),
+            HeaderField::Author => (user(&old.author), user(&new.author)),
+            HeaderField::Description => (old.description.clone(), new.description.clone()),
+            HeaderField::SaveTime => (old.save_time.to_string(), new.save_time.to_string()),
+        };
+        println!("{:?}: {} -> {}", field, a, b);
+    }
+
+    fn table<T>(name: &str, table: &TableDiff<T>, show: impl Fn(&T) -> String) {
+        for value in &table.added {
+            println!("+ {} {}", name, show(value));
+        }
+        for value in &table.removed {
+            println!("- {} {}", name, show(value));
+        }
+    }
+    table("mod", &diff.mods, String::clone);
+    table("asset", &diff.brick_assets, String::clone);
+    table("material", &diff.materials, String::clone);
+    table("color", &diff.colors, |c| format!("{:?}", c));
+    table("owner", &diff.brick_owners, user);
+
+    println!(
+        "Bricks: {} added, {} removed, {} modified",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.modified.len()
+    );
+    let mut fields = BTreeMap::new();
+    for change in &diff.modified {
+        for field in &change.fields {
+            *fields.entry(format!("{:?}", field)).or_insert(0) += 1;
+        }
+    }
+    for (field, count) in &fields {
+        println!("  {:>10}  {} changed", count, field);
+    }
+
+    if bricks {
+        let describe = |data: &WriteData, i: usize| {
+            let brick = &data.bricks[i];
+            let asset = data.brick_assets.get(brick.asset_name_index as usize);
+            format!(
+                "{} at {:?} {:?}:{:?}",
+                asset.map_or("<invalid>", String::as_str),
+                brick.position,
+                brick.direction,
+                brick.rotation
+            )
+        };
+        for &i in &diff.added {
+            println!("+ {}", describe(&new, i));
+        }
+        for &i in &diff.removed {
+            println!("- {}", describe(&old, i));
+        }
+        for change in &diff.modified {
+            println!("~ {} {:?}", describe(&new, change.new), change.fields);
+        }
+    }
+    Ok(())
+}
@@ -1,24 +1,48 @@
-// Currently panics on EOF.
-// Needs improvement.
+// Like UE4's FBitReader, reading past the end doesn't fail right away.
+// It returns zero bits and marks the reader as overflowed, which callers
+// check once they're done reading a unit of data.
 
 use std::io::{self, prelude::*};
 
 pub struct BitReader {
     buf: Vec<u8>,
     pos: usize,
+    overflowed: bool,
 }
 
 impl BitReader {
     pub fn new(buf: Vec<u8>) -> Self {
-        Self { buf, pos: 0 }
+        Self {
+            buf,
+            pos: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Whether anything was read past the end of the buffer.
+    pub fn is_overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    /// The number of bytes that haven't been fully read yet.
+    pub fn remaining_bytes(&self) -> usize {
+        self.buf.len().saturating_sub(self.pos >> 3)
     }
 
     // ReadBit
     #[inline(always)]
     pub fn read_bit(&mut self) -> bool {
-        let bit = (self.buf[self.pos >> 3] & (1 << (self.pos & 7))) != 0;
-        self.pos += 1;
-        bit
+        match self.buf.get(self.pos >> 3) {
+            Some(byte) => {
+                let bit = (byte & (1 << (self.pos & 7))) != 0;
+                self.pos += 1;
+                bit
+            }
+            None => {
+                self.overflowed = true;
+                false
+            }
+        }
     }
 
     // SerializeBits
@@ -127,6 +151,9 @@ impl BitReader {
 impl Read for BitReader {
     fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
         self.read_bits(dst, dst.len() * 8);
+        if self.overflowed {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
         Ok(dst.len())
     }
 }
@@ -51,8 +51,7 @@ impl<W: Write> BitWriter<W> {
     }
 
     pub fn write_int(&mut self, value: u32, max: u32) -> io::Result<()> {
-        assert!(max >= 2);
-
+        // A max of 1 writes no bits, like `BitReader::read_int`.
         if value >= max {
             return Err(io::Error::from(io::ErrorKind::InvalidInput));
         }
@@ -92,7 +91,7 @@ impl<W: Write> BitWriter<W> {
 
     pub fn write_int_vector_packed(&mut self, v: (i32, i32, i32)) -> io::Result<()> {
         fn map(x: i32) -> u32 {
-            ((x.abs() as u32) << 1) | (x.is_positive() as u32)
+            (x.unsigned_abs() << 1) | (x.is_positive() as u32)
         }
         self.write_int_packed(map(v.0))?;
         self.write_int_packed(map(v.1))?;
@@ -0,0 +1,605 @@
+//! Import and export of Blockland `.bls` save files.
+//!
+//! Blockland bricks are identified by the UI name of their datablock, such as
+//! `1x4`, `2x2F` or `1x1 Round`. A [`BlsMapping`](struct.BlsMapping.html)
+//! maps those names to a Brickadia asset and size. Plain bricks, plates and
+//! baseplates named like `AxB`, `AxBF`, `AxBxC` and `AxB Base` are mapped
+//! to procedural bricks without needing an entry.
+//!
+//! Blockland units are converted by a factor of 20, so a 1x1 brick of
+//! 0.5 x 0.5 x 0.6 becomes 10 x 10 x 12. The Y axis is mirrored, as Blockland
+//! uses a right-handed coordinate system.
+
+use crate::{
+    save::{Brick, Color, ColorMode, Direction, Rotation, User},
+    transform::table_index,
+    WriteData,
+};
+use chrono::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{self, prelude::*, BufReader},
+    path::Path,
+};
+use uuid::Uuid;
+
+const HEADER: &str =
+    "This is a Blockland save file.  You probably shouldn't modify it cause you'll screw it up.";
+
+/// The number of colors in a Blockland color set.
+const COLOR_SET_SIZE: usize = 64;
+
+/// Blockland units per Brickadia unit.
+const SCALE: f64 = 20.0;
+
+/// The Brickadia asset and size a Blockland brick is mapped to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BlsBrick {
+    pub asset: String,
+    /// The size of the brick at angle 0, in half extents like `Brick::size`.
+    /// Fixed size assets should use the size from their
+    /// [`AssetCatalog`](../catalog/struct.AssetCatalog.html) entry,
+    /// which is used to find them when exporting.
+    pub size: (u32, u32, u32),
+    /// Whether the asset has a fixed size, and bricks should be
+    /// written with a size of `(0, 0, 0)`.
+    pub fixed: bool,
+}
+
+const BUILTIN_FIXED: &[(&str, &str, (u32, u32, u32))] = &[
+    ("1x1 Round", "B_1x1_Round", (5, 5, 6)),
+    ("1x1F Round", "B_1x1F_Round", (5, 5, 2)),
+    ("2x2 Round", "B_2x2_Round", (10, 10, 6)),
+    ("2x2F Round", "B_2x2F_Round", (10, 10, 2)),
+    ("4x4 Round", "B_4x4_Round", (20, 20, 6)),
+    ("1x1 Cone", "B_1x1_Cone", (5, 5, 6)),
+    ("2x2x2 Cone", "B_2x2_Cone", (10, 10, 12)),
+    ("1x1 Octo", "B_1x_Octo", (5, 5, 5)),
+    ("2x2 Octo", "B_2x_Octo", (10, 10, 10)),
+    ("1x1F Octo", "B_1x1F_Octo", (5, 5, 2)),
+    ("2x2F Octo", "B_2x2F_Octo", (10, 10, 2)),
+    ("Pine Tree", "B_Pine_Tree", (20, 20, 48)),
+];
+
+/// The entries of a `BlsMapping` for each asset, sorted by UI name.
+type AssetIndex<'a> = HashMap<&'a str, Vec<(&'a str, &'a BlsBrick)>>;
+
+/// A lookup table from Blockland brick UI names to Brickadia bricks.
+///
+/// Names are compared case-insensitively.
+///
+/// ```
+/// use brs::bls::BlsMapping;
+/// let mapping = BlsMapping::default();
+/// assert_eq!(mapping.get("1x4").unwrap().size, (5, 20, 6));
+/// assert_eq!(mapping.get("2x2F").unwrap().size, (10, 10, 2));
+/// assert_eq!(mapping.get("1x1x5").unwrap().size, (5, 5, 30));
+/// assert_eq!(mapping.get("1x1 Round").unwrap().asset, "B_1x1_Round");
+/// assert!(mapping.get("Castle Wall").is_none());
+/// ```
+#[derive(Debug, Clone)]
+pub struct BlsMapping {
+    entries: HashMap<String, (String, BlsBrick)>,
+    generic: bool,
+}
+
+impl Default for BlsMapping {
+    fn default() -> Self {
+        let mut mapping = Self::empty();
+        mapping.generic = true;
+        for &(name, asset, size) in BUILTIN_FIXED {
+            mapping.insert(
+                name,
+                BlsBrick {
+                    asset: asset.to_string(),
+                    size,
+                    fixed: true,
+                },
+            );
+        }
+        mapping
+    }
+}
+
+impl BlsMapping {
+    /// Create a mapping without any entries, which doesn't map
+    /// plain bricks and plates either.
+    pub fn empty() -> Self {
+        Self {
+            entries: HashMap::new(),
+            generic: false,
+        }
+    }
+
+    /// Add or replace the entry for the UI name `name`.
+    pub fn insert(&mut self, name: &str, brick: BlsBrick) -> Option<BlsBrick> {
+        self.entries
+            .insert(name.to_lowercase(), (name.to_string(), brick))
+            .map(|(_, brick)| brick)
+    }
+
+    /// Look up the Brickadia brick for the UI name `name`.
+    pub fn get(&self, name: &str) -> Option<BlsBrick> {
+        match self.entries.get(&name.to_lowercase()) {
+            Some((_, brick)) => Some(brick.clone()),
+            None if self.generic => generic_brick(name),
+            None => None,
+        }
+    }
+
+    /// Group the entries by asset, sorting each group by UI name,
+    /// for looking up bricks with `find`.
+    fn asset_index(&self) -> AssetIndex<'_> {
+        let mut index = AssetIndex::new();
+        for (name, brick) in self.entries.values() {
+            index
+                .entry(brick.asset.as_str())
+                .or_default()
+                .push((name.as_str(), brick));
+        }
+        for entries in index.values_mut() {
+            entries.sort_by_key(|&(name, _)| name);
+        }
+        index
+    }
+
+    /// Find the UI name for a brick of `asset` with the unrotated `size`,
+    /// along with a number of quarter turns to add to its rotation.
+    /// `index` must come from `asset_index`.
+    fn find(&self, index: &AssetIndex, asset: &str, size: (u32, u32, u32)) -> Option<(String, u8)> {
+        for &(name, brick) in index.get(asset).into_iter().flatten() {
+            if brick.fixed || brick.size == size {
+                return Some((name.to_string(), 0));
+            }
+            let swapped = (brick.size.1, brick.size.0, brick.size.2);
+            if swapped == size {
+                return Some((name.to_string(), 1));
+            }
+        }
+
+        if self.generic && asset == "PB_DefaultBrick" {
+            return generic_name(size);
+        }
+
+        None
+    }
+
+    /// Load additional entries from a text file at `path`.
+    /// See [`load`](#method.load) for the format.
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.load(BufReader::new(File::open(path)?))
+    }
+
+    /// Load additional entries from `r`, replacing existing entries
+    /// with the same name.
+    ///
+    /// Like in `.bls` files, each line starts with the UI name followed by a
+    /// `"`. After it come the asset, `procedural` or `fixed`, and the three
+    /// size components, separated by whitespace.
+    /// Empty lines and lines starting with `#` are ignored.
+    ///
+    /// ```
+    /// use brs::bls::BlsMapping;
+    /// let mut mapping = BlsMapping::default();
+    /// mapping.load(&b"1x4 Fence\" B_Picket_Fence fixed 5 10 12\n"[..])?;
+    /// assert_eq!(mapping.get("1x4 fence").unwrap().asset, "B_Picket_Fence");
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn load(&mut self, r: impl BufRead) -> io::Result<()> {
+        for (number, line) in r.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid .bls mapping entry on line {}", number + 1),
+                )
+            };
+
+            let (name, rest) = split_name(line).ok_or_else(invalid)?;
+            let fields = rest.split_whitespace().collect::<Vec<_>>();
+            if fields.len() != 5 {
+                return Err(invalid());
+            }
+            let fixed = match fields[1] {
+                "procedural" => false,
+                "fixed" => true,
+                _ => return Err(invalid()),
+            };
+            let mut size = [0; 3];
+            for (value, field) in size.iter_mut().zip(&fields[2..]) {
+                *value = field.parse().map_err(|_| invalid())?;
+            }
+
+            self.insert(
+                name,
+                BlsBrick {
+                    asset: fields[0].to_string(),
+                    size: (size[0], size[1], size[2]),
+                    fixed,
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Map plain bricks, plates and baseplates by their name.
+fn generic_brick(name: &str) -> Option<BlsBrick> {
+    let name = name.trim().to_lowercase();
+    let (dims, base) = match name.strip_suffix(" base") {
+        Some(dims) => (dims, true),
+        None => (name.as_str(), false),
+    };
+    let (dims, plate) = match dims.strip_suffix('f') {
+        Some(dims) => (dims, true),
+        None => (dims, base),
+    };
+
+    let dims = dims
+        .split('x')
+        .map(|d| d.parse::<u32>().ok().filter(|&d| d > 0 && d <= 1000))
+        .collect::<Option<Vec<_>>>()?;
+    let size = match (dims.as_slice(), plate) {
+        (&[x, y], false) => (x * 5, y * 5, 6),
+        (&[x, y], true) => (x * 5, y * 5, 2),
+        (&[x, y, z], false) if !base => (x * 5, y * 5, z * 6),
+        _ => return None,
+    };
+
+    Some(BlsBrick {
+        asset: "PB_DefaultBrick".to_string(),
+        size,
+        fixed: false,
+    })
+}
+
+/// The name of a plain brick or plate of `size`, which can be found by
+/// [`generic_brick`](fn.generic_brick.html), plus a number of quarter turns.
+fn generic_name(size: (u32, u32, u32)) -> Option<(String, u8)> {
+    let (x, y, z) = size;
+    if x == 0 || y == 0 || x % 5 != 0 || y % 5 != 0 {
+        return None;
+    }
+    // Blockland names list the shorter side first.
+    let (a, b, turns) = if x <= y {
+        (x / 5, y / 5, 0)
+    } else {
+        (y / 5, x / 5, 1)
+    };
+    let name = match z {
+        2 => format!("{}x{}F", a, b),
+        6 => format!("{}x{}", a, b),
+        z if z % 6 == 0 => format!("{}x{}x{}", a, b, z / 6),
+        _ => return None,
+    };
+    Some((name, turns))
+}
+
+/// Split a line at the `"` ending the UI name.
+fn split_name(line: &str) -> Option<(&str, &str)> {
+    let end = line.find('"')?;
+    Some((&line[..end], &line[end + 1..]))
+}
+
+/// The result of reading a `.bls` file.
+#[derive(Debug, Clone)]
+pub struct BlsImport {
+    pub data: WriteData,
+    /// UI names of bricks without a mapping, with how many were skipped.
+    pub unmapped: BTreeMap<String, usize>,
+}
+
+/// Decode text as Windows-1252, or rather its Latin-1 subset,
+/// which is what Blockland writes.
+fn decode(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+fn invalid_data(line: usize, what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid .bls {} on line {}", what, line + 1),
+    )
+}
+
+/// The rotation of a Blockland angle ID, accounting for the mirrored Y axis.
+fn rotation_from_angle(angle: u8) -> Rotation {
+    match angle % 4 {
+        0 => Rotation::Deg0,
+        1 => Rotation::Deg270,
+        2 => Rotation::Deg180,
+        _ => Rotation::Deg90,
+    }
+}
+
+/// The Blockland angle ID of a number of quarter turns, the inverse of
+/// [`rotation_from_angle`](fn.rotation_from_angle.html).
+fn angle_from_turns(turns: u8) -> u8 {
+    (4 - turns % 4) % 4
+}
+
+/// The Blockland user that owns bricks with `bl_id`.
+/// Blockland users have no UUID, so the BL_ID is used as one.
+fn bl_user(bl_id: u32) -> User {
+    User {
+        id: Uuid::from_u128(u128::from(bl_id)),
+        name: format!("BL_ID {}", bl_id),
+    }
+}
+
+/// Read a Blockland save from `r`, mapping bricks with `mapping`.
+///
+/// Bricks without a mapping are skipped and counted in
+/// [`BlsImport::unmapped`](struct.BlsImport.html#structfield.unmapped).
+/// Events, lights, emitters and other extras are ignored.
+///
+/// ```no_run
+/// # use std::fs::File;
+/// use brs::bls::{self, BlsMapping};
+/// let import = bls::read(File::open("castle.bls")?, &BlsMapping::default())?;
+/// for (name, count) in &import.unmapped {
+///     println!("skipped {} bricks of {}", count, name);
+/// }
+/// brs::write_save(&mut File::create("castle.brs")?, &import.data)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read(mut r: impl Read, mapping: &BlsMapping) -> io::Result<BlsImport> {
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes)?;
+    let text = decode(&bytes);
+    let mut lines = text.lines().map(|l| l.trim_end_matches('\r')).enumerate();
+    let mut next = |what: &str| {
+        lines.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("Missing .bls {}", what),
+            )
+        })
+    };
+
+    let (n, header) = next("header")?;
+    if !header.starts_with("This is a Blockland save file") {
+        return Err(invalid_data(n, "header"));
+    }
+
+    let (n, count) = next("description")?;
+    let count = count
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| invalid_data(n, "description line count"))?;
+    let mut description = Vec::new();
+    for _ in 0..count {
+        description.push(next("description")?.1.to_string());
+    }
+
+    let mut colors = Vec::with_capacity(COLOR_SET_SIZE);
+    for _ in 0..COLOR_SET_SIZE {
+        let (n, line) = next("color set")?;
+        let c = line
+            .split_whitespace()
+            .map(|c| {
+                c.parse::<f64>()
+                    .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid_data(n, "color"))?;
+        match c[..] {
+            [r, g, b, a] => colors.push(Color::from_rgba(r, g, b, a)),
+            _ => return Err(invalid_data(n, "color")),
+        }
+    }
+
+    let (n, line_count) = next("line count")?;
+    if !line_count.starts_with("Linecount ") {
+        return Err(invalid_data(n, "line count"));
+    }
+
+    let mut data = WriteData {
+        map: "Plate".to_string(),
+        author: User {
+            id: Uuid::nil(),
+            name: "Blockland".to_string(),
+        },
+        description: description.join("\n"),
+        save_time: Utc::now(),
+        mods: Vec::new(),
+        brick_assets: Vec::new(),
+        colors,
+        materials: vec![
+            "BMC_Plastic".to_string(),
+            "BMC_Glow".to_string(),
+            "BMC_Metallic".to_string(),
+        ],
+        brick_owners: Vec::new(),
+        bricks: Vec::new(),
+    };
+    let mut unmapped = BTreeMap::new();
+    // Whether the last brick line was imported, for its extra lines.
+    let mut last_imported = false;
+
+    for (n, line) in lines {
+        if let Some(extra) = line.strip_prefix("+-") {
+            if let Some(bl_id) = extra.strip_prefix("OWNER ") {
+                let bl_id = bl_id.trim().parse().map_err(|_| invalid_data(n, "owner"))?;
+                if last_imported {
+                    let owner = table_index(&mut data.brick_owners, &bl_user(bl_id));
+                    data.bricks.last_mut().unwrap().owner_index = Some(owner);
+                }
+            }
+            continue;
+        }
+
+        let (name, rest) = split_name(line).ok_or_else(|| invalid_data(n, "brick"))?;
+        // The print name may be empty, so fields are split by single spaces.
+        let fields = rest.trim_start_matches(' ').split(' ').collect::<Vec<_>>();
+        if fields.len() < 12 {
+            return Err(invalid_data(n, "brick"));
+        }
+        let float = |i: usize| {
+            fields[i]
+                .parse::<f64>()
+                .map_err(|_| invalid_data(n, "brick"))
+        };
+        let int = |i: usize| {
+            fields[i]
+                .parse::<u32>()
+                .map_err(|_| invalid_data(n, "brick"))
+        };
+        let (x, y, z) = (float(0)?, float(1)?, float(2)?);
+        let angle = int(3)?;
+        let color = int(5)?;
+        let color_fx = int(7)?;
+        let collision = int(10)? != 0;
+        let rendering = int(11)? != 0;
+
+        let brick = match mapping.get(name) {
+            Some(brick) => brick,
+            None => {
+                *unmapped.entry(name.to_string()).or_insert(0) += 1;
+                last_imported = false;
+                continue;
+            }
+        };
+
+        data.bricks.push(Brick {
+            asset_name_index: table_index(&mut data.brick_assets, &brick.asset),
+            size: if brick.fixed { (0, 0, 0) } else { brick.size },
+            position: (
+                (x * SCALE).round() as i32,
+                (-y * SCALE).round() as i32,
+                (z * SCALE).round() as i32,
+            ),
+            direction: Direction::ZPositive,
+            rotation: rotation_from_angle(angle as u8),
+            collision,
+            visibility: rendering,
+            material_index: match color_fx {
+                1 | 2 => 2,
+                3 => 1,
+                _ => 0,
+            },
+            color: ColorMode::Set(color.min(COLOR_SET_SIZE as u32 - 1)),
+            owner_index: None,
+        });
+        last_imported = true;
+    }
+
+    Ok(BlsImport { data, unmapped })
+}
+
+/// Write `data` as a Blockland save to `w`, mapping bricks back with `mapping`.
+///
+/// Only upright bricks with a mapping can be written. The indices of all
+/// other bricks are returned. The color set is made of the first 64 colors
+/// of the color lookup table, and all other colors are replaced by the
+/// closest color in it.
+pub fn write(w: &mut impl Write, data: &WriteData, mapping: &BlsMapping) -> io::Result<Vec<usize>> {
+    let mut color_set = data
+        .colors
+        .iter()
+        .copied()
+        .take(COLOR_SET_SIZE)
+        .collect::<Vec<_>>();
+    if color_set.is_empty() {
+        color_set.push(Color::from_rgba(255, 255, 255, 255));
+    }
+
+    let index = mapping.asset_index();
+    let mut lines = Vec::new();
+    let mut skipped = Vec::new();
+    for (i, brick) in data.bricks.iter().enumerate() {
+        let asset = match data.brick_assets.get(brick.asset_name_index as usize) {
+            Some(asset) if brick.direction == Direction::ZPositive => asset,
+            _ => {
+                skipped.push(i);
+                continue;
+            }
+        };
+        let (name, turns) = match mapping.find(&index, asset, brick.size) {
+            Some(found) => found,
+            None => {
+                skipped.push(i);
+                continue;
+            }
+        };
+
+        let angle = angle_from_turns(u8::from(brick.rotation) + turns);
+        let color = match brick.color {
+            ColorMode::Set(c) if (c as usize) < color_set.len() => c as usize,
+            ColorMode::Set(c) => data
+                .colors
+                .get(c as usize)
+                .and_then(|c| c.nearest(&color_set))
+                .unwrap_or(0),
+            ColorMode::Custom(c) => c.nearest(&color_set).unwrap_or(0),
+        };
+        let color_fx = match data.materials.get(brick.material_index as usize) {
+            Some(m) if m == "BMC_Glow" => 3,
+            Some(m) if m == "BMC_Metallic" => 1,
+            _ => 0,
+        };
+
+        let (x, y, z) = brick.position;
+        lines.push(format!(
+            "{}\" {} {} {} {} 0 {}  {} 0 {} {} {}",
+            name,
+            f64::from(x) / SCALE,
+            f64::from(-y) / SCALE,
+            f64::from(z) / SCALE,
+            angle,
+            color,
+            color_fx,
+            u8::from(brick.collision),
+            u8::from(brick.collision),
+            u8::from(brick.visibility),
+        ));
+        if let Some(owner) = brick
+            .owner_index
+            .and_then(|o| data.brick_owners.get(o as usize))
+        {
+            if let Some(bl_id) = owner.name.strip_prefix("BL_ID ") {
+                lines.push(format!("+-OWNER {}", bl_id));
+            }
+        }
+    }
+
+    writeln!(w, "{}\r", HEADER)?;
+    let description = data.description.lines().collect::<Vec<_>>();
+    writeln!(w, "{}\r", description.len())?;
+    for line in description {
+        writeln!(w, "{}\r", line)?;
+    }
+    for i in 0..COLOR_SET_SIZE {
+        let c = color_set
+            .get(i)
+            .copied()
+            .unwrap_or(Color::from_rgba(0, 0, 0, 0));
+        let f = |v: u8| f64::from(v) / 255.0;
+        writeln!(
+            w,
+            "{:.6} {:.6} {:.6} {:.6}\r",
+            f(c.r()),
+            f(c.g()),
+            f(c.b()),
+            f(c.a())
+        )?;
+    }
+    writeln!(w, "Linecount {}\r", data.bricks.len() - skipped.len())?;
+    for line in lines {
+        // Blockland expects Latin-1 text.
+        let bytes = line
+            .chars()
+            .map(|c| if (c as u32) < 256 { c as u8 } else { b'?' })
+            .collect::<Vec<_>>();
+        w.write_all(&bytes)?;
+        w.write_all(b"\r\n")?;
+    }
+
+    Ok(skipped)
+}
@@ -0,0 +1,247 @@
+use crate::{catalog::AssetCatalog, Brick, WriteData};
+use std::convert::TryFrom;
+
+/// An axis-aligned bounding box in save units.
+///
+/// Both `min` and `max` are inclusive corners, so a box where
+/// `min == max` is a single point with no volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Aabb {
+    pub min: (i32, i32, i32),
+    pub max: (i32, i32, i32),
+}
+
+impl Aabb {
+    /// Create a box from two opposite corners, in any order.
+    pub fn new(a: (i32, i32, i32), b: (i32, i32, i32)) -> Self {
+        Self {
+            min: (a.0.min(b.0), a.1.min(b.1), a.2.min(b.2)),
+            max: (a.0.max(b.0), a.1.max(b.1), a.2.max(b.2)),
+        }
+    }
+
+    /// Create a box around `center` extending `half_size` in each direction,
+    /// like a brick with that position and size.
+    pub fn from_center(center: (i32, i32, i32), half_size: (u32, u32, u32)) -> Self {
+        let h = |v: u32| i32::try_from(v).unwrap_or(i32::MAX);
+        let (x, y, z) = (h(half_size.0), h(half_size.1), h(half_size.2));
+        Self {
+            min: (
+                center.0.saturating_sub(x),
+                center.1.saturating_sub(y),
+                center.2.saturating_sub(z),
+            ),
+            max: (
+                center.0.saturating_add(x),
+                center.1.saturating_add(y),
+                center.2.saturating_add(z),
+            ),
+        }
+    }
+
+    /// The center of the box, rounded down.
+    pub fn center(&self) -> (i32, i32, i32) {
+        let mid = |a: i32, b: i32| ((i64::from(a) + i64::from(b)).div_euclid(2)) as i32;
+        (
+            mid(self.min.0, self.max.0),
+            mid(self.min.1, self.max.1),
+            mid(self.min.2, self.max.2),
+        )
+    }
+
+    /// The full extent of the box along each axis.
+    pub fn size(&self) -> (u32, u32, u32) {
+        let len = |a: i32, b: i32| (i64::from(b) - i64::from(a)) as u32;
+        (
+            len(self.min.0, self.max.0),
+            len(self.min.1, self.max.1),
+            len(self.min.2, self.max.2),
+        )
+    }
+
+    /// The volume of the box in cubic save units.
+    pub fn volume(&self) -> u64 {
+        let (x, y, z) = self.size();
+        u64::from(x) * u64::from(y) * u64::from(z)
+    }
+
+    /// The smallest box containing both boxes.
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: (
+                self.min.0.min(other.min.0),
+                self.min.1.min(other.min.1),
+                self.min.2.min(other.min.2),
+            ),
+            max: (
+                self.max.0.max(other.max.0),
+                self.max.1.max(other.max.1),
+                self.max.2.max(other.max.2),
+            ),
+        }
+    }
+
+    /// The box shared by both boxes, if they touch or overlap.
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        if !self.touches(other) {
+            return None;
+        }
+        Some(Self {
+            min: (
+                self.min.0.max(other.min.0),
+                self.min.1.max(other.min.1),
+                self.min.2.max(other.min.2),
+            ),
+            max: (
+                self.max.0.min(other.max.0),
+                self.max.1.min(other.max.1),
+                self.max.2.min(other.max.2),
+            ),
+        })
+    }
+
+    /// Whether the boxes share any volume. Boxes that only share a face,
+    /// edge or corner don't intersect.
+    ///
+    /// ```
+    /// use brs::Aabb;
+    /// let a = Aabb::new((0, 0, 0), (10, 10, 10));
+    /// assert!(a.intersects(&Aabb::new((5, 5, 5), (15, 15, 15))));
+    /// assert!(!a.intersects(&Aabb::new((10, 0, 0), (20, 10, 10))));
+    /// assert!(a.touches(&Aabb::new((10, 0, 0), (20, 10, 10))));
+    /// ```
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min.0 < other.max.0
+            && other.min.0 < self.max.0
+            && self.min.1 < other.max.1
+            && other.min.1 < self.max.1
+            && self.min.2 < other.max.2
+            && other.min.2 < self.max.2
+    }
+
+    /// Whether the boxes intersect or share a face, edge or corner.
+    pub fn touches(&self, other: &Self) -> bool {
+        self.min.0 <= other.max.0
+            && other.min.0 <= self.max.0
+            && self.min.1 <= other.max.1
+            && other.min.1 <= self.max.1
+            && self.min.2 <= other.max.2
+            && other.min.2 <= self.max.2
+    }
+
+    /// Whether `other` lies entirely within this box.
+    pub fn contains(&self, other: &Self) -> bool {
+        self.min.0 <= other.min.0
+            && self.min.1 <= other.min.1
+            && self.min.2 <= other.min.2
+            && other.max.0 <= self.max.0
+            && other.max.1 <= self.max.1
+            && other.max.2 <= self.max.2
+    }
+
+    /// Whether `point` lies within or on the surface of this box.
+    pub fn contains_point(&self, point: (i32, i32, i32)) -> bool {
+        self.contains(&Self {
+            min: point,
+            max: point,
+        })
+    }
+
+    /// The squared distance from `point` to the closest point in the box.
+    pub fn distance_squared(&self, point: (i32, i32, i32)) -> u64 {
+        let axis = |p: i32, min: i32, max: i32| {
+            let d = if p < min {
+                i64::from(min) - i64::from(p)
+            } else if p > max {
+                i64::from(p) - i64::from(max)
+            } else {
+                0
+            } as u64;
+            d * d
+        };
+        axis(point.0, self.min.0, self.max.0)
+            + axis(point.1, self.min.1, self.max.1)
+            + axis(point.2, self.min.2, self.max.2)
+    }
+
+    /// Move the box by `offset`, saturating at the limits of `i32`.
+    pub fn translate(&self, offset: (i32, i32, i32)) -> Self {
+        Self {
+            min: (
+                self.min.0.saturating_add(offset.0),
+                self.min.1.saturating_add(offset.1),
+                self.min.2.saturating_add(offset.2),
+            ),
+            max: (
+                self.max.0.saturating_add(offset.0),
+                self.max.1.saturating_add(offset.1),
+                self.max.2.saturating_add(offset.2),
+            ),
+        }
+    }
+}
+
+impl Brick {
+    /// The half extents of the brick in world space, given its unrotated
+    /// half extents `size`.
+    pub fn rotated_size(&self, size: (u32, u32, u32)) -> (u32, u32, u32) {
+        let h = |v: u32| i32::try_from(v).unwrap_or(i32::MAX);
+        let (x, y, z) = self.orientation().apply((h(size.0), h(size.1), h(size.2)));
+        (x.unsigned_abs(), y.unsigned_abs(), z.unsigned_abs())
+    }
+
+    /// The world space bounding box of the brick, based on its `size`.
+    ///
+    /// Fixed size bricks have a size of `(0, 0, 0)`, so they will only cover
+    /// their position. Use [`aabb_with`](#method.aabb_with) to look up
+    /// their size in an [`AssetCatalog`](catalog/struct.AssetCatalog.html).
+    ///
+    /// ```
+    /// # use brs::{Aabb, Brick, ColorMode, Direction, Rotation};
+    /// let brick = Brick {
+    ///     asset_name_index: 0,
+    ///     size: (10, 5, 6),
+    ///     position: (0, 0, 6),
+    ///     direction: Direction::XPositive,
+    ///     rotation: Rotation::Deg0,
+    ///     collision: true,
+    ///     visibility: true,
+    ///     material_index: 0,
+    ///     color: ColorMode::Set(0),
+    ///     owner_index: None,
+    /// };
+    /// assert_eq!(brick.aabb(), Aabb::new((-6, -5, -4), (6, 5, 16)));
+    /// ```
+    pub fn aabb(&self) -> Aabb {
+        Aabb::from_center(self.position, self.rotated_size(self.size))
+    }
+
+    /// The world space bounding box of the brick, looking up the size of
+    /// fixed size bricks in `catalog` by their name in `brick_assets`.
+    /// Bricks with unknown assets and no size only cover their position.
+    pub fn aabb_with(&self, brick_assets: &[String], catalog: &AssetCatalog) -> Aabb {
+        let size = catalog.brick_size(self, brick_assets).unwrap_or(self.size);
+        Aabb::from_center(self.position, self.rotated_size(size))
+    }
+}
+
+impl WriteData {
+    /// The bounding box of all bricks, using the built-in
+    /// [`AssetCatalog`](catalog/struct.AssetCatalog.html) for fixed size bricks.
+    /// Returns `None` if there are no bricks.
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.bounds_with(&AssetCatalog::default())
+    }
+
+    /// The bounding box of all bricks, using `catalog` for fixed size bricks.
+    /// Returns `None` if there are no bricks.
+    pub fn bounds_with(&self, catalog: &AssetCatalog) -> Option<Aabb> {
+        self.bricks
+            .iter()
+            .map(|brick| brick.aabb_with(&self.brick_assets, catalog))
+            .fold(None, |acc: Option<Aabb>, aabb| {
+                Some(acc.map_or(aabb, |acc| acc.union(&aabb)))
+            })
+    }
+}
@@ -0,0 +1,327 @@
+//! Known brick assets and their dimensions.
+//!
+//! Procedural bricks store their size in [`Brick::size`](../struct.Brick.html#structfield.size),
+//! but fixed size bricks are written with a size of `(0, 0, 0)`,
+//! so their extent can only be known by looking up the asset.
+//! An [`AssetCatalog`](struct.AssetCatalog.html) provides that lookup.
+//!
+//! All sizes are half extents in save units, the same as `Brick::size`.
+//! A 1x1 brick is `(5, 5, 6)` and a 1x1 plate is `(5, 5, 2)`.
+
+use crate::Brick;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, prelude::*, BufReader},
+    path::Path,
+};
+
+/// Whether an asset is sized by its bricks or has a fixed size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssetKind {
+    /// The size is taken from `Brick::size`.
+    Procedural,
+    /// The size is fixed by the asset, and `Brick::size` is usually `(0, 0, 0)`.
+    Fixed,
+}
+
+/// What is known about a brick asset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssetInfo {
+    pub kind: AssetKind,
+    /// For fixed assets, the size of the asset.
+    /// For procedural assets, the size used when a brick has none.
+    pub size: (u32, u32, u32),
+    /// Whether bricks of the asset fill their whole bounding box,
+    /// like plain bricks and tiles but unlike ramps and wedges.
+    pub box_shaped: bool,
+}
+
+impl AssetInfo {
+    pub fn procedural(size: (u32, u32, u32)) -> Self {
+        Self {
+            kind: AssetKind::Procedural,
+            size,
+            box_shaped: false,
+        }
+    }
+
+    /// A procedural asset filling its whole bounding box.
+    pub fn procedural_box(size: (u32, u32, u32)) -> Self {
+        Self {
+            box_shaped: true,
+            ..Self::procedural(size)
+        }
+    }
+
+    pub fn fixed(size: (u32, u32, u32)) -> Self {
+        Self {
+            kind: AssetKind::Fixed,
+            size,
+            box_shaped: false,
+        }
+    }
+
+    pub fn is_procedural(&self) -> bool {
+        self.kind == AssetKind::Procedural
+    }
+}
+
+const BRICK: (u32, u32, u32) = (5, 5, 6);
+const PLATE: (u32, u32, u32) = (5, 5, 2);
+
+const BUILTIN_BOX: &[(&str, (u32, u32, u32))] = &[
+    ("PB_DefaultBrick", BRICK),
+    ("PB_DefaultTile", PLATE),
+    ("PB_DefaultSmoothTile", PLATE),
+    ("PB_DefaultMicroBrick", (1, 1, 1)),
+];
+
+const BUILTIN_PROCEDURAL: &[(&str, (u32, u32, u32))] = &[
+    ("PB_DefaultRamp", (10, 10, 6)),
+    ("PB_DefaultRampInverted", (10, 10, 6)),
+    ("PB_DefaultRampCorner", (10, 10, 6)),
+    ("PB_DefaultRampCornerInverted", (10, 10, 6)),
+    ("PB_DefaultRampInnerCorner", (10, 10, 6)),
+    ("PB_DefaultRampInnerCornerInverted", (10, 10, 6)),
+    ("PB_DefaultRampCrest", (10, 5, 6)),
+    ("PB_DefaultRampCrestEnd", (10, 5, 6)),
+    ("PB_DefaultRampCrestCorner", (10, 10, 6)),
+    ("PB_DefaultWedge", BRICK),
+    ("PB_DefaultSideWedge", PLATE),
+    ("PB_DefaultSideWedgeTile", PLATE),
+    ("PB_DefaultPole", BRICK),
+    ("PB_DefaultMicroWedge", (1, 1, 1)),
+    ("PB_DefaultMicroWedgeCorner", (1, 1, 1)),
+    ("PB_DefaultMicroWedgeInnerCorner", (1, 1, 1)),
+    ("PB_DefaultMicroWedgeOuterCorner", (1, 1, 1)),
+    ("PB_DefaultMicroWedgeTriangleCorner", (1, 1, 1)),
+    ("PB_DefaultMicroWedgeHalfInnerCorner", (1, 1, 1)),
+    ("PB_DefaultMicroWedgeHalfOuterCorner", (1, 1, 1)),
+];
+
+const BUILTIN_FIXED: &[(&str, (u32, u32, u32))] = &[
+    ("B_1x1_Round", BRICK),
+    ("B_1x1F_Round", PLATE),
+    ("B_1x1F_Octo", PLATE),
+    ("B_1x1_Cone", BRICK),
+    ("B_1x1_Brick_Side", BRICK),
+    ("B_1x1_Brick_Side_Lip", BRICK),
+    ("B_1x2f_Plate_Center", (5, 10, 2)),
+    ("B_1x2f_Plate_Center_Inv", (5, 10, 2)),
+    ("B_1x_Octo", (5, 5, 5)),
+    ("B_1x_Octo_T", (5, 5, 5)),
+    ("B_1x_Octo_T_Inv", (5, 5, 5)),
+    ("B_1x_Octo_90Deg", (5, 5, 5)),
+    ("B_1x_Octo_90Deg_Inv", (5, 5, 5)),
+    ("B_2x_Octo", (10, 10, 10)),
+    ("B_2x_Octo_T", (10, 10, 10)),
+    ("B_2x_Octo_90Deg", (10, 10, 10)),
+    ("B_2x_Octo_Cone", (10, 10, 10)),
+    ("B_2x2_Round", (10, 10, 6)),
+    ("B_2x2F_Round", (10, 10, 2)),
+    ("B_2x2F_Octo", (10, 10, 2)),
+    ("B_2x2_Cone", (10, 10, 12)),
+    ("B_2x2_Corner", (10, 10, 6)),
+    ("B_2x2_Slipper", (10, 10, 6)),
+    ("B_4x4_Round", (20, 20, 6)),
+    ("B_8x8_Lattice_Plate", (40, 40, 2)),
+    ("B_Inverted_Cone", BRICK),
+    ("B_Picket_Fence", (5, 10, 12)),
+    ("B_Ladder", (5, 10, 12)),
+    ("B_Handle", PLATE),
+    ("B_Hedge_1x1", BRICK),
+    ("B_Hedge_1x1_Corner", BRICK),
+    ("B_Hedge_1x2", (5, 10, 6)),
+    ("B_Hedge_1x4", (5, 20, 6)),
+    ("B_Flower", PLATE),
+    ("B_Small_Flower", PLATE),
+    ("B_Pine_Tree", (20, 20, 48)),
+    ("B_Gravestone", (5, 10, 12)),
+    ("B_Coffin", (10, 20, 6)),
+    ("B_Coffin_Lid", (10, 20, 2)),
+    ("B_Pumpkin", (10, 10, 10)),
+    ("B_Pumpkin_Carved", (10, 10, 10)),
+    ("B_Bone", (5, 10, 2)),
+    ("B_Pawn", (5, 5, 12)),
+    ("B_Rook", (5, 5, 14)),
+    ("B_Knight", (5, 5, 16)),
+    ("B_Bishop", (5, 5, 18)),
+    ("B_Queen", (5, 5, 20)),
+    ("B_King", (5, 5, 22)),
+];
+
+/// A lookup table from brick asset names to what is known about them.
+///
+/// [`AssetCatalog::default`](#impl-Default) comes with the assets of the base
+/// game. Entries can be added with [`insert`](#method.insert) or loaded from
+/// a text file with [`load`](#method.load).
+///
+/// ```
+/// use brs::catalog::{AssetCatalog, AssetKind};
+/// let catalog = AssetCatalog::default();
+/// assert_eq!(catalog.get("PB_DefaultTile").unwrap().kind, AssetKind::Procedural);
+/// assert_eq!(catalog.get("B_1x_Octo_T").unwrap().kind, AssetKind::Fixed);
+/// assert!(catalog.get("PB_DefaultTile").unwrap().box_shaped);
+/// assert!(!catalog.get("PB_DefaultRamp").unwrap().box_shaped);
+/// assert!(catalog.get("B_Unknown").is_none());
+/// ```
+#[derive(Debug, Clone)]
+pub struct AssetCatalog {
+    assets: HashMap<String, AssetInfo>,
+}
+
+impl Default for AssetCatalog {
+    fn default() -> Self {
+        let mut catalog = Self::empty();
+        for &(name, size) in BUILTIN_BOX {
+            catalog.insert(name, AssetInfo::procedural_box(size));
+        }
+        for &(name, size) in BUILTIN_PROCEDURAL {
+            catalog.insert(name, AssetInfo::procedural(size));
+        }
+        for &(name, size) in BUILTIN_FIXED {
+            catalog.insert(name, AssetInfo::fixed(size));
+        }
+        catalog
+    }
+}
+
+impl AssetCatalog {
+    /// Create a catalog without any entries, not even the built-in ones.
+    pub fn empty() -> Self {
+        Self {
+            assets: HashMap::new(),
+        }
+    }
+
+    /// Look up an asset by name.
+    pub fn get(&self, name: &str) -> Option<&AssetInfo> {
+        self.assets.get(name)
+    }
+
+    /// Add or replace an entry.
+    pub fn insert(&mut self, name: impl Into<String>, info: AssetInfo) -> Option<AssetInfo> {
+        self.assets.insert(name.into(), info)
+    }
+
+    /// Iterate over all entries, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &AssetInfo)> {
+        self.assets.iter().map(|(name, info)| (name.as_str(), info))
+    }
+
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+
+    /// Whether the named asset is known to be procedural.
+    /// Unknown assets are assumed to be procedural if their name starts with `PB_`.
+    pub fn is_procedural(&self, name: &str) -> bool {
+        match self.get(name) {
+            Some(info) => info.is_procedural(),
+            None => name.starts_with("PB_"),
+        }
+    }
+
+    /// The size of `brick`, looking up its asset in `brick_assets`
+    /// when the brick itself has no size.
+    /// Returns `None` if the brick has no size and the asset is unknown.
+    ///
+    /// ```
+    /// # use brs::{catalog::AssetCatalog, Brick, ColorMode, Direction, Rotation};
+    /// let assets = vec!["B_2x2_Round".to_string()];
+    /// let brick = Brick {
+    ///     asset_name_index: 0,
+    ///     size: (0, 0, 0),
+    ///     position: (0, 0, 6),
+    ///     direction: Direction::ZPositive,
+    ///     rotation: Rotation::Deg0,
+    ///     collision: true,
+    ///     visibility: true,
+    ///     material_index: 0,
+    ///     color: ColorMode::Set(0),
+    ///     owner_index: None,
+    /// };
+    /// let catalog = AssetCatalog::default();
+    /// assert_eq!(catalog.brick_size(&brick, &assets), Some((10, 10, 6)));
+    /// ```
+    pub fn brick_size(&self, brick: &Brick, brick_assets: &[String]) -> Option<(u32, u32, u32)> {
+        if brick.size != (0, 0, 0) {
+            return Some(brick.size);
+        }
+        brick_assets
+            .get(brick.asset_name_index as usize)
+            .and_then(|name| self.get(name))
+            .map(|info| info.size)
+    }
+
+    /// Load additional entries from a text file at `path`.
+    /// See [`load`](#method.load) for the format.
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.load(BufReader::new(File::open(path)?))
+    }
+
+    /// Load additional entries from `r`, replacing existing entries
+    /// with the same name.
+    ///
+    /// Each line holds one asset: its name, `procedural`, `box` for
+    /// procedural assets filling their bounding box, or `fixed`,
+    /// and the three size components, separated by whitespace.
+    /// Empty lines and lines starting with `#` are ignored.
+    ///
+    /// ```
+    /// use brs::catalog::{AssetCatalog, AssetInfo};
+    /// let mut catalog = AssetCatalog::empty();
+    /// catalog.load(&b"# name kind x y z\nB_Custom_Arch fixed 5 20 12\n"[..])?;
+    /// assert_eq!(catalog.get("B_Custom_Arch"), Some(&AssetInfo::fixed((5, 20, 12))));
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn load(&mut self, r: impl BufRead) -> io::Result<()> {
+        for (number, line) in r.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |what: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid asset catalog {} on line {}", what, number + 1),
+                )
+            };
+
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields.len() != 5 {
+                return Err(invalid("entry"));
+            }
+
+            let (kind, box_shaped) = match fields[1] {
+                "procedural" => (AssetKind::Procedural, false),
+                "box" => (AssetKind::Procedural, true),
+                "fixed" => (AssetKind::Fixed, false),
+                _ => return Err(invalid("kind")),
+            };
+
+            let mut size = [0; 3];
+            for (value, field) in size.iter_mut().zip(&fields[2..]) {
+                *value = field.parse().map_err(|_| invalid("size"))?;
+            }
+
+            self.insert(
+                fields[0],
+                AssetInfo {
+                    kind,
+                    size: (size[0], size[1], size[2]),
+                    box_shaped,
+                },
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,361 @@
+use crate::{write::BrickFields, Brick, ColorMode, Orientation};
+use std::{io, iter::FromIterator};
+
+/// Bricks stored as one vector per field rather than one struct per brick.
+///
+/// Row `i` of every vector belongs to the same brick, and all vectors must
+/// have the same length, as [`check_lengths`](#method.check_lengths) checks.
+/// Booleans and the optional parts of a brick are stored as bits of `flags`:
+///
+/// * [`COLLISION`](#associatedconstant.COLLISION) and
+///   [`VISIBILITY`](#associatedconstant.VISIBILITY) mirror the fields of
+///   [`Brick`](struct.Brick.html).
+/// * With [`CUSTOM_COLOR`](#associatedconstant.CUSTOM_COLOR) set, `colors`
+///   holds a custom color as `0xAARRGGBB` instead of an index into the
+///   color table.
+/// * With [`OWNER`](#associatedconstant.OWNER) set, `owner_indices` holds
+///   the index of the brick's owner. Otherwise it's 0 and unused.
+///
+/// Columns can be read directly with
+/// [`ReadBricks::read_columns`](read/struct.ReadBricks.html#method.read_columns)
+/// and written with [`write_save_columns`](fn.write_save_columns.html).
+///
+/// ```
+/// use brs::{Brick, BrickColumns, ColorMode, Direction, Rotation};
+/// let brick = Brick {
+///     asset_name_index: 0,
+///     size: (5, 5, 6),
+///     position: (0, 0, 6),
+///     direction: Direction::ZPositive,
+///     rotation: Rotation::Deg0,
+///     collision: true,
+///     visibility: true,
+///     material_index: 0,
+///     color: ColorMode::Custom(brs::Color::from_rgba(255, 0, 0, 255)),
+///     owner_index: None,
+/// };
+/// let columns = BrickColumns::from(vec![brick.clone()]);
+/// assert_eq!(columns.colors, vec![0xffff0000]);
+/// assert_eq!(columns.flags[0] & BrickColumns::CUSTOM_COLOR, BrickColumns::CUSTOM_COLOR);
+/// assert_eq!(Vec::from(columns), vec![brick]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BrickColumns {
+    pub asset_name_indices: Vec<u32>,
+    pub sizes: Vec<(u32, u32, u32)>,
+    pub positions: Vec<(i32, i32, i32)>,
+    pub orientations: Vec<Orientation>,
+    pub flags: Vec<u8>,
+    pub material_indices: Vec<u32>,
+    pub colors: Vec<u32>,
+    pub owner_indices: Vec<u32>,
+}
+
+impl BrickColumns {
+    /// The brick has collision.
+    pub const COLLISION: u8 = 1;
+    /// The brick is visible.
+    pub const VISIBILITY: u8 = 2;
+    /// The brick's color is a custom color rather than an index.
+    pub const CUSTOM_COLOR: u8 = 4;
+    /// The brick has an owner.
+    pub const OWNER: u8 = 8;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create empty columns with room for `capacity` bricks.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            asset_name_indices: Vec::with_capacity(capacity),
+            sizes: Vec::with_capacity(capacity),
+            positions: Vec::with_capacity(capacity),
+            orientations: Vec::with_capacity(capacity),
+            flags: Vec::with_capacity(capacity),
+            material_indices: Vec::with_capacity(capacity),
+            colors: Vec::with_capacity(capacity),
+            owner_indices: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// The number of bricks, which is the length of each column.
+    pub fn len(&self) -> usize {
+        self.asset_name_indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reserve room for at least `additional` more bricks in every column.
+    pub fn reserve(&mut self, additional: usize) {
+        self.asset_name_indices.reserve(additional);
+        self.sizes.reserve(additional);
+        self.positions.reserve(additional);
+        self.orientations.reserve(additional);
+        self.flags.reserve(additional);
+        self.material_indices.reserve(additional);
+        self.colors.reserve(additional);
+        self.owner_indices.reserve(additional);
+    }
+
+    /// Add `brick` to the end of the columns.
+    pub fn push(&mut self, brick: &Brick) {
+        let mut flags = 0;
+        if brick.collision {
+            flags |= Self::COLLISION;
+        }
+        if brick.visibility {
+            flags |= Self::VISIBILITY;
+        }
+        let color = match brick.color {
+            ColorMode::Set(index) => index,
+            ColorMode::Custom(color) => {
+                flags |= Self::CUSTOM_COLOR;
+                color.into()
+            }
+        };
+        if brick.owner_index.is_some() {
+            flags |= Self::OWNER;
+        }
+
+        self.asset_name_indices.push(brick.asset_name_index);
+        self.sizes.push(brick.size);
+        self.positions.push(brick.position);
+        self.orientations.push(brick.orientation());
+        self.flags.push(flags);
+        self.material_indices.push(brick.material_index);
+        self.colors.push(color);
+        self.owner_indices.push(brick.owner_index.unwrap_or(0));
+    }
+
+    /// The brick at `index`, or `None` if it's out of bounds
+    /// or the columns have different lengths.
+    pub fn get(&self, index: usize) -> Option<Brick> {
+        if index < self.len() && self.check_lengths().is_ok() {
+            Some(self.row(index).to_brick())
+        } else {
+            None
+        }
+    }
+
+    /// Iterate over the bricks as [`Brick`](struct.Brick.html)s.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the columns have different lengths.
+    pub fn iter(&self) -> impl Iterator<Item = Brick> + '_ {
+        self.assert_lengths();
+        (0..self.len()).map(move |i| self.row(i).to_brick())
+    }
+
+    /// The color of the brick at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds or the columns have
+    /// different lengths.
+    pub fn color(&self, index: usize) -> ColorMode {
+        self.assert_lengths();
+        self.row(index).color()
+    }
+
+    /// The owner of the brick at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds or the columns have
+    /// different lengths.
+    pub fn owner_index(&self, index: usize) -> Option<u32> {
+        self.assert_lengths();
+        self.row(index).owner_index()
+    }
+
+    /// Check that all columns have the same length, returning an
+    /// `InvalidInput` error if they don't.
+    ///
+    /// ```
+    /// let mut columns = brs::BrickColumns::new();
+    /// assert!(columns.check_lengths().is_ok());
+    /// columns.colors.push(0);
+    /// assert!(columns.check_lengths().is_err());
+    /// assert_eq!(columns.get(0), None);
+    /// ```
+    pub fn check_lengths(&self) -> io::Result<()> {
+        let len = self.len();
+        let lengths = [
+            self.sizes.len(),
+            self.positions.len(),
+            self.orientations.len(),
+            self.flags.len(),
+            self.material_indices.len(),
+            self.colors.len(),
+            self.owner_indices.len(),
+        ];
+        if lengths.iter().all(|&l| l == len) {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Brick columns have different lengths",
+            ))
+        }
+    }
+
+    fn assert_lengths(&self) {
+        if let Err(error) = self.check_lengths() {
+            panic!("{}", error);
+        }
+    }
+
+    /// A view of the brick at `index` for the writer.
+    pub(crate) fn row(&self, index: usize) -> Row<'_> {
+        Row {
+            columns: self,
+            index,
+        }
+    }
+}
+
+/// A single brick within [`BrickColumns`](struct.BrickColumns.html).
+pub(crate) struct Row<'a> {
+    columns: &'a BrickColumns,
+    index: usize,
+}
+
+impl Row<'_> {
+    fn has_flag(&self, flag: u8) -> bool {
+        self.columns.flags[self.index] & flag != 0
+    }
+
+    fn to_brick(&self) -> Brick {
+        let orientation = self.orientation();
+        Brick {
+            asset_name_index: self.asset_name_index(),
+            size: self.size(),
+            position: self.position(),
+            direction: orientation.direction,
+            rotation: orientation.rotation,
+            collision: self.collision(),
+            visibility: self.visibility(),
+            material_index: self.material_index(),
+            color: self.color(),
+            owner_index: self.owner_index(),
+        }
+    }
+}
+
+impl BrickFields for Row<'_> {
+    fn asset_name_index(&self) -> u32 {
+        self.columns.asset_name_indices[self.index]
+    }
+
+    fn size(&self) -> (u32, u32, u32) {
+        self.columns.sizes[self.index]
+    }
+
+    fn position(&self) -> (i32, i32, i32) {
+        self.columns.positions[self.index]
+    }
+
+    fn orientation(&self) -> Orientation {
+        self.columns.orientations[self.index]
+    }
+
+    fn collision(&self) -> bool {
+        self.has_flag(BrickColumns::COLLISION)
+    }
+
+    fn visibility(&self) -> bool {
+        self.has_flag(BrickColumns::VISIBILITY)
+    }
+
+    fn material_index(&self) -> u32 {
+        self.columns.material_indices[self.index]
+    }
+
+    fn color(&self) -> ColorMode {
+        let color = self.columns.colors[self.index];
+        if self.has_flag(BrickColumns::CUSTOM_COLOR) {
+            ColorMode::Custom(color.into())
+        } else {
+            ColorMode::Set(color)
+        }
+    }
+
+    fn owner_index(&self) -> Option<u32> {
+        if self.has_flag(BrickColumns::OWNER) {
+            Some(self.columns.owner_indices[self.index])
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> FromIterator<&'a Brick> for BrickColumns {
+    fn from_iter<I: IntoIterator<Item = &'a Brick>>(iter: I) -> Self {
+        let mut columns = Self::new();
+        columns.extend(iter);
+        columns
+    }
+}
+
+impl FromIterator<Brick> for BrickColumns {
+    fn from_iter<I: IntoIterator<Item = Brick>>(iter: I) -> Self {
+        let mut columns = Self::new();
+        columns.extend(iter);
+        columns
+    }
+}
+
+impl<'a> Extend<&'a Brick> for BrickColumns {
+    fn extend<I: IntoIterator<Item = &'a Brick>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for brick in iter {
+            self.push(brick);
+        }
+    }
+}
+
+impl Extend<Brick> for BrickColumns {
+    fn extend<I: IntoIterator<Item = Brick>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for brick in iter {
+            self.push(&brick);
+        }
+    }
+}
+
+impl From<&[Brick]> for BrickColumns {
+    fn from(bricks: &[Brick]) -> Self {
+        bricks.iter().collect()
+    }
+}
+
+impl From<Vec<Brick>> for BrickColumns {
+    fn from(bricks: Vec<Brick>) -> Self {
+        bricks.iter().collect()
+    }
+}
+
+/// # Panics
+///
+/// Panics if the columns have different lengths.
+impl From<BrickColumns> for Vec<Brick> {
+    fn from(columns: BrickColumns) -> Self {
+        columns.iter().collect()
+    }
+}
+
+/// # Panics
+///
+/// Panics if the columns have different lengths.
+impl From<&BrickColumns> for Vec<Brick> {
+    fn from(columns: &BrickColumns) -> Self {
+        columns.iter().collect()
+    }
+}
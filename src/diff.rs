@@ -0,0 +1,274 @@
+//! Differences between two saves.
+//!
+//! Bricks are matched across saves by their asset, position and orientation,
+//! regardless of their order or the order of the lookup tables, so a save
+//! that was only re-saved or had its tables reordered shows no changes.
+
+use crate::{Brick, Color, ColorMode, User, WriteData};
+use std::collections::{HashMap, VecDeque};
+use uuid::Uuid;
+
+/// A header field of a save.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HeaderField {
+    Map,
+    Author,
+    Description,
+    SaveTime,
+}
+
+/// A field of a brick that can change without the brick being replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BrickField {
+    Size,
+    Collision,
+    Visibility,
+    Material,
+    Color,
+    Owner,
+}
+
+/// Entries added to and removed from a lookup table, compared by value.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TableDiff<T> {
+    pub added: Vec<T>,
+    pub removed: Vec<T>,
+}
+
+impl<T: PartialEq + Clone> TableDiff<T> {
+    fn new(a: &[T], b: &[T]) -> Self {
+        Self {
+            added: b.iter().filter(|v| !a.contains(v)).cloned().collect(),
+            removed: a.iter().filter(|v| !b.contains(v)).cloned().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// A brick present in both saves with some of its fields changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BrickChange {
+    /// The index of the brick in the old save.
+    pub old: usize,
+    /// The index of the brick in the new save.
+    pub new: usize,
+    pub fields: Vec<BrickField>,
+}
+
+/// The changes from one save to another, as found by [`diff`](fn.diff.html).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SaveDiff {
+    /// Header fields that differ.
+    pub header: Vec<HeaderField>,
+    pub mods: TableDiff<String>,
+    pub brick_assets: TableDiff<String>,
+    pub materials: TableDiff<String>,
+    pub colors: TableDiff<Color>,
+    pub brick_owners: TableDiff<User>,
+    /// Indices of bricks in the new save that aren't in the old one.
+    pub added: Vec<usize>,
+    /// Indices of bricks in the old save that aren't in the new one.
+    pub removed: Vec<usize>,
+    /// Bricks in both saves that have changed.
+    pub modified: Vec<BrickChange>,
+}
+
+impl SaveDiff {
+    /// Whether the saves have no differences.
+    pub fn is_empty(&self) -> bool {
+        self.header.is_empty() && self.tables_unchanged() && self.bricks_unchanged()
+    }
+
+    /// Whether the lookup tables contain the same entries.
+    pub fn tables_unchanged(&self) -> bool {
+        self.mods.is_empty()
+            && self.brick_assets.is_empty()
+            && self.materials.is_empty()
+            && self.colors.is_empty()
+            && self.brick_owners.is_empty()
+    }
+
+    /// Whether the bricks are the same.
+    pub fn bricks_unchanged(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Look up `index` in `table`, if it's in range.
+pub(crate) fn lookup<T>(table: &[T], index: u32) -> Option<&T> {
+    table.get(index as usize)
+}
+
+/// The color of `brick`, resolving colors from the lookup table of `data`.
+pub(crate) fn resolve_color(data: &WriteData, brick: &Brick) -> Option<Color> {
+    match brick.color {
+        ColorMode::Set(i) => lookup(&data.colors, i).copied(),
+        ColorMode::Custom(c) => Some(c),
+    }
+}
+
+/// What identifies a brick across saves.
+type Key<'a> = (Option<&'a str>, (i32, i32, i32), u8);
+
+fn key<'a>(data: &'a WriteData, brick: &Brick) -> Key<'a> {
+    (
+        lookup(&data.brick_assets, brick.asset_name_index).map(String::as_str),
+        brick.position,
+        brick.orientation().packed(),
+    )
+}
+
+/// The fields of a brick compared by `changed_fields`, by value.
+type Fields<'a> = (
+    (u32, u32, u32),
+    bool,
+    bool,
+    Option<&'a str>,
+    Option<u32>,
+    Option<(&'a str, Uuid)>,
+);
+
+fn fields<'a>(data: &'a WriteData, brick: &Brick) -> Fields<'a> {
+    let owner = brick
+        .owner_index
+        .and_then(|i| lookup(&data.brick_owners, i));
+    (
+        brick.size,
+        brick.collision,
+        brick.visibility,
+        lookup(&data.materials, brick.material_index).map(String::as_str),
+        resolve_color(data, brick).map(u32::from),
+        owner.map(|o| (o.name.as_str(), o.id)),
+    )
+}
+
+/// The fields that differ between brick `a` of save `old` and brick `b`
+/// of save `new`, comparing table entries by value.
+fn changed_fields(old: &WriteData, a: &Brick, new: &WriteData, b: &Brick) -> Vec<BrickField> {
+    let mut fields = Vec::new();
+    if a.size != b.size {
+        fields.push(BrickField::Size);
+    }
+    if a.collision != b.collision {
+        fields.push(BrickField::Collision);
+    }
+    if a.visibility != b.visibility {
+        fields.push(BrickField::Visibility);
+    }
+    if lookup(&old.materials, a.material_index) != lookup(&new.materials, b.material_index) {
+        fields.push(BrickField::Material);
+    }
+    if resolve_color(old, a) != resolve_color(new, b) {
+        fields.push(BrickField::Color);
+    }
+    let owner_a = a.owner_index.and_then(|i| lookup(&old.brick_owners, i));
+    let owner_b = b.owner_index.and_then(|i| lookup(&new.brick_owners, i));
+    if owner_a != owner_b {
+        fields.push(BrickField::Owner);
+    }
+    fields
+}
+
+/// Find the differences between the saves `old` and `new`.
+///
+/// Bricks sharing an asset, position and orientation are matched to an
+/// identical brick if there is one, and otherwise in the order they appear.
+/// Matched bricks differing in any other field are reported as modified.
+///
+/// ```no_run
+/// # let (old, new): (brs::WriteData, brs::WriteData) = unimplemented!();
+/// let diff = brs::diff(&old, &new);
+/// println!(
+///     "{} added, {} removed, {} modified",
+///     diff.added.len(),
+///     diff.removed.len(),
+///     diff.modified.len(),
+/// );
+/// ```
+pub fn diff(old: &WriteData, new: &WriteData) -> SaveDiff {
+    let mut header = Vec::new();
+    if old.map != new.map {
+        header.push(HeaderField::Map);
+    }
+    if old.author != new.author {
+        header.push(HeaderField::Author);
+    }
+    if old.description != new.description {
+        header.push(HeaderField::Description);
+    }
+    if old.save_time != new.save_time {
+        header.push(HeaderField::SaveTime);
+    }
+
+    // The old bricks for each key, in order, and for each key and
+    // set of fields, so identical bricks can be found directly.
+    let mut unmatched: HashMap<Key, VecDeque<usize>> = HashMap::new();
+    let mut identical: HashMap<(Key, Fields), VecDeque<usize>> = HashMap::new();
+    for (i, brick) in old.bricks.iter().enumerate() {
+        unmatched.entry(key(old, brick)).or_default().push_back(i);
+        identical
+            .entry((key(old, brick), fields(old, brick)))
+            .or_default()
+            .push_back(i);
+    }
+
+    // Match identical bricks first, so that reordering bricks
+    // sharing a key doesn't show up as changes.
+    let mut matched = vec![false; old.bricks.len()];
+    let mut partners = vec![None; new.bricks.len()];
+    for (j, b) in new.bricks.iter().enumerate() {
+        let candidates = identical.get_mut(&(key(new, b), fields(new, b)));
+        if let Some(i) = candidates.and_then(VecDeque::pop_front) {
+            matched[i] = true;
+            partners[j] = Some(i);
+        }
+    }
+
+    let mut added = Vec::new();
+    let mut modified = Vec::new();
+    for (j, b) in new.bricks.iter().enumerate() {
+        // Each old brick is popped at most once, so skipping the ones
+        // already matched keeps this linear.
+        let partner = partners[j].or_else(|| {
+            let candidates = unmatched.get_mut(&key(new, b))?;
+            std::iter::from_fn(|| candidates.pop_front()).find(|&i| !matched[i])
+        });
+
+        match partner {
+            Some(i) => {
+                matched[i] = true;
+                let fields = changed_fields(old, &old.bricks[i], new, b);
+                if !fields.is_empty() {
+                    modified.push(BrickChange {
+                        old: i,
+                        new: j,
+                        fields,
+                    });
+                }
+            }
+            None => added.push(j),
+        }
+    }
+
+    let removed = (0..old.bricks.len()).filter(|&i| !matched[i]).collect();
+
+    SaveDiff {
+        header,
+        mods: TableDiff::new(&old.mods, &new.mods),
+        brick_assets: TableDiff::new(&old.brick_assets, &new.brick_assets),
+        materials: TableDiff::new(&old.materials, &new.materials),
+        colors: TableDiff::new(&old.colors, &new.colors),
+        brick_owners: TableDiff::new(&old.brick_owners, &new.brick_owners),
+        added,
+        removed,
+        modified,
+    }
+}
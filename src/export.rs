@@ -0,0 +1,419 @@
+//! Export of saves as 3D models, in Wavefront OBJ and binary glTF formats.
+//!
+//! Every brick is exported as a box of its bounding box, so bricks that
+//! aren't boxes, such as ramps or round bricks, lose their shape.
+//! Bricks are grouped into one mesh per combination of color and material.
+//!
+//! Models use Y as the up axis, as is usual for both formats, and are scaled
+//! by [`ExportOptions::scale`](struct.ExportOptions.html#structfield.scale).
+
+use crate::{catalog::AssetCatalog, diff::resolve_color, Aabb, Color, WriteData};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    io::{self, Write},
+};
+
+/// Options for exporting a save.
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// Model units per save unit. Defaults to 0.01, for meters.
+    pub scale: f32,
+    /// Used for the sizes of fixed size bricks.
+    pub catalog: AssetCatalog,
+    /// Whether to export bricks with visibility turned off.
+    pub include_invisible: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            scale: 0.01,
+            catalog: AssetCatalog::default(),
+            include_invisible: false,
+        }
+    }
+}
+
+/// How a material of the game is rendered, approximately.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pbr {
+    metallic: f32,
+    roughness: f32,
+    emissive: bool,
+    /// The opacity of the material, multiplied with that of its color.
+    opacity: f32,
+}
+
+impl Pbr {
+    fn new(material: &str) -> Self {
+        let mut pbr = Self {
+            metallic: 0.0,
+            roughness: 0.5,
+            emissive: false,
+            opacity: 1.0,
+        };
+        match material {
+            "BMC_Glow" => pbr.emissive = true,
+            "BMC_Metallic" => {
+                pbr.metallic = 1.0;
+                pbr.roughness = 0.25;
+            }
+            "BMC_Hologram" => pbr.opacity = 0.5,
+            _ => (),
+        }
+        pbr
+    }
+}
+
+/// Boxes sharing a color and material.
+struct Group {
+    color: Color,
+    material: String,
+    boxes: Vec<Aabb>,
+}
+
+impl Group {
+    fn pbr(&self) -> Pbr {
+        Pbr::new(&self.material)
+    }
+
+    /// The color channels from 0 to 1, with the opacity of the material.
+    fn rgba(&self) -> [f32; 4] {
+        let c = self.color;
+        let f = |v: u8| f32::from(v) / 255.0;
+        [f(c.r()), f(c.g()), f(c.b()), f(c.a()) * self.pbr().opacity]
+    }
+
+    fn name(&self) -> String {
+        let c = self.color;
+        format!(
+            "{}_{:02x}{:02x}{:02x}{:02x}",
+            self.material,
+            c.r(),
+            c.g(),
+            c.b(),
+            c.a()
+        )
+    }
+}
+
+/// Collect the boxes of all bricks, grouped in order of first appearance.
+/// Bricks with lookup table indices out of range or no volume are skipped.
+fn groups(data: &WriteData, options: &ExportOptions) -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+    let mut indices = HashMap::new();
+    for brick in &data.bricks {
+        if !brick.visibility && !options.include_invisible {
+            continue;
+        }
+        let color = match resolve_color(data, brick) {
+            Some(color) => color,
+            None => continue,
+        };
+        let material = match data.materials.get(brick.material_index as usize) {
+            Some(material) => material,
+            None => continue,
+        };
+        let aabb = brick.aabb_with(&data.brick_assets, &options.catalog);
+        if aabb.volume() == 0 {
+            continue;
+        }
+
+        let index = *indices
+            .entry((u32::from(color), material))
+            .or_insert_with(|| {
+                groups.push(Group {
+                    color,
+                    material: material.clone(),
+                    boxes: Vec::new(),
+                });
+                groups.len() - 1
+            });
+        groups[index].boxes.push(aabb);
+    }
+    groups
+}
+
+/// The corners of a box in model space, indexed by bits for X, Y and Z.
+/// Swapping Y and Z both makes Y point up and turns the left-handed
+/// coordinates of the game into right-handed ones.
+fn corners(aabb: &Aabb, scale: f32) -> [[f32; 3]; 8] {
+    let min = [aabb.min.0, aabb.min.2, aabb.min.1];
+    let max = [aabb.max.0, aabb.max.2, aabb.max.1];
+    let mut corners = [[0.0; 3]; 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        for axis in 0..3 {
+            let v = if i & (1 << axis) != 0 {
+                max[axis]
+            } else {
+                min[axis]
+            };
+            corner[axis] = (f64::from(v) * f64::from(scale)) as f32;
+        }
+    }
+    corners
+}
+
+/// The faces of a box as corners in counter-clockwise order seen from outside.
+const FACES: [([usize; 4], [f32; 3]); 6] = [
+    ([1, 3, 7, 5], [1.0, 0.0, 0.0]),
+    ([0, 4, 6, 2], [-1.0, 0.0, 0.0]),
+    ([2, 6, 7, 3], [0.0, 1.0, 0.0]),
+    ([0, 1, 5, 4], [0.0, -1.0, 0.0]),
+    ([4, 5, 7, 6], [0.0, 0.0, 1.0]),
+    ([0, 2, 3, 1], [0.0, 0.0, -1.0]),
+];
+
+/// Write `data` as a Wavefront OBJ model to `obj`, with its materials to `mtl`.
+/// `mtl_file` is the path of the material library relative to the model.
+///
+/// ```no_run
+/// # use std::fs::File;
+/// use brs::export::{self, ExportOptions};
+/// # let data: brs::WriteData = unimplemented!();
+/// export::write_obj(
+///     &mut File::create("village.obj")?,
+///     &mut File::create("village.mtl")?,
+///     "village.mtl",
+///     &data,
+///     &ExportOptions::default(),
+/// )?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn write_obj(
+    obj: &mut impl Write,
+    mtl: &mut impl Write,
+    mtl_file: &str,
+    data: &WriteData,
+    options: &ExportOptions,
+) -> io::Result<()> {
+    let groups = groups(data, options);
+
+    for group in &groups {
+        let [r, g, b, a] = group.rgba();
+        let pbr = group.pbr();
+        writeln!(mtl, "newmtl {}", group.name())?;
+        writeln!(mtl, "Kd {} {} {}", r, g, b)?;
+        if pbr.emissive {
+            writeln!(mtl, "Ke {} {} {}", r, g, b)?;
+        }
+        writeln!(mtl, "Pm {}", pbr.metallic)?;
+        writeln!(mtl, "Pr {}", pbr.roughness)?;
+        if a < 1.0 {
+            writeln!(mtl, "d {}", a)?;
+        }
+        writeln!(mtl)?;
+    }
+
+    writeln!(obj, "mtllib {}", mtl_file)?;
+    for (_, n) in &FACES {
+        writeln!(obj, "vn {} {} {}", n[0], n[1], n[2])?;
+    }
+    let mut vertex_count = 0;
+    for group in &groups {
+        writeln!(obj, "usemtl {}", group.name())?;
+        for aabb in &group.boxes {
+            for v in &corners(aabb, options.scale) {
+                writeln!(obj, "v {} {} {}", v[0], v[1], v[2])?;
+            }
+            for (normal, (face, _)) in FACES.iter().enumerate() {
+                write!(obj, "f")?;
+                for corner in face {
+                    write!(obj, " {}//{}", vertex_count + corner + 1, normal + 1)?;
+                }
+                writeln!(obj)?;
+            }
+            vertex_count += 8;
+        }
+    }
+    Ok(())
+}
+
+/// Escape `s` for use in a JSON string.
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Convert an sRGB channel to linear, as glTF expects.
+fn linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Write `data` as a binary glTF (`.glb`) model to `w`.
+///
+/// Materials use the metallic-roughness model. Glowing materials are
+/// emissive and translucent colors and holograms use alpha blending.
+pub fn write_glb(w: &mut impl Write, data: &WriteData, options: &ExportOptions) -> io::Result<()> {
+    let groups = groups(data, options);
+
+    let mut bin = Vec::new();
+    let mut views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut primitives = Vec::new();
+    let mut materials = Vec::new();
+
+    for (index, group) in groups.iter().enumerate() {
+        let vertex_count = group.boxes.len() * 24;
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+
+        // Positions, normals and indices each get a buffer view and accessor.
+        let start = bin.len();
+        for aabb in &group.boxes {
+            let corners = corners(aabb, options.scale);
+            for (face, _) in &FACES {
+                for &corner in face {
+                    for axis in 0..3 {
+                        let v = corners[corner][axis];
+                        min[axis] = min[axis].min(v);
+                        max[axis] = max[axis].max(v);
+                        bin.extend_from_slice(&v.to_le_bytes());
+                    }
+                }
+            }
+        }
+        views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":34962}}"#,
+            start,
+            bin.len() - start
+        ));
+        accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":5126,"count":{},"type":"VEC3","min":[{},{},{}],"max":[{},{},{}]}}"#,
+            views.len() - 1,
+            vertex_count,
+            min[0],
+            min[1],
+            min[2],
+            max[0],
+            max[1],
+            max[2]
+        ));
+
+        let start = bin.len();
+        for _ in &group.boxes {
+            for (_, normal) in &FACES {
+                for _ in 0..4 {
+                    for v in normal {
+                        bin.extend_from_slice(&v.to_le_bytes());
+                    }
+                }
+            }
+        }
+        views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":34962}}"#,
+            start,
+            bin.len() - start
+        ));
+        accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":5126,"count":{},"type":"VEC3"}}"#,
+            views.len() - 1,
+            vertex_count
+        ));
+
+        let start = bin.len();
+        for quad in 0..(vertex_count / 4) as u32 {
+            let base = quad * 4;
+            for i in [0, 1, 2, 0, 2, 3] {
+                bin.extend_from_slice(&(base + i).to_le_bytes());
+            }
+        }
+        views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":34963}}"#,
+            start,
+            bin.len() - start
+        ));
+        accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":5125,"count":{},"type":"SCALAR"}}"#,
+            views.len() - 1,
+            vertex_count / 4 * 6
+        ));
+
+        let first = accessors.len() - 3;
+        primitives.push(format!(
+            r#"{{"attributes":{{"POSITION":{},"NORMAL":{}}},"indices":{},"material":{}}}"#,
+            first,
+            first + 1,
+            first + 2,
+            index
+        ));
+
+        let [r, g, b, a] = group.rgba();
+        let (r, g, b) = (linear(r), linear(g), linear(b));
+        let pbr = group.pbr();
+        let mut material = format!(
+            r#"{{"name":{},"pbrMetallicRoughness":{{"baseColorFactor":[{},{},{},{}],"metallicFactor":{},"roughnessFactor":{}}}"#,
+            json_string(&group.name()),
+            r,
+            g,
+            b,
+            a,
+            pbr.metallic,
+            pbr.roughness
+        );
+        if pbr.emissive {
+            material.push_str(&format!(r#","emissiveFactor":[{},{},{}]"#, r, g, b));
+        }
+        if a < 1.0 {
+            material.push_str(r#","alphaMode":"BLEND""#);
+        }
+        material.push('}');
+        materials.push(material);
+    }
+
+    let mut json = String::from(r#"{"asset":{"version":"2.0","generator":"brs"},"scene":0"#);
+    if groups.is_empty() {
+        // Meshes and buffers can't be empty, so there is nothing but the scene.
+        json.push_str(r#","scenes":[{}]"#);
+    } else {
+        json.push_str(&format!(
+            r#","scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],"meshes":[{{"primitives":[{}]}}],"materials":[{}],"buffers":[{{"byteLength":{}}}],"bufferViews":[{}],"accessors":[{}]"#,
+            primitives.join(","),
+            materials.join(","),
+            bin.len(),
+            views.join(","),
+            accessors.join(",")
+        ));
+    }
+    json.push('}');
+
+    // Chunks are padded to 4 bytes, JSON with spaces and binary with zeros.
+    let mut json = json.into_bytes();
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+    while bin.len() % 4 != 0 {
+        bin.push(0);
+    }
+    let bin_chunk = if bin.is_empty() { 0 } else { 8 + bin.len() };
+    let length = 12 + 8 + json.len() + bin_chunk;
+    let length = u32::try_from(length)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Model too large for glTF"))?;
+
+    w.write_all(b"glTF")?;
+    w.write_all(&2u32.to_le_bytes())?;
+    w.write_all(&length.to_le_bytes())?;
+    w.write_all(&(json.len() as u32).to_le_bytes())?;
+    w.write_all(b"JSON")?;
+    w.write_all(&json)?;
+    if !bin.is_empty() {
+        w.write_all(&(bin.len() as u32).to_le_bytes())?;
+        w.write_all(b"BIN\0")?;
+        w.write_all(&bin)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,777 @@
+//! Import of LDraw `.ldr` and `.mpd` models, as made by LEGO CAD tools.
+//!
+//! Parts are identified by their file name without `.dat`, such as `3001`,
+//! and mapped to bricks with an [`LdrawMapping`](struct.LdrawMapping.html).
+//! Submodels of `.mpd` files are followed, with their placement and color
+//! applied to the parts inside. Lines, triangles and quads drawn directly in
+//! a model are ignored.
+//!
+//! LDraw units are converted by a factor of 2, so a 1x1 brick of
+//! 20 x 20 x 24 becomes 10 x 10 x 12. LDraw's Y axis points down, so it
+//! becomes Brickadia's negative Z, and LDraw's Z becomes Brickadia's Y.
+
+use crate::{
+    save::{Brick, Color, ColorMode, User},
+    transform::table_index,
+    Orientation, WriteData,
+};
+use chrono::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{self, prelude::*, BufReader},
+    path::Path,
+};
+use uuid::Uuid;
+
+/// LDraw units per Brickadia unit.
+const SCALE: f64 = 2.0;
+
+/// How deep submodels can be nested, which also stops reference cycles.
+const MAX_DEPTH: usize = 64;
+
+/// The color code for the color of the referencing line.
+const INHERIT_COLOR: u32 = 16;
+
+/// The color code for the edge color of the referencing line,
+/// which is treated like [`INHERIT_COLOR`](constant.INHERIT_COLOR.html).
+const EDGE_COLOR: u32 = 24;
+
+/// The color code used where the main model inherits its color.
+const DEFAULT_COLOR: u32 = 7;
+
+/// The Brickadia asset and size an LDraw part is mapped to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LdrawPart {
+    pub asset: String,
+    /// The size of the brick in half extents like `Brick::size`, with X
+    /// along the part's X axis and Y along the part's Z axis.
+    pub size: (u32, u32, u32),
+    /// Whether the asset has a fixed size, and bricks should be
+    /// written with a size of `(0, 0, 0)`.
+    pub fixed: bool,
+}
+
+const BUILTIN_PARTS: &[(&str, &str, (u32, u32, u32))] = &[
+    ("3005", "PB_DefaultBrick", (5, 5, 6)),
+    ("3004", "PB_DefaultBrick", (10, 5, 6)),
+    ("3622", "PB_DefaultBrick", (15, 5, 6)),
+    ("3010", "PB_DefaultBrick", (20, 5, 6)),
+    ("3009", "PB_DefaultBrick", (30, 5, 6)),
+    ("3008", "PB_DefaultBrick", (40, 5, 6)),
+    ("6111", "PB_DefaultBrick", (50, 5, 6)),
+    ("6112", "PB_DefaultBrick", (60, 5, 6)),
+    ("3003", "PB_DefaultBrick", (10, 10, 6)),
+    ("3002", "PB_DefaultBrick", (15, 10, 6)),
+    ("3001", "PB_DefaultBrick", (20, 10, 6)),
+    ("2456", "PB_DefaultBrick", (30, 10, 6)),
+    ("3007", "PB_DefaultBrick", (40, 10, 6)),
+    ("3006", "PB_DefaultBrick", (50, 10, 6)),
+    ("3024", "PB_DefaultBrick", (5, 5, 2)),
+    ("3023", "PB_DefaultBrick", (10, 5, 2)),
+    ("3623", "PB_DefaultBrick", (15, 5, 2)),
+    ("3710", "PB_DefaultBrick", (20, 5, 2)),
+    ("3666", "PB_DefaultBrick", (30, 5, 2)),
+    ("3460", "PB_DefaultBrick", (40, 5, 2)),
+    ("4477", "PB_DefaultBrick", (50, 5, 2)),
+    ("60479", "PB_DefaultBrick", (60, 5, 2)),
+    ("3022", "PB_DefaultBrick", (10, 10, 2)),
+    ("3021", "PB_DefaultBrick", (15, 10, 2)),
+    ("3020", "PB_DefaultBrick", (20, 10, 2)),
+    ("3795", "PB_DefaultBrick", (30, 10, 2)),
+    ("3034", "PB_DefaultBrick", (40, 10, 2)),
+    ("3832", "PB_DefaultBrick", (50, 10, 2)),
+    ("3031", "PB_DefaultBrick", (20, 20, 2)),
+    ("3032", "PB_DefaultBrick", (30, 20, 2)),
+    ("3035", "PB_DefaultBrick", (40, 20, 2)),
+    ("3958", "PB_DefaultBrick", (30, 30, 2)),
+    ("3036", "PB_DefaultBrick", (40, 30, 2)),
+    ("41539", "PB_DefaultBrick", (40, 40, 2)),
+    ("3070b", "PB_DefaultTile", (5, 5, 2)),
+    ("3069b", "PB_DefaultTile", (10, 5, 2)),
+    ("63864", "PB_DefaultTile", (15, 5, 2)),
+    ("2431", "PB_DefaultTile", (20, 5, 2)),
+    ("3068b", "PB_DefaultTile", (10, 10, 2)),
+    ("87079", "PB_DefaultTile", (20, 10, 2)),
+];
+
+/// The key of a part or submodel file name, which is lowercase
+/// with forward slashes and without `.dat`.
+fn part_key(name: &str) -> String {
+    let name = name.trim().to_lowercase().replace('\\', "/");
+    match name.strip_suffix(".dat") {
+        Some(name) => name.to_string(),
+        None => name,
+    }
+}
+
+/// A lookup table from LDraw parts to Brickadia bricks.
+///
+/// Part names are compared case-insensitively, with or without `.dat`.
+/// Parts are expected to have their origin at the middle of the top of
+/// their body, below the studs, like plain bricks and plates do.
+///
+/// ```
+/// use brs::ldraw::LdrawMapping;
+/// let mapping = LdrawMapping::default();
+/// assert_eq!(mapping.get("3001.dat").unwrap().size, (20, 10, 6));
+/// assert_eq!(mapping.get("3024").unwrap().size, (5, 5, 2));
+/// assert!(mapping.get("3062b").is_none());
+/// ```
+#[derive(Debug, Clone)]
+pub struct LdrawMapping {
+    parts: HashMap<String, LdrawPart>,
+}
+
+impl Default for LdrawMapping {
+    fn default() -> Self {
+        let mut mapping = Self::empty();
+        for &(name, asset, size) in BUILTIN_PARTS {
+            mapping.insert(
+                name,
+                LdrawPart {
+                    asset: asset.to_string(),
+                    size,
+                    fixed: false,
+                },
+            );
+        }
+        mapping
+    }
+}
+
+impl LdrawMapping {
+    /// Create a mapping without any entries.
+    pub fn empty() -> Self {
+        Self {
+            parts: HashMap::new(),
+        }
+    }
+
+    /// Add or replace the entry for the part `name`.
+    pub fn insert(&mut self, name: &str, part: LdrawPart) -> Option<LdrawPart> {
+        self.parts.insert(part_key(name), part)
+    }
+
+    /// Look up the Brickadia brick for the part `name`.
+    pub fn get(&self, name: &str) -> Option<&LdrawPart> {
+        self.parts.get(&part_key(name))
+    }
+
+    /// Load additional entries from a text file at `path`.
+    /// See [`load`](#method.load) for the format.
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.load(BufReader::new(File::open(path)?))
+    }
+
+    /// Load additional entries from `r`, replacing existing entries
+    /// for the same parts.
+    ///
+    /// Each line holds a part name, an asset, `procedural` or `fixed`,
+    /// and the three size components, separated by whitespace.
+    /// Empty lines and lines starting with `#` are ignored.
+    ///
+    /// ```
+    /// use brs::ldraw::LdrawMapping;
+    /// let mut mapping = LdrawMapping::default();
+    /// mapping.load(&b"3062b.dat B_1x1_Round fixed 5 5 6\n"[..])?;
+    /// assert_eq!(mapping.get("3062B").unwrap().asset, "B_1x1_Round");
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn load(&mut self, r: impl BufRead) -> io::Result<()> {
+        for (number, line) in r.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid LDraw mapping entry on line {}", number + 1),
+                )
+            };
+
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields.len() != 6 {
+                return Err(invalid());
+            }
+            let fixed = match fields[2] {
+                "procedural" => false,
+                "fixed" => true,
+                _ => return Err(invalid()),
+            };
+            let mut size = [0; 3];
+            for (value, field) in size.iter_mut().zip(&fields[3..]) {
+                *value = field.parse().map_err(|_| invalid())?;
+            }
+
+            self.insert(
+                fields[0],
+                LdrawPart {
+                    asset: fields[1].to_string(),
+                    size: (size[0], size[1], size[2]),
+                    fixed,
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The Brickadia color and material of an LDraw color code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LdrawColor {
+    pub color: Color,
+    pub material: String,
+}
+
+/// Solid colors from the LDraw configuration, by color code.
+const BUILTIN_COLORS: &[(u32, u32)] = &[
+    (0, 0x1b2a34),
+    (1, 0x1e5aa8),
+    (2, 0x00852b),
+    (3, 0x069d9f),
+    (4, 0xb40000),
+    (5, 0xd3359d),
+    (6, 0x543324),
+    (7, 0x8a928d),
+    (8, 0x545955),
+    (9, 0x97cbd9),
+    (10, 0x58ab41),
+    (11, 0x00aaa4),
+    (12, 0xf06d61),
+    (13, 0xf6a9bb),
+    (14, 0xfac80a),
+    (15, 0xf4f4f4),
+    (17, 0xadd9a8),
+    (18, 0xffd67f),
+    (19, 0xe4cd9e),
+    (20, 0xc9cae2),
+    (22, 0x81007b),
+    (23, 0x2032b0),
+    (25, 0xd67923),
+    (26, 0x901f76),
+    (27, 0xa5ca18),
+    (28, 0x897d62),
+    (29, 0xff9ecd),
+    (70, 0x5f3109),
+    (71, 0x969696),
+    (72, 0x646464),
+    (73, 0x7396c8),
+    (74, 0x7fc475),
+    (191, 0xfcac00),
+    (226, 0xffec6c),
+    (272, 0x19325a),
+    (288, 0x00451a),
+    (308, 0x352100),
+    (320, 0x720012),
+    (484, 0x91501c),
+];
+
+/// Transparent colors, which become holograms.
+const BUILTIN_TRANSPARENT: &[(u32, u32)] = &[
+    (33, 0x0020a0),
+    (34, 0x237841),
+    (36, 0xc91a09),
+    (40, 0x635f52),
+    (41, 0xaeefec),
+    (46, 0xf5cd2f),
+    (47, 0xfcfcfc),
+];
+
+/// Chrome and metallic colors.
+const BUILTIN_METALLIC: &[(u32, u32)] = &[
+    (80, 0x767676),
+    (82, 0xdbac34),
+    (334, 0xbba53d),
+    (383, 0xe0e0e0),
+];
+
+fn rgb(value: u32, alpha: u8) -> Color {
+    Color::from_rgba((value >> 16) as u8, (value >> 8) as u8, value as u8, alpha)
+}
+
+/// A lookup table from LDraw color codes to colors and materials.
+///
+/// Besides the codes in the table, direct colors like `0x2FF8000` are
+/// supported. Colors defined with `!COLOUR` lines in a model are added
+/// to the table while reading it.
+///
+/// ```
+/// use brs::{ldraw::LdrawColors, Color};
+/// let colors = LdrawColors::default();
+/// assert_eq!(colors.get(4).unwrap().color, Color::from_rgba(180, 0, 0, 255));
+/// assert_eq!(colors.get(47).unwrap().material, "BMC_Hologram");
+/// assert_eq!(colors.get(0x2ff8000).unwrap().color, Color::from_rgba(255, 128, 0, 255));
+/// ```
+#[derive(Debug, Clone)]
+pub struct LdrawColors {
+    colors: HashMap<u32, LdrawColor>,
+}
+
+impl Default for LdrawColors {
+    fn default() -> Self {
+        let mut colors = Self::empty();
+        let mut add = |group: &[(u32, u32)], alpha, material: &str| {
+            for &(code, value) in group {
+                colors.insert(
+                    code,
+                    LdrawColor {
+                        color: rgb(value, alpha),
+                        material: material.to_string(),
+                    },
+                );
+            }
+        };
+        add(BUILTIN_COLORS, 255, "BMC_Plastic");
+        add(BUILTIN_TRANSPARENT, 128, "BMC_Hologram");
+        add(BUILTIN_METALLIC, 255, "BMC_Metallic");
+        colors
+    }
+}
+
+impl LdrawColors {
+    /// Create a table without any entries, which still supports direct colors.
+    pub fn empty() -> Self {
+        Self {
+            colors: HashMap::new(),
+        }
+    }
+
+    /// Add or replace the entry for the color `code`.
+    pub fn insert(&mut self, code: u32, color: LdrawColor) -> Option<LdrawColor> {
+        self.colors.insert(code, color)
+    }
+
+    /// Look up the color `code`.
+    pub fn get(&self, code: u32) -> Option<LdrawColor> {
+        if code >> 24 == 2 {
+            return Some(LdrawColor {
+                color: rgb(code, 255),
+                material: "BMC_Plastic".to_string(),
+            });
+        }
+        self.colors.get(&code).cloned()
+    }
+
+    /// Load the colors of an LDraw configuration file at `path`,
+    /// usually named `LDConfig.ldr`.
+    /// See [`load`](#method.load) for the format.
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.load(BufReader::new(File::open(path)?))
+    }
+
+    /// Load the colors defined by `0 !COLOUR` lines in `r`, replacing
+    /// existing entries with the same codes. Other lines are ignored.
+    ///
+    /// Colors with an `ALPHA` below 255 become holograms, colors with a
+    /// `LUMINANCE` glow and `CHROME`, `METAL` and `PEARLESCENT` colors
+    /// are metallic.
+    ///
+    /// ```
+    /// use brs::ldraw::LdrawColors;
+    /// let mut colors = LdrawColors::empty();
+    /// colors.load(&b"0 !COLOUR Glow_In_Dark CODE 21 VALUE #E0FFB0 EDGE #A4C374 LUMINANCE 15\n"[..])?;
+    /// assert_eq!(colors.get(21).unwrap().material, "BMC_Glow");
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn load(&mut self, r: impl BufRead) -> io::Result<()> {
+        for (number, line) in r.lines().enumerate() {
+            self.load_line(&line?, number)?;
+        }
+        Ok(())
+    }
+
+    /// Add the color defined on `line` if it's a `!COLOUR` line.
+    fn load_line(&mut self, line: &str, number: usize) -> io::Result<()> {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some("0") || tokens.next() != Some("!COLOUR") {
+            return Ok(());
+        }
+        let invalid = || invalid_data(number, "color definition");
+
+        tokens.next().ok_or_else(invalid)?;
+        let mut code = None;
+        let mut value = None;
+        let mut alpha = 255;
+        let mut material = "BMC_Plastic";
+        while let Some(token) = tokens.next() {
+            match token {
+                "CODE" => code = tokens.next().and_then(|c| c.parse().ok()),
+                "VALUE" => {
+                    value = tokens
+                        .next()
+                        .and_then(|v| v.strip_prefix('#'))
+                        .and_then(|v| u32::from_str_radix(v, 16).ok())
+                }
+                "ALPHA" => {
+                    alpha = tokens
+                        .next()
+                        .and_then(|a| a.parse().ok())
+                        .ok_or_else(invalid)?;
+                }
+                "LUMINANCE" if tokens.next().and_then(|l| l.parse::<u8>().ok()) > Some(0) => {
+                    material = "BMC_Glow"
+                }
+                "CHROME" | "METAL" | "PEARLESCENT" => material = "BMC_Metallic",
+                _ => (),
+            }
+        }
+        if alpha < 255 {
+            material = "BMC_Hologram";
+        }
+
+        match (code, value) {
+            (Some(code), Some(value)) => {
+                self.insert(
+                    code,
+                    LdrawColor {
+                        color: rgb(value, alpha),
+                        material: material.to_string(),
+                    },
+                );
+                Ok(())
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Options for [`read`](fn.read.html).
+#[derive(Debug, Clone)]
+pub struct LdrawOptions {
+    pub mapping: LdrawMapping,
+    pub colors: LdrawColors,
+    /// The most part and submodel references to place. A submodel used
+    /// several times is placed once for each use, so a small file can
+    /// describe a huge model. Larger models are rejected with an
+    /// `InvalidData` error. Defaults to `1 << 22`.
+    pub max_parts: usize,
+}
+
+impl Default for LdrawOptions {
+    fn default() -> Self {
+        Self {
+            mapping: LdrawMapping::default(),
+            colors: LdrawColors::default(),
+            max_parts: 1 << 22,
+        }
+    }
+}
+
+/// The result of reading an LDraw model.
+#[derive(Debug, Clone)]
+pub struct LdrawImport {
+    pub data: WriteData,
+    /// Parts without a mapping, with how many were skipped.
+    pub unmapped: BTreeMap<String, usize>,
+    /// How many parts were skipped because they aren't rotated
+    /// in multiples of 90 degrees, or are mirrored.
+    pub unaligned: usize,
+}
+
+fn invalid_data(line: usize, what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid LDraw {} on line {}", what, line + 1),
+    )
+}
+
+/// A placement of a part or submodel, in LDraw units and axes.
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    matrix: [[f64; 3]; 3],
+    position: [f64; 3],
+    color: u32,
+}
+
+impl Placement {
+    /// The placement of `child` inside this one.
+    fn then(&self, child: &Placement) -> Placement {
+        let mut matrix = [[0.0; 3]; 3];
+        let mut position = self.position;
+        for (i, row) in self.matrix.iter().enumerate() {
+            for (j, value) in matrix[i].iter_mut().enumerate() {
+                *value = (0..3).map(|k| row[k] * child.matrix[k][j]).sum();
+            }
+            position[i] += (0..3).map(|k| row[k] * child.position[k]).sum::<f64>();
+        }
+        let color = match child.color {
+            INHERIT_COLOR | EDGE_COLOR => self.color,
+            color => color,
+        };
+        Placement {
+            matrix,
+            position,
+            color,
+        }
+    }
+
+    /// The point `v` of the placed part or submodel.
+    fn apply(&self, v: [f64; 3]) -> [f64; 3] {
+        let mut out = self.position;
+        for (i, row) in self.matrix.iter().enumerate() {
+            out[i] += row[0] * v[0] + row[1] * v[1] + row[2] * v[2];
+        }
+        out
+    }
+
+    /// The Brickadia orientation of the placed part, if it's axis-aligned.
+    fn orientation(&self) -> Option<Orientation> {
+        // Brickadia (x, y, z) is LDraw (x, z, -y), so the basis change
+        // swaps rows and columns of Y and Z, negating the new Z.
+        let axis = |i: usize| match i {
+            0 => (0, 1.0),
+            1 => (2, 1.0),
+            _ => (1, -1.0),
+        };
+        let mut m = [[0; 3]; 3];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                let ((si, fi), (sj, fj)) = (axis(i), axis(j));
+                let v = self.matrix[si][sj] * fi * fj;
+                if (v - v.round()).abs() > 1e-3 {
+                    return None;
+                }
+                *value = v.round() as i32;
+            }
+        }
+        Orientation::from_matrix(&m)
+    }
+}
+
+/// A line referencing a part or submodel.
+struct Reference<'a> {
+    line: usize,
+    placement: Placement,
+    name: &'a str,
+}
+
+fn parse_color(code: &str) -> Option<u32> {
+    match code.strip_prefix("0x").or_else(|| code.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => code.parse().ok(),
+    }
+}
+
+/// Parse a line of type 1, without the leading `1`.
+fn parse_reference(rest: &str, line: usize) -> io::Result<Reference<'_>> {
+    let invalid = || invalid_data(line, "part reference");
+    let mut rest = rest.trim_start();
+    let mut fields = [""; 13];
+    for field in fields.iter_mut() {
+        let end = rest.find(char::is_whitespace).ok_or_else(invalid)?;
+        *field = &rest[..end];
+        rest = rest[end..].trim_start();
+    }
+    let name = rest.trim_end();
+    if name.is_empty() {
+        return Err(invalid());
+    }
+
+    let color = parse_color(fields[0]).ok_or_else(invalid)?;
+    let mut numbers = [0.0; 12];
+    for (number, field) in numbers.iter_mut().zip(&fields[1..]) {
+        *number = field.parse::<f64>().map_err(|_| invalid())?;
+        if !number.is_finite() {
+            return Err(invalid());
+        }
+    }
+    let [x, y, z, a, b, c, d, e, f, g, h, i] = numbers;
+    Ok(Reference {
+        line,
+        placement: Placement {
+            matrix: [[a, b, c], [d, e, f], [g, h, i]],
+            position: [x, y, z],
+            color,
+        },
+        name,
+    })
+}
+
+struct Reader<'a> {
+    options: &'a LdrawOptions,
+    colors: LdrawColors,
+    files: HashMap<String, Vec<Reference<'a>>>,
+    data: WriteData,
+    unmapped: BTreeMap<String, usize>,
+    unaligned: usize,
+    /// How many more references can be placed.
+    budget: usize,
+}
+
+impl Reader<'_> {
+    fn add(&mut self, file: &str, placement: &Placement, depth: usize) -> io::Result<()> {
+        let references = match self.files.get(file) {
+            Some(references) => references,
+            None => return Ok(()),
+        };
+        // Collect the placements first so the file table isn't borrowed
+        // while adding bricks.
+        let references = references
+            .iter()
+            .map(|r| (r.line, placement.then(&r.placement), part_key(r.name)))
+            .collect::<Vec<_>>();
+
+        for (line, placement, name) in references {
+            self.budget = self.budget.checked_sub(1).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Too many LDraw parts placed by line {}", line + 1),
+                )
+            })?;
+            if self.files.contains_key(&name) {
+                if depth >= MAX_DEPTH {
+                    return Err(invalid_data(line, "submodel nesting"));
+                }
+                self.add(&name, &placement, depth + 1)?;
+            } else {
+                self.add_part(&name, &placement);
+            }
+        }
+        Ok(())
+    }
+
+    fn add_part(&mut self, name: &str, placement: &Placement) {
+        let part = match self.options.mapping.get(name) {
+            Some(part) => part,
+            None => {
+                *self.unmapped.entry(name.to_string()).or_insert(0) += 1;
+                return;
+            }
+        };
+        let orientation = match placement.orientation() {
+            Some(orientation) => orientation,
+            None => {
+                self.unaligned += 1;
+                return;
+            }
+        };
+
+        // The part's origin is on top, with its body below it along +Y.
+        let center = placement.apply([0.0, f64::from(part.size.2) * SCALE, 0.0]);
+        let position = (
+            (center[0] / SCALE).round() as i32,
+            (center[2] / SCALE).round() as i32,
+            (-center[1] / SCALE).round() as i32,
+        );
+
+        let color = self
+            .colors
+            .get(placement.color)
+            .or_else(|| self.colors.get(DEFAULT_COLOR))
+            .unwrap_or_else(|| LdrawColor {
+                color: rgb(0x8a928d, 255),
+                material: "BMC_Plastic".to_string(),
+            });
+
+        let (direction, rotation) = orientation.into();
+        self.data.bricks.push(Brick {
+            asset_name_index: table_index(&mut self.data.brick_assets, &part.asset),
+            size: if part.fixed { (0, 0, 0) } else { part.size },
+            position,
+            direction,
+            rotation,
+            collision: true,
+            visibility: true,
+            material_index: table_index(&mut self.data.materials, &color.material),
+            color: ColorMode::Set(table_index(&mut self.data.colors, &color.color)),
+            owner_index: None,
+        });
+    }
+}
+
+/// Read an LDraw `.ldr` or `.mpd` model from `r`.
+///
+/// The first file of an `.mpd` is the main model. Parts without a mapping
+/// are skipped and counted in
+/// [`LdrawImport::unmapped`](struct.LdrawImport.html#structfield.unmapped),
+/// and parts with an unknown color get the color of code 7, light gray.
+///
+/// ```
+/// use brs::ldraw::{self, LdrawOptions};
+/// let model = b"0 FILE house.ldr\n\
+///     1 4 0 0 0 1 0 0 0 1 0 0 0 1 wall.ldr\n\
+///     1 16 0 -24 0 0 0 1 0 1 0 -1 0 0 wall.ldr\n\
+///     0 FILE wall.ldr\n\
+///     1 16 0 0 0 1 0 0 0 1 0 0 0 1 3001.dat\n";
+/// let import = ldraw::read(&model[..], &LdrawOptions::default())?;
+/// assert_eq!(import.data.bricks.len(), 2);
+/// // The upper brick is one brick height higher and turned by 90 degrees.
+/// assert_eq!(import.data.bricks[0].position, (0, 0, -6));
+/// assert_eq!(import.data.bricks[1].position, (0, 0, 6));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read(mut r: impl Read, options: &LdrawOptions) -> io::Result<LdrawImport> {
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes)?;
+    let text = String::from_utf8_lossy(&bytes);
+
+    let mut colors = options.colors.clone();
+    let mut files: HashMap<String, Vec<Reference>> = HashMap::new();
+    let mut main = None;
+    let mut current = Some(String::new());
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix('0') {
+            let rest = rest.trim_start();
+            if let Some(name) = rest.strip_prefix("FILE ") {
+                let name = part_key(name);
+                main.get_or_insert_with(|| name.clone());
+                files.entry(name.clone()).or_default();
+                current = Some(name);
+            } else if rest == "NOFILE" {
+                current = None;
+            } else {
+                colors.load_line(line, number)?;
+            }
+        } else if let Some(rest) = line.strip_prefix('1') {
+            if !rest.starts_with(char::is_whitespace) {
+                return Err(invalid_data(number, "line type"));
+            }
+            if let Some(file) = &current {
+                let reference = parse_reference(rest, number)?;
+                files.entry(file.clone()).or_default().push(reference);
+            }
+        }
+    }
+
+    let main = main.unwrap_or_default();
+    let mut reader = Reader {
+        options,
+        colors,
+        files,
+        data: WriteData {
+            map: "Plate".to_string(),
+            author: User {
+                id: Uuid::nil(),
+                name: "LDraw".to_string(),
+            },
+            description: String::new(),
+            save_time: Utc::now(),
+            mods: Vec::new(),
+            brick_assets: Vec::new(),
+            colors: Vec::new(),
+            materials: Vec::new(),
+            brick_owners: Vec::new(),
+            bricks: Vec::new(),
+        },
+        unmapped: BTreeMap::new(),
+        unaligned: 0,
+        budget: options.max_parts,
+    };
+    let root = Placement {
+        matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+        position: [0.0; 3],
+        color: DEFAULT_COLOR,
+    };
+    reader.add(&main, &root, 0)?;
+
+    Ok(LdrawImport {
+        data: reader.data,
+        unmapped: reader.unmapped,
+        unaligned: reader.unaligned,
+    })
+}
@@ -105,14 +105,42 @@
 
 mod bit_reader;
 mod bit_writer;
+mod bounds;
+mod columns;
+mod orientation;
 mod save;
 
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod bls;
+pub mod catalog;
+pub mod diff;
+pub mod export;
+pub mod ldraw;
+pub mod optimize;
+pub mod overlap;
+pub mod patch;
+pub mod pixel_art;
 pub mod read;
+pub mod render;
+pub mod schem;
+pub mod spatial;
+pub mod stats;
+pub mod terrain;
+mod transform;
+pub mod vox;
+pub mod voxelize;
+#[cfg(feature = "wasm-bindgen")]
+pub mod wasm;
 mod write;
 
+pub use bounds::Aabb;
+pub use columns::BrickColumns;
+pub use diff::diff;
+pub use orientation::{Matrix, Orientation};
 pub use read::{HasHeader1, HasHeader2, Reader};
 pub use save::*;
-pub use write::{write_save, WriteData};
+pub use write::{write_save, write_save_columns, WriteData};
 
 pub use chrono;
 pub use uuid;
@@ -136,5 +164,5 @@ pub enum Version {
 pub const VERSION_WRITE: Version = Version::AddedDateTime;
 
 fn ue4_date_time_base() -> DateTime<Utc> {
-    Utc.ymd(1, 1, 1).and_hms(0, 0, 0)
+    Utc.with_ymd_and_hms(1, 1, 1, 0, 0, 0).unwrap()
 }
@@ -0,0 +1,234 @@
+//! Reducing the brick count of a save by merging bricks.
+//!
+//! Box-shaped procedural bricks, such as plain bricks and tiles, that line up
+//! exactly, sharing a full face, are merged into one larger brick when they
+//! look and behave the same: same asset, orientation, material, color, owner,
+//! collision and visibility.
+//! The merged bricks cover exactly the volume of the bricks they replace.
+
+use crate::{catalog::AssetCatalog, Aabb, Brick, ColorMode, WriteData};
+use std::collections::HashMap;
+
+/// The default largest size of merged bricks along each axis, in half
+/// extents. This is 100 studs, comfortably within what the game allows.
+pub const MAX_BRICK_SIZE: u32 = 500;
+
+/// Options for [`merge_bricks`](fn.merge_bricks.html).
+#[derive(Debug, Clone)]
+pub struct MergeOptions {
+    /// The largest size a merged brick may have along each axis,
+    /// in half extents like `Brick::size`.
+    /// Defaults to [`MAX_BRICK_SIZE`](constant.MAX_BRICK_SIZE.html).
+    pub max_size: u32,
+    /// Used to find the box-shaped procedural assets. Bricks of all other
+    /// assets, including ones missing from the catalog, are never merged.
+    pub catalog: AssetCatalog,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            max_size: MAX_BRICK_SIZE,
+            catalog: AssetCatalog::default(),
+        }
+    }
+}
+
+/// The brick counts before and after merging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MergeReport {
+    pub before: usize,
+    pub after: usize,
+}
+
+impl MergeReport {
+    /// The number of bricks merged away.
+    pub fn removed(&self) -> usize {
+        self.before - self.after
+    }
+}
+
+/// The properties bricks must share to be merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Key {
+    asset: u32,
+    orientation: u8,
+    material: u32,
+    color: (bool, u32),
+    owner: Option<u32>,
+    collision: bool,
+    visibility: bool,
+}
+
+impl Key {
+    fn new(brick: &Brick) -> Self {
+        Self {
+            asset: brick.asset_name_index,
+            orientation: brick.orientation().packed(),
+            material: brick.material_index,
+            color: match brick.color {
+                ColorMode::Set(i) => (false, i),
+                ColorMode::Custom(c) => (true, c.into()),
+            },
+            owner: brick.owner_index,
+            collision: brick.collision,
+            visibility: brick.visibility,
+        }
+    }
+}
+
+/// A box being grown, made of the bricks starting at `first`.
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    key: Key,
+    aabb: Aabb,
+    /// The lowest index of the bricks merged into this block,
+    /// which decides where the merged brick goes in the save.
+    first: usize,
+}
+
+fn axis(point: (i32, i32, i32), axis: usize) -> i32 {
+    match axis {
+        0 => point.0,
+        1 => point.1,
+        _ => point.2,
+    }
+}
+
+/// Merge blocks sharing a full face perpendicular to `along`.
+/// Returns whether any blocks were merged.
+fn merge_along(blocks: &mut Vec<Block>, along: usize, max_size: u32) -> bool {
+    let (a, b) = ((along + 1) % 3, (along + 2) % 3);
+
+    // Blocks can only merge with blocks of the same key and cross-section.
+    let mut rows: HashMap<_, Vec<usize>> = HashMap::new();
+    for (i, block) in blocks.iter().enumerate() {
+        let section = (
+            axis(block.aabb.min, a),
+            axis(block.aabb.max, a),
+            axis(block.aabb.min, b),
+            axis(block.aabb.max, b),
+        );
+        rows.entry((block.key, section)).or_default().push(i);
+    }
+
+    let mut merged = false;
+    let mut removed = vec![false; blocks.len()];
+    for row in rows.values_mut() {
+        row.sort_by_key(|&i| axis(blocks[i].aabb.min, along));
+        let mut current = row[0];
+        for &next in &row[1..] {
+            let (cur, nxt) = (blocks[current], blocks[next]);
+            let length =
+                i64::from(axis(nxt.aabb.max, along)) - i64::from(axis(cur.aabb.min, along));
+            if axis(cur.aabb.max, along) == axis(nxt.aabb.min, along)
+                && length <= 2 * i64::from(max_size)
+            {
+                blocks[current].aabb = cur.aabb.union(&nxt.aabb);
+                blocks[current].first = cur.first.min(nxt.first);
+                removed[next] = true;
+                merged = true;
+            } else {
+                current = next;
+            }
+        }
+    }
+
+    if merged {
+        let mut i = 0;
+        blocks.retain(|_| {
+            i += 1;
+            !removed[i - 1]
+        });
+    }
+    merged
+}
+
+/// Merge adjacent bricks of `data` into larger ones, returning how many
+/// bricks there were before and after.
+///
+/// Bricks are merged greedily, into runs along the X axis, then rows
+/// of runs along Y, then layers along Z, repeating until nothing changes.
+/// Merged bricks take the place of the earliest brick they replace, and
+/// all other bricks keep their order.
+///
+/// ```no_run
+/// use brs::optimize::{self, MergeOptions};
+/// # let mut data: brs::WriteData = unimplemented!();
+/// let report = optimize::merge_bricks(&mut data, &MergeOptions::default());
+/// println!("{} -> {} bricks", report.before, report.after);
+/// ```
+pub fn merge_bricks(data: &mut WriteData, options: &MergeOptions) -> MergeReport {
+    let before = data.bricks.len();
+
+    let box_shaped = data
+        .brick_assets
+        .iter()
+        .map(|name| {
+            options
+                .catalog
+                .get(name)
+                .is_some_and(|info| info.is_procedural() && info.box_shaped)
+        })
+        .collect::<Vec<_>>();
+    let mergeable = |brick: &Brick| {
+        brick.size != (0, 0, 0)
+            && box_shaped
+                .get(brick.asset_name_index as usize)
+                .copied()
+                .unwrap_or(false)
+    };
+
+    let mut blocks = Vec::new();
+    let mut kept = Vec::new();
+    for (i, brick) in data.bricks.iter().enumerate() {
+        if mergeable(brick) {
+            blocks.push(Block {
+                key: Key::new(brick),
+                aabb: brick.aabb(),
+                first: i,
+            });
+        } else {
+            kept.push(i);
+        }
+    }
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for along in 0..3 {
+            changed |= merge_along(&mut blocks, along, options.max_size);
+        }
+    }
+
+    let mut out = kept
+        .into_iter()
+        .map(|i| (i, data.bricks[i].clone()))
+        .collect::<Vec<_>>();
+    for block in blocks {
+        let template = &data.bricks[block.first];
+        // Half of a difference of two `i32`s always fits in one.
+        let (x, y, z) = block.aabb.size();
+        let half = ((x / 2) as i32, (y / 2) as i32, (z / 2) as i32);
+        // Bricks that weren't merged are left exactly as they were.
+        let brick = if template.aabb() == block.aabb {
+            template.clone()
+        } else {
+            let (x, y, z) = template.orientation().inverse().apply(half);
+            Brick {
+                size: (x.unsigned_abs(), y.unsigned_abs(), z.unsigned_abs()),
+                position: block.aabb.center(),
+                ..template.clone()
+            }
+        };
+        out.push((block.first, brick));
+    }
+    out.sort_by_key(|&(i, _)| i);
+
+    data.bricks = out.into_iter().map(|(_, brick)| brick).collect();
+    MergeReport {
+        before,
+        after: data.bricks.len(),
+    }
+}
@@ -0,0 +1,303 @@
+use crate::save::{Direction, Rotation};
+use std::{convert::TryInto, ops::Mul};
+
+/// A 3x3 integer rotation matrix, stored row-major.
+///
+/// Applying it to a column vector `v` gives `m * v`.
+pub type Matrix = [[i32; 3]; 3];
+
+const DIRECTIONS: [Direction; 6] = [
+    Direction::XPositive,
+    Direction::XNegative,
+    Direction::YPositive,
+    Direction::YNegative,
+    Direction::ZPositive,
+    Direction::ZNegative,
+];
+
+const ROTATIONS: [Rotation; 4] = [
+    Rotation::Deg0,
+    Rotation::Deg90,
+    Rotation::Deg180,
+    Rotation::Deg270,
+];
+
+/// The orientation of a brick, a combination of a
+/// [`Direction`](enum.Direction.html) and a [`Rotation`](enum.Rotation.html).
+///
+/// There are exactly 24 orientations, one for each axis-aligned rotation.
+/// The brick is first rotated by `rotation` around its local Z axis, after
+/// which its local positive Z axis is turned to face `direction`.
+///
+/// ```
+/// use brs::{Direction, Orientation, Rotation};
+/// let o = Orientation::new(Direction::XPositive, Rotation::Deg90);
+/// assert_eq!(o * o.inverse(), Orientation::IDENTITY);
+/// assert_eq!(o.apply((0, 0, 1)), (1, 0, 0));
+/// assert_eq!(Orientation::all().count(), 24);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Orientation {
+    pub direction: Direction,
+    pub rotation: Rotation,
+}
+
+impl Default for Orientation {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Orientation {
+    /// The orientation that leaves everything as is.
+    pub const IDENTITY: Self = Self {
+        direction: Direction::ZPositive,
+        rotation: Rotation::Deg0,
+    };
+
+    pub fn new(direction: Direction, rotation: Rotation) -> Self {
+        Self {
+            direction,
+            rotation,
+        }
+    }
+
+    /// Splits a packed orientation, as stored in save files, into its
+    /// corresponding direction and rotation.
+    ///
+    /// Values of 24 and above don't name an orientation, and are wrapped
+    /// around onto one. Use [`try_from_packed`](#method.try_from_packed)
+    /// to reject them instead.
+    pub fn from_packed(orientation: u8) -> Self {
+        Self {
+            direction: ((orientation >> 2) % 6).try_into().unwrap(),
+            rotation: (orientation & 0b11).try_into().unwrap(),
+        }
+    }
+
+    /// Splits a packed orientation like [`from_packed`](#method.from_packed),
+    /// returning `None` if it is 24 or above.
+    ///
+    /// ```
+    /// use brs::Orientation;
+    /// for o in Orientation::all() {
+    ///     assert_eq!(Orientation::try_from_packed(o.packed()), Some(o));
+    /// }
+    /// assert_eq!(Orientation::try_from_packed(24), None);
+    /// ```
+    pub fn try_from_packed(orientation: u8) -> Option<Self> {
+        if orientation < 24 {
+            Some(Self::from_packed(orientation))
+        } else {
+            None
+        }
+    }
+
+    /// Combines the direction and rotation into their corresponding packed
+    /// orientation, as stored in save files.
+    pub fn packed(self) -> u8 {
+        (u8::from(self.direction) << 2) | u8::from(self.rotation)
+    }
+
+    /// Iterate over all 24 valid orientations, in packed order.
+    pub fn all() -> impl Iterator<Item = Self> {
+        DIRECTIONS
+            .iter()
+            .flat_map(|&d| ROTATIONS.iter().map(move |&r| Self::new(d, r)))
+    }
+
+    /// The rotation matrix of this orientation.
+    pub fn matrix(self) -> Matrix {
+        mat_mul(
+            &direction_matrix(self.direction),
+            &rotation_matrix(self.rotation),
+        )
+    }
+
+    /// Find the orientation with the rotation matrix `m`.
+    /// Returns `None` if `m` isn't an axis-aligned rotation.
+    ///
+    /// ```
+    /// use brs::Orientation;
+    /// for o in Orientation::all() {
+    ///     assert_eq!(Orientation::from_matrix(&o.matrix()), Some(o));
+    /// }
+    /// assert_eq!(Orientation::from_matrix(&[[2, 0, 0], [0, 1, 0], [0, 0, 1]]), None);
+    /// ```
+    pub fn from_matrix(m: &Matrix) -> Option<Self> {
+        Self::all().find(|o| o.matrix() == *m)
+    }
+
+    /// Rotate the integer vector `v` by this orientation.
+    pub fn apply(self, v: (i32, i32, i32)) -> (i32, i32, i32) {
+        let m = self.matrix();
+        let v = [v.0, v.1, v.2];
+        let row = |r: &[i32; 3]| r[0] * v[0] + r[1] * v[1] + r[2] * v[2];
+        (row(&m[0]), row(&m[1]), row(&m[2]))
+    }
+
+    /// Rotate the integer vector `v` by this orientation,
+    /// returning `None` if a component overflows.
+    ///
+    /// ```
+    /// use brs::{Direction, Orientation, Rotation};
+    /// let o = Orientation::new(Direction::ZNegative, Rotation::Deg0);
+    /// assert_eq!(o.checked_apply((0, 0, i32::MAX)), Some((0, 0, -i32::MAX)));
+    /// assert_eq!(o.checked_apply((0, 0, i32::MIN)), None);
+    /// ```
+    pub fn checked_apply(self, v: (i32, i32, i32)) -> Option<(i32, i32, i32)> {
+        let m = self.matrix();
+        let v = [v.0, v.1, v.2];
+        let row = |r: &[i32; 3]| {
+            r[0].checked_mul(v[0])?
+                .checked_add(r[1].checked_mul(v[1])?)?
+                .checked_add(r[2].checked_mul(v[2])?)
+        };
+        Some((row(&m[0])?, row(&m[1])?, row(&m[2])?))
+    }
+
+    /// The orientation that results from first applying `self`, then `other`.
+    /// Equivalent to `other * self`.
+    pub fn then(self, other: Self) -> Self {
+        other * self
+    }
+
+    /// The orientation that undoes this one.
+    pub fn inverse(self) -> Self {
+        let m = self.matrix();
+        let mut t = [[0; 3]; 3];
+        for (i, row) in m.iter().enumerate() {
+            for (j, &value) in row.iter().enumerate() {
+                t[j][i] = value;
+            }
+        }
+        Self::from_matrix(&t).unwrap()
+    }
+
+    /// The unit quaternion of this orientation, as `[x, y, z, w]`.
+    pub fn quaternion(self) -> [f64; 4] {
+        let m = self.matrix();
+        let m = |i: usize, j: usize| f64::from(m[i][j]);
+        let trace = m(0, 0) + m(1, 1) + m(2, 2);
+
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            [
+                (m(2, 1) - m(1, 2)) / s,
+                (m(0, 2) - m(2, 0)) / s,
+                (m(1, 0) - m(0, 1)) / s,
+                0.25 * s,
+            ]
+        } else if m(0, 0) > m(1, 1) && m(0, 0) > m(2, 2) {
+            let s = (1.0 + m(0, 0) - m(1, 1) - m(2, 2)).sqrt() * 2.0;
+            [
+                0.25 * s,
+                (m(0, 1) + m(1, 0)) / s,
+                (m(0, 2) + m(2, 0)) / s,
+                (m(2, 1) - m(1, 2)) / s,
+            ]
+        } else if m(1, 1) > m(2, 2) {
+            let s = (1.0 + m(1, 1) - m(0, 0) - m(2, 2)).sqrt() * 2.0;
+            [
+                (m(0, 1) + m(1, 0)) / s,
+                0.25 * s,
+                (m(1, 2) + m(2, 1)) / s,
+                (m(0, 2) - m(2, 0)) / s,
+            ]
+        } else {
+            let s = (1.0 + m(2, 2) - m(0, 0) - m(1, 1)).sqrt() * 2.0;
+            [
+                (m(0, 2) + m(2, 0)) / s,
+                (m(1, 2) + m(2, 1)) / s,
+                0.25 * s,
+                (m(1, 0) - m(0, 1)) / s,
+            ]
+        };
+
+        // Keep a canonical sign so equal rotations give equal quaternions.
+        let leading = q.iter().rev().copied().find(|&c| c != 0.0);
+        if leading.unwrap_or(0.0) < 0.0 {
+            [-q[0], -q[1], -q[2], -q[3]]
+        } else {
+            q
+        }
+    }
+
+    /// Find the orientation matching the quaternion `q`, given as
+    /// `[x, y, z, w]`. `q` doesn't need to be normalized.
+    /// Returns `None` if `q` isn't (close to) an axis-aligned rotation.
+    ///
+    /// ```
+    /// use brs::Orientation;
+    /// for o in Orientation::all() {
+    ///     assert_eq!(Orientation::from_quaternion(o.quaternion()), Some(o));
+    /// }
+    /// ```
+    pub fn from_quaternion(q: [f64; 4]) -> Option<Self> {
+        let len = q.iter().map(|c| c * c).sum::<f64>().sqrt();
+        if !len.is_normal() {
+            return None;
+        }
+
+        Self::all().find(|o| {
+            let p = o.quaternion();
+            let dot = (0..4).map(|i| p[i] * q[i] / len).sum::<f64>();
+            dot.abs() > 1.0 - 1e-6
+        })
+    }
+}
+
+impl Mul for Orientation {
+    type Output = Self;
+
+    /// Compose two orientations, such that `(a * b).apply(v)`
+    /// is `a.apply(b.apply(v))`.
+    fn mul(self, rhs: Self) -> Self {
+        Self::from_matrix(&mat_mul(&self.matrix(), &rhs.matrix())).unwrap()
+    }
+}
+
+impl From<(Direction, Rotation)> for Orientation {
+    fn from((direction, rotation): (Direction, Rotation)) -> Self {
+        Self::new(direction, rotation)
+    }
+}
+
+impl From<Orientation> for (Direction, Rotation) {
+    fn from(o: Orientation) -> Self {
+        (o.direction, o.rotation)
+    }
+}
+
+/// The rotation that turns positive Z to face `direction`.
+fn direction_matrix(direction: Direction) -> Matrix {
+    match direction {
+        Direction::XPositive => [[0, 0, 1], [0, 1, 0], [-1, 0, 0]],
+        Direction::XNegative => [[0, 0, -1], [0, 1, 0], [1, 0, 0]],
+        Direction::YPositive => [[1, 0, 0], [0, 0, 1], [0, -1, 0]],
+        Direction::YNegative => [[1, 0, 0], [0, 0, -1], [0, 1, 0]],
+        Direction::ZPositive => [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+        Direction::ZNegative => [[1, 0, 0], [0, -1, 0], [0, 0, -1]],
+    }
+}
+
+/// The rotation around positive Z by `rotation`.
+fn rotation_matrix(rotation: Rotation) -> Matrix {
+    match rotation {
+        Rotation::Deg0 => [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+        Rotation::Deg90 => [[0, -1, 0], [1, 0, 0], [0, 0, 1]],
+        Rotation::Deg180 => [[-1, 0, 0], [0, -1, 0], [0, 0, 1]],
+        Rotation::Deg270 => [[0, 1, 0], [-1, 0, 0], [0, 0, 1]],
+    }
+}
+
+fn mat_mul(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0; 3]; 3];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
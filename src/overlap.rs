@@ -0,0 +1,122 @@
+//! Detection of duplicated and intersecting bricks.
+//!
+//! Bricks sharing volume render with z-fighting in game. Overlaps are found
+//! by comparing bounding boxes, so bricks that aren't boxes, such as ramps
+//! or round bricks, may be reported even when their shapes don't touch.
+
+use crate::{catalog::AssetCatalog, spatial::SpatialIndex, Brick, WriteData};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+/// Pairs of overlapping bricks, by their index in `WriteData::bricks`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Overlaps {
+    /// Bricks with the same asset, size, position and orientation.
+    /// The first index is the earliest such brick.
+    pub duplicates: Vec<(usize, usize)>,
+    /// Bricks lying entirely within another brick.
+    /// The first index is the outer brick, the second the inner one.
+    pub contained: Vec<(usize, usize)>,
+    /// Bricks sharing some, but not all, of their volume.
+    pub partial: Vec<(usize, usize)>,
+}
+
+impl Overlaps {
+    /// Whether no overlaps were found.
+    pub fn is_empty(&self) -> bool {
+        self.duplicates.is_empty() && self.contained.is_empty() && self.partial.is_empty()
+    }
+
+    /// The total number of overlapping pairs.
+    pub fn len(&self) -> usize {
+        self.duplicates.len() + self.contained.len() + self.partial.len()
+    }
+}
+
+type Key = (u32, (u32, u32, u32), (i32, i32, i32), u8);
+
+/// What makes two bricks exact duplicates. Orientations are compared as is,
+/// even though many assets look the same in several of them.
+fn key(brick: &Brick) -> Key {
+    (
+        brick.asset_name_index,
+        brick.size,
+        brick.position,
+        brick.orientation().packed(),
+    )
+}
+
+/// Find all overlapping bricks in `data`, using the built-in
+/// [`AssetCatalog`](../catalog/struct.AssetCatalog.html) for fixed size bricks.
+///
+/// ```no_run
+/// # use brs::overlap;
+/// # let mut data: brs::WriteData = unimplemented!();
+/// let overlaps = overlap::find(&data);
+/// for (a, b) in &overlaps.partial {
+///     println!("bricks {} and {} intersect", a, b);
+/// }
+/// let removed = overlap::remove_duplicates(&mut data);
+/// ```
+pub fn find(data: &WriteData) -> Overlaps {
+    find_with(data, &AssetCatalog::default())
+}
+
+/// Find all overlapping bricks in `data`, using `catalog` for fixed size bricks.
+/// Each list is sorted by its first, then second index.
+pub fn find_with(data: &WriteData, catalog: &AssetCatalog) -> Overlaps {
+    let mut overlaps = Overlaps::default();
+
+    // Duplicates are found by key, as bricks of unknown size
+    // have no volume and would never intersect.
+    let mut first = HashMap::new();
+    let mut duplicate_of = vec![None; data.bricks.len()];
+    for (i, brick) in data.bricks.iter().enumerate() {
+        match first.entry(key(brick)) {
+            Entry::Vacant(e) => {
+                e.insert(i);
+            }
+            Entry::Occupied(e) => {
+                duplicate_of[i] = Some(*e.get());
+                overlaps.duplicates.push((*e.get(), i));
+            }
+        }
+    }
+
+    let index = SpatialIndex::with_catalog(data, catalog);
+    for i in 0..index.len() {
+        let a = index.aabb(i);
+        for j in index.query(&a) {
+            if j <= i {
+                continue;
+            }
+
+            // Skip pairs that are duplicates of each other.
+            let root = |n: usize| duplicate_of[n].unwrap_or(n);
+            if root(i) == root(j) {
+                continue;
+            }
+
+            let b = index.aabb(j);
+            if a.contains(&b) {
+                overlaps.contained.push((i, j));
+            } else if b.contains(&a) {
+                overlaps.contained.push((j, i));
+            } else {
+                overlaps.partial.push((i, j));
+            }
+        }
+    }
+
+    overlaps.duplicates.sort_unstable();
+    overlaps.contained.sort_unstable();
+    overlaps
+}
+
+/// Remove bricks that are exact duplicates of an earlier brick, keeping the
+/// first of each. Returns the number of bricks removed.
+pub fn remove_duplicates(data: &mut WriteData) -> usize {
+    let before = data.bricks.len();
+    let mut seen = HashSet::new();
+    data.bricks.retain(|brick| seen.insert(key(brick)));
+    before - data.bricks.len()
+}
@@ -0,0 +1,494 @@
+//! Brick-level patches between saves.
+//!
+//! A [`Patch`](struct.Patch.html) holds the changes found by
+//! [`diff`](../fn.diff.html) with all table indices resolved to the values
+//! they point to, so it can be stored on its own and applied to a copy of
+//! the older save to reproduce the newer one.
+//!
+//! ```no_run
+//! # let (mut base, new): (brs::WriteData, brs::WriteData) = unimplemented!();
+//! use brs::patch::Patch;
+//! let patch = Patch::new(&base, &new)?;
+//! patch.apply(&mut base)?;
+//! assert!(brs::diff(&base, &new).bricks_unchanged());
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use crate::{
+    diff::{diff, lookup, BrickField, HeaderField},
+    transform::table_index,
+    Brick, Color, ColorMode, Direction, Orientation, Rotation, User, WriteData,
+};
+use chrono::prelude::*;
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    io,
+};
+use uuid::Uuid;
+
+/// The color of a brick, resolved from the color lookup table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PatchColor {
+    /// A color from the color lookup table.
+    Set(Color),
+    /// A custom color.
+    Custom(Color),
+}
+
+/// A brick with its asset, material, color and owner resolved.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BrickRecord {
+    pub asset: String,
+    pub size: (u32, u32, u32),
+    pub position: (i32, i32, i32),
+    pub direction: Direction,
+    pub rotation: Rotation,
+    pub collision: bool,
+    pub visibility: bool,
+    pub material: String,
+    pub color: PatchColor,
+    pub owner: Option<User>,
+}
+
+impl BrickRecord {
+    /// Resolve the table indices of `brick` using the lookup tables of `data`.
+    /// Fails if any index is out of range.
+    pub fn new(data: &WriteData, brick: &Brick) -> io::Result<Self> {
+        let invalid = |what: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Brick {} index out of range", what),
+            )
+        };
+
+        Ok(Self {
+            asset: lookup(&data.brick_assets, brick.asset_name_index)
+                .ok_or_else(|| invalid("asset"))?
+                .clone(),
+            size: brick.size,
+            position: brick.position,
+            direction: brick.direction,
+            rotation: brick.rotation,
+            collision: brick.collision,
+            visibility: brick.visibility,
+            material: lookup(&data.materials, brick.material_index)
+                .ok_or_else(|| invalid("material"))?
+                .clone(),
+            color: match brick.color {
+                ColorMode::Set(i) => {
+                    PatchColor::Set(*lookup(&data.colors, i).ok_or_else(|| invalid("color"))?)
+                }
+                ColorMode::Custom(c) => PatchColor::Custom(c),
+            },
+            owner: match brick.owner_index {
+                Some(i) => Some(
+                    lookup(&data.brick_owners, i)
+                        .ok_or_else(|| invalid("owner"))?
+                        .clone(),
+                ),
+                None => None,
+            },
+        })
+    }
+
+    /// Create a brick from this record, adding any values missing from the
+    /// lookup tables of `data`.
+    ///
+    /// This searches the tables, so creating many bricks is slow
+    /// for large tables.
+    pub fn to_brick(&self, data: &mut WriteData) -> Brick {
+        Brick {
+            asset_name_index: table_index(&mut data.brick_assets, &self.asset),
+            size: self.size,
+            position: self.position,
+            direction: self.direction,
+            rotation: self.rotation,
+            collision: self.collision,
+            visibility: self.visibility,
+            material_index: table_index(&mut data.materials, &self.material),
+            color: match self.color {
+                PatchColor::Set(c) => ColorMode::Set(table_index(&mut data.colors, &c)),
+                PatchColor::Custom(c) => ColorMode::Custom(c),
+            },
+            owner_index: self
+                .owner
+                .as_ref()
+                .map(|o| table_index(&mut data.brick_owners, o)),
+        }
+    }
+
+    /// Like `to_brick`, looking up values with `index`,
+    /// which must have been built from the tables of `data`.
+    fn to_brick_indexed(&self, data: &mut WriteData, index: &mut TableIndex) -> Brick {
+        Brick {
+            asset_name_index: index_of(
+                &mut index.assets,
+                &mut data.brick_assets,
+                self.asset.clone(),
+                &self.asset,
+            ),
+            size: self.size,
+            position: self.position,
+            direction: self.direction,
+            rotation: self.rotation,
+            collision: self.collision,
+            visibility: self.visibility,
+            material_index: index_of(
+                &mut index.materials,
+                &mut data.materials,
+                self.material.clone(),
+                &self.material,
+            ),
+            color: match self.color {
+                PatchColor::Set(c) => {
+                    ColorMode::Set(index_of(&mut index.colors, &mut data.colors, c.into(), &c))
+                }
+                PatchColor::Custom(c) => ColorMode::Custom(c),
+            },
+            owner_index: self
+                .owner
+                .as_ref()
+                .map(|o| index_of(&mut index.owners, &mut data.brick_owners, owner_key(o), o)),
+        }
+    }
+}
+
+/// The index of each value in the lookup tables of a save,
+/// so bricks can be created without searching the tables.
+/// Values appearing more than once map to their first index.
+struct TableIndex {
+    assets: HashMap<String, u32>,
+    materials: HashMap<String, u32>,
+    colors: HashMap<u32, u32>,
+    owners: HashMap<(String, Uuid), u32>,
+}
+
+fn owner_key(owner: &User) -> (String, Uuid) {
+    (owner.name.clone(), owner.id)
+}
+
+impl TableIndex {
+    fn new(data: &WriteData) -> Self {
+        fn build<K: Eq + Hash, T>(table: &[T], key: impl Fn(&T) -> K) -> HashMap<K, u32> {
+            let mut index = HashMap::with_capacity(table.len());
+            for (i, value) in table.iter().enumerate() {
+                index.entry(key(value)).or_insert(i as u32);
+            }
+            index
+        }
+
+        Self {
+            assets: build(&data.brick_assets, String::clone),
+            materials: build(&data.materials, String::clone),
+            colors: build(&data.colors, |&c| u32::from(c)),
+            owners: build(&data.brick_owners, owner_key),
+        }
+    }
+}
+
+/// The index of `value` in `table`, looked up by `key` in `index`,
+/// adding it to both if missing.
+fn index_of<K: Eq + Hash, T: Clone>(
+    index: &mut HashMap<K, u32>,
+    table: &mut Vec<T>,
+    key: K,
+    value: &T,
+) -> u32 {
+    *index.entry(key).or_insert_with(|| {
+        table.push(value.clone());
+        (table.len() - 1) as u32
+    })
+}
+
+/// New values for the fields of a brick. Fields set to `None` are unchanged.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct BrickEdit {
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub size: Option<(u32, u32, u32)>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub collision: Option<bool>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub visibility: Option<bool>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub material: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub color: Option<PatchColor>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub owner: Option<Option<User>>,
+}
+
+impl BrickEdit {
+    /// Apply the edit to `record`.
+    pub fn apply(&self, record: &mut BrickRecord) {
+        if let Some(size) = self.size {
+            record.size = size;
+        }
+        if let Some(collision) = self.collision {
+            record.collision = collision;
+        }
+        if let Some(visibility) = self.visibility {
+            record.visibility = visibility;
+        }
+        if let Some(material) = &self.material {
+            record.material = material.clone();
+        }
+        if let Some(color) = self.color {
+            record.color = color;
+        }
+        if let Some(owner) = &self.owner {
+            record.owner = owner.clone();
+        }
+    }
+}
+
+/// A single change to the bricks of a save.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BrickPatch {
+    /// Add a new brick.
+    Add(BrickRecord),
+    /// Remove the brick matching the record.
+    Remove(BrickRecord),
+    /// Change some fields of the brick matching the record.
+    Modify(BrickRecord, BrickEdit),
+}
+
+/// New values for the header fields of a save.
+/// Fields set to `None` are unchanged.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct HeaderPatch {
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub map: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub author: Option<User>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub description: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub save_time: Option<DateTime<Utc>>,
+}
+
+/// The complete lookup tables of the newer save.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PatchTables {
+    pub mods: Vec<String>,
+    pub brick_assets: Vec<String>,
+    pub colors: Vec<Color>,
+    pub materials: Vec<String>,
+    pub brick_owners: Vec<User>,
+}
+
+/// The changes turning one save into another.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Patch {
+    pub header: HeaderPatch,
+    /// The lookup tables of the newer save, if they have changed.
+    /// Bricks are remapped to them after all other changes.
+    pub tables: Option<PatchTables>,
+    /// Removals and modifications come first, in the order of the
+    /// older save, followed by additions.
+    pub bricks: Vec<BrickPatch>,
+}
+
+impl Patch {
+    /// Create a patch turning `old` into `new`.
+    /// Fails if any brick has a table index out of range.
+    pub fn new(old: &WriteData, new: &WriteData) -> io::Result<Self> {
+        let changes = diff(old, new);
+        let mut patch = Patch::default();
+
+        for field in &changes.header {
+            match field {
+                HeaderField::Map => patch.header.map = Some(new.map.clone()),
+                HeaderField::Author => patch.header.author = Some(new.author.clone()),
+                HeaderField::Description => {
+                    patch.header.description = Some(new.description.clone())
+                }
+                HeaderField::SaveTime => patch.header.save_time = Some(new.save_time),
+            }
+        }
+
+        if !changes.tables_unchanged() {
+            patch.tables = Some(PatchTables {
+                mods: new.mods.clone(),
+                brick_assets: new.brick_assets.clone(),
+                colors: new.colors.clone(),
+                materials: new.materials.clone(),
+                brick_owners: new.brick_owners.clone(),
+            });
+        }
+
+        let mut touched = changes
+            .removed
+            .iter()
+            .map(|&i| (i, None))
+            .chain(changes.modified.iter().map(|c| (c.old, Some(c))))
+            .collect::<Vec<_>>();
+        touched.sort_unstable_by_key(|&(i, _)| i);
+
+        for (i, change) in touched {
+            let record = BrickRecord::new(old, &old.bricks[i])?;
+            patch.bricks.push(match change {
+                None => BrickPatch::Remove(record),
+                Some(change) => {
+                    let target = BrickRecord::new(new, &new.bricks[change.new])?;
+                    let mut edit = BrickEdit::default();
+                    for field in &change.fields {
+                        match field {
+                            BrickField::Size => edit.size = Some(target.size),
+                            BrickField::Collision => edit.collision = Some(target.collision),
+                            BrickField::Visibility => edit.visibility = Some(target.visibility),
+                            BrickField::Material => edit.material = Some(target.material.clone()),
+                            BrickField::Color => edit.color = Some(target.color),
+                            BrickField::Owner => edit.owner = Some(target.owner.clone()),
+                        }
+                    }
+                    BrickPatch::Modify(record, edit)
+                }
+            });
+        }
+
+        for &i in &changes.added {
+            patch
+                .bricks
+                .push(BrickPatch::Add(BrickRecord::new(new, &new.bricks[i])?));
+        }
+
+        Ok(patch)
+    }
+
+    /// Whether the patch changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.header == HeaderPatch::default() && self.tables.is_none() && self.bricks.is_empty()
+    }
+
+    /// Apply the patch to `data`.
+    ///
+    /// Bricks to remove or modify are found by their asset, position and
+    /// orientation, preferring a brick that also matches in all other fields.
+    /// Fails without changing `data` if such a brick can't be found.
+    pub fn apply(&self, data: &mut WriteData) -> io::Result<()> {
+        let mut result = data.clone();
+
+        if let Some(map) = &self.header.map {
+            result.map = map.clone();
+        }
+        if let Some(author) = &self.header.author {
+            result.author = author.clone();
+        }
+        if let Some(description) = &self.header.description {
+            result.description = description.clone();
+        }
+        if let Some(save_time) = self.header.save_time {
+            result.save_time = save_time;
+        }
+
+        // Bricks by asset, position and orientation, in order, so that each
+        // can be matched once. Assets are identified by their first index,
+        // in case the table has duplicates.
+        let mut assets = HashMap::new();
+        for (i, asset) in data.brick_assets.iter().enumerate() {
+            assets.entry(asset.as_str()).or_insert(i as u32);
+        }
+        let mut by_key: HashMap<_, VecDeque<usize>> = HashMap::new();
+        for (i, brick) in data.bricks.iter().enumerate() {
+            if let Some(asset) = lookup(&data.brick_assets, brick.asset_name_index) {
+                by_key
+                    .entry((
+                        assets[asset.as_str()],
+                        brick.position,
+                        brick.orientation().packed(),
+                    ))
+                    .or_default()
+                    .push_back(i);
+            }
+        }
+
+        let mut removed = vec![false; data.bricks.len()];
+        let mut find = |record: &BrickRecord| -> io::Result<usize> {
+            let orientation = Orientation::new(record.direction, record.rotation);
+            let candidates = assets
+                .get(record.asset.as_str())
+                .and_then(|&asset| by_key.get_mut(&(asset, record.position, orientation.packed())))
+                .filter(|c| !c.is_empty())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Patch brick {} at {:?} not found",
+                            record.asset, record.position
+                        ),
+                    )
+                })?;
+            let exact = candidates.iter().position(|&i| {
+                BrickRecord::new(data, &data.bricks[i]).ok().as_ref() == Some(record)
+            });
+            // Without an exact match, the first candidate is taken in O(1).
+            Ok(candidates.remove(exact.unwrap_or(0)).unwrap())
+        };
+
+        let mut index = TableIndex::new(&result);
+
+        for change in &self.bricks {
+            match change {
+                BrickPatch::Remove(record) => removed[find(record)?] = true,
+                BrickPatch::Modify(record, edit) => {
+                    let i = find(record)?;
+                    let mut record = BrickRecord::new(data, &data.bricks[i])?;
+                    edit.apply(&mut record);
+                    result.bricks[i] = record.to_brick_indexed(&mut result, &mut index);
+                }
+                BrickPatch::Add(record) => {
+                    let brick = record.to_brick_indexed(&mut result, &mut index);
+                    result.bricks.push(brick);
+                }
+            }
+        }
+
+        let mut i = 0;
+        result.bricks.retain(|_| {
+            let keep = !removed.get(i).copied().unwrap_or(false);
+            i += 1;
+            keep
+        });
+
+        if let Some(tables) = &self.tables {
+            remap_tables(&mut result, tables)?;
+        }
+
+        *data = result;
+        Ok(())
+    }
+}
+
+/// Replace the lookup tables of `data` with `tables`,
+/// adding any values used by bricks but missing from them.
+fn remap_tables(data: &mut WriteData, tables: &PatchTables) -> io::Result<()> {
+    let records = data
+        .bricks
+        .iter()
+        .map(|brick| BrickRecord::new(data, brick))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    data.mods = tables.mods.clone();
+    data.brick_assets = tables.brick_assets.clone();
+    data.colors = tables.colors.clone();
+    data.materials = tables.materials.clone();
+    data.brick_owners = tables.brick_owners.clone();
+
+    let mut index = TableIndex::new(data);
+    let bricks = records
+        .iter()
+        .map(|record| record.to_brick_indexed(data, &mut index))
+        .collect();
+    data.bricks = bricks;
+    Ok(())
+}
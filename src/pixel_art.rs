@@ -0,0 +1,225 @@
+//! Building pixel art from images.
+//!
+//! Images are given as raw RGBA pixel data, row by row from the top,
+//! so any image decoder can be used to load them.
+
+use crate::{
+    save::{Brick, Color, ColorMode, Direction, Rotation, User},
+    Aabb, Orientation, WriteData,
+};
+use chrono::prelude::*;
+use std::{convert::TryFrom, io};
+use uuid::Uuid;
+
+/// The plane pixel art is built in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Plane {
+    /// Lying flat on the ground, facing up. Columns run along X
+    /// and rows along Y.
+    XY,
+    /// Standing upright, facing towards negative Y. Columns run along X
+    /// and the top row is highest.
+    XZ,
+    /// Standing upright, facing towards positive X. Columns run along Y
+    /// and the top row is highest.
+    YZ,
+}
+
+impl Plane {
+    /// The direction bricks face, so that their top is the visible side.
+    fn direction(self) -> Direction {
+        match self {
+            Plane::XY => Direction::ZPositive,
+            Plane::XZ => Direction::YNegative,
+            Plane::YZ => Direction::XPositive,
+        }
+    }
+}
+
+/// How pixels are colored.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PixelColors {
+    /// Give every brick the exact color of its pixel.
+    Custom,
+    /// Replace pixel colors with the closest color in a palette,
+    /// which becomes the color lookup table of the save.
+    Palette(Vec<Color>),
+}
+
+/// Options for [`build`](fn.build.html).
+#[derive(Debug, Clone)]
+pub struct PixelArtOptions {
+    /// The plane to build in.
+    pub plane: Plane,
+    /// Half the width of each pixel in save units, so the default of 5
+    /// makes pixels the width of a 1x1 brick.
+    pub pixel_size: u32,
+    /// Half the thickness of the bricks in save units,
+    /// which is 2 for plates and tiles.
+    pub thickness: u32,
+    /// The procedural asset to build with.
+    pub asset: String,
+    pub material: String,
+    pub colors: PixelColors,
+    /// Pixels with an alpha below this are left out.
+    pub alpha_threshold: u8,
+    /// Merge runs of pixels of the same color in each row into one brick.
+    pub merge_rows: bool,
+}
+
+impl Default for PixelArtOptions {
+    fn default() -> Self {
+        Self {
+            plane: Plane::XZ,
+            pixel_size: 5,
+            thickness: 2,
+            asset: "PB_DefaultTile".to_string(),
+            material: "BMC_Plastic".to_string(),
+            colors: PixelColors::Custom,
+            alpha_threshold: 128,
+            merge_rows: true,
+        }
+    }
+}
+
+/// Build pixel art from an image of `width` by `height` pixels, with
+/// `pixels` holding 4 bytes of RGBA for each pixel, row by row from the top.
+///
+/// The art starts at the origin and extends along the positive axes of the
+/// plane. Bricks are written row by row from the top. Pixels are made opaque,
+/// as translucent bricks render poorly.
+///
+/// ```
+/// use brs::pixel_art::{self, PixelArtOptions, PixelColors};
+/// use brs::Color;
+/// let red = [255, 0, 0, 255];
+/// let clear = [0, 0, 0, 0];
+/// let pixels = [red, red, clear, red].concat();
+/// let options = PixelArtOptions {
+///     colors: PixelColors::Palette(vec![
+///         Color::from_rgba(255, 255, 255, 255),
+///         Color::from_rgba(200, 0, 0, 255),
+///     ]),
+///     ..Default::default()
+/// };
+/// let data = pixel_art::build(2, 2, &pixels, &options)?;
+/// assert_eq!(data.bricks.len(), 2);
+/// assert_eq!(data.bricks[0].color, brs::ColorMode::Set(1));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn build(
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+    options: &PixelArtOptions,
+) -> io::Result<WriteData> {
+    if pixels.len() as u64 != u64::from(width) * u64::from(height) * 4 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Pixel data doesn't match image size",
+        ));
+    }
+    if options.pixel_size == 0 || options.thickness == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Pixel size and thickness must not be zero",
+        ));
+    }
+    // Every coordinate lies between zero and these, so checking them
+    // once keeps all the positions below in range.
+    let extent = |n: u32, size: u32| i32::try_from(u64::from(n) * 2 * u64::from(size));
+    let too_large = |_| io::Error::new(io::ErrorKind::InvalidInput, "Pixel art is too large");
+    extent(width, options.pixel_size).map_err(too_large)?;
+    let top = extent(height, options.pixel_size).map_err(too_large)?;
+    extent(1, options.thickness).map_err(too_large)?;
+    let palette = match &options.colors {
+        PixelColors::Palette(palette) if palette.is_empty() => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Pixel art palette is empty",
+            ))
+        }
+        PixelColors::Palette(palette) => palette.clone(),
+        PixelColors::Custom => Vec::new(),
+    };
+
+    let color_at = |x: u32, y: u32| {
+        let i = (y as usize * width as usize + x as usize) * 4;
+        let p = &pixels[i..i + 4];
+        if p[3] < options.alpha_threshold {
+            return None;
+        }
+        let color = Color::from_rgba(p[0], p[1], p[2], 255);
+        Some(match options.colors {
+            PixelColors::Custom => ColorMode::Custom(color),
+            PixelColors::Palette(_) => ColorMode::Set(color.nearest(&palette).unwrap() as u32),
+        })
+    };
+
+    let orientation = Orientation::new(options.plane.direction(), Rotation::Deg0);
+    let inverse = orientation.inverse();
+    let s = i64::from(options.pixel_size);
+    let t = options.thickness as i32;
+    let coord = |n: u32| (i64::from(n) * 2 * s) as i32;
+    let mut bricks = Vec::new();
+    let mut push = |x: u32, y: u32, length: u32, color: ColorMode| {
+        // The corners of the run in the image, in save units.
+        let (u0, u1) = (coord(x), coord(x + length));
+        let (v0, v1) = (coord(y), coord(y + 1));
+        let aabb = match options.plane {
+            Plane::XY => Aabb::new((u0, v0, 0), (u1, v1, 2 * t)),
+            Plane::XZ => Aabb::new((u0, -2 * t, top - v1), (u1, 0, top - v0)),
+            Plane::YZ => Aabb::new((0, u0, top - v1), (2 * t, u1, top - v0)),
+        };
+        let (w, d, h) = aabb.size();
+        let (lx, ly, lz) = inverse.apply(((w / 2) as i32, (d / 2) as i32, (h / 2) as i32));
+        bricks.push(Brick {
+            asset_name_index: 0,
+            size: (lx.unsigned_abs(), ly.unsigned_abs(), lz.unsigned_abs()),
+            position: aabb.center(),
+            direction: orientation.direction,
+            rotation: orientation.rotation,
+            collision: true,
+            visibility: true,
+            material_index: 0,
+            color,
+            owner_index: None,
+        });
+    };
+
+    for y in 0..height {
+        let mut x = 0;
+        while x < width {
+            let color = match color_at(x, y) {
+                Some(color) => color,
+                None => {
+                    x += 1;
+                    continue;
+                }
+            };
+            let mut length = 1;
+            while options.merge_rows && x + length < width && color_at(x + length, y) == Some(color)
+            {
+                length += 1;
+            }
+            push(x, y, length, color);
+            x += length;
+        }
+    }
+
+    Ok(WriteData {
+        map: "Plate".to_string(),
+        author: User {
+            id: Uuid::nil(),
+            name: "Pixel Art".to_string(),
+        },
+        description: String::new(),
+        save_time: Utc::now(),
+        mods: Vec::new(),
+        brick_assets: vec![options.asset.clone()],
+        colors: palette,
+        materials: vec![options.material.clone()],
+        brick_owners: Vec::new(),
+        bricks,
+    })
+}
@@ -1,25 +1,87 @@
 use crate::{
     bit_reader::BitReader,
-    save::{Brick, Color, ColorMode, Direction, Rotation, User},
-    ue4_date_time_base, Version, MAGIC,
+    save::{Brick, Color, ColorMode, User},
+    ue4_date_time_base, BrickColumns, Orientation, Version, MAGIC,
 };
 use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
 use chrono::{prelude::*, Duration};
 use libflate::zlib;
 use std::{
-    convert::TryInto,
+    convert::{TryFrom, TryInto},
     io::{self, prelude::*, Cursor},
 };
 use uuid::Uuid;
 
+/// Limits on what a save may contain, checked while reading it so that
+/// corrupt or malicious files can't exhaust memory. Reading fails with an
+/// `InvalidData` error when a save exceeds them.
+///
+/// The defaults are far above what the game creates. Use
+/// [`Reader::with_limits`](struct.Reader.html#method.with_limits)
+/// for stricter ones.
+///
+/// ```no_run
+/// # use std::fs::File;
+/// use brs::read::Limits;
+/// let limits = Limits {
+///     max_brick_count: 100_000,
+///     ..Limits::default()
+/// };
+/// let reader = brs::Reader::with_limits(File::open("upload.brs")?, limits)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The longest string, in bytes as stored in the file. Defaults to 1 MiB.
+    pub max_string_len: usize,
+    /// The most entries in each lookup table of the second header.
+    /// Defaults to 1,048,576.
+    pub max_table_len: usize,
+    /// The most bricks. Defaults to 100,000,000.
+    pub max_brick_count: usize,
+    /// The largest section of the file once decompressed, in bytes.
+    /// Defaults to 1 GiB.
+    pub max_section_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_string_len: 1 << 20,
+            max_table_len: 1 << 20,
+            max_brick_count: 100_000_000,
+            max_section_size: 1 << 30,
+        }
+    }
+}
+
+impl Limits {
+    /// No limits other than those of the file format itself.
+    pub fn unlimited() -> Self {
+        Self {
+            max_string_len: usize::MAX,
+            max_table_len: usize::MAX,
+            max_brick_count: usize::MAX,
+            max_section_size: usize::MAX,
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
 pub struct Reader<R: Read> {
     r: R,
     version: Version,
+    #[allow(dead_code)]
     game_version: u32,
+    limits: Limits,
 }
 
 impl<R: Read> Reader<R> {
-    /// Create a new reader that reads from `r`.
+    /// Create a new reader that reads from `r`, with the default
+    /// [`Limits`](struct.Limits.html).
     ///
     /// ```no_run
     /// # use std::fs::File;
@@ -27,7 +89,13 @@ impl<R: Read> Reader<R> {
     /// let reader = Reader::new(File::open("village.brs")?)?;
     /// # Ok::<(), std::io::Error>(())
     /// ```
-    pub fn new(mut r: R) -> io::Result<Self> {
+    pub fn new(r: R) -> io::Result<Self> {
+        Self::with_limits(r, Limits::default())
+    }
+
+    /// Create a new reader that reads from `r`, failing on saves
+    /// that exceed `limits`.
+    pub fn with_limits(mut r: R, limits: Limits) -> io::Result<Self> {
         let mut magic = [0; 3];
         r.read_exact(&mut magic)?;
         if magic != MAGIC {
@@ -50,9 +118,15 @@ impl<R: Read> Reader<R> {
             r,
             version,
             game_version,
+            limits,
         })
     }
 
+    /// The version of the save file being read.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
     /// Continue parsing to read the first header.
     /// See [`HasHeader1`](trait.HasHeader1.html) for what it makes available.
     ///
@@ -62,7 +136,11 @@ impl<R: Read> Reader<R> {
     /// # Ok::<(), std::io::Error>(())
     /// ```
     pub fn read_header1(mut self) -> io::Result<ReaderAfterHeader1<R>> {
-        let header1 = read_header1(&mut read_compressed(&mut self.r)?, self.version)?;
+        let header1 = read_header1(
+            &mut read_compressed(&mut self.r, &self.limits)?,
+            self.version,
+            &self.limits,
+        )?;
 
         Ok(ReaderAfterHeader1 {
             inner: self,
@@ -86,7 +164,12 @@ impl<R: Read> ReaderAfterHeader1<R> {
     /// # Ok::<(), std::io::Error>(())
     /// ```
     pub fn read_header2(mut self) -> io::Result<ReaderAfterHeader2<R>> {
-        let header2 = read_header2(&mut read_compressed(&mut self.inner.r)?, self.inner.version)?;
+        let rdr = &mut self.inner;
+        let header2 = read_header2(
+            &mut read_compressed(&mut rdr.r, &rdr.limits)?,
+            rdr.version,
+            &rdr.limits,
+        )?;
 
         Ok(ReaderAfterHeader2 {
             inner: self,
@@ -113,7 +196,7 @@ impl<R: Read> ReaderAfterHeader2<R> {
     /// ```
     pub fn iter_bricks(mut self) -> io::Result<ReadBricks> {
         let rdr = &mut self.inner.inner;
-        let mut brick_section = read_compressed(&mut rdr.r)?;
+        let mut brick_section = read_compressed(&mut rdr.r, &rdr.limits)?;
         let bricks_iter = read_bricks(
             &mut brick_section,
             rdr.version,
@@ -139,7 +222,7 @@ impl<R: Read> ReaderAfterHeader2<R> {
     /// ```
     pub fn iter_bricks_and_reader(mut self) -> io::Result<(ReaderAfterBricks, ReadBricks)> {
         let rdr = &mut self.inner.inner;
-        let mut brick_section = read_compressed(&mut rdr.r)?;
+        let mut brick_section = read_compressed(&mut rdr.r, &rdr.limits)?;
         let bricks_iter = read_bricks(
             &mut brick_section,
             rdr.version,
@@ -191,6 +274,7 @@ pub struct ReaderAfterBricks {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header1 {
     pub map: String,
     pub author: User,
@@ -201,6 +285,7 @@ pub struct Header1 {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header2 {
     pub mods: Vec<String>,
     pub brick_assets: Vec<String>,
@@ -289,10 +374,10 @@ impl HasHeader2 for ReaderAfterBricks {
     }
 }
 
-fn read_header1(r: &mut impl Read, version: Version) -> io::Result<Header1> {
-    let map = string(r)?;
-    let author_name = string(r)?;
-    let description = string(r)?;
+fn read_header1(r: &mut impl Read, version: Version, limits: &Limits) -> io::Result<Header1> {
+    let map = string(r, limits)?;
+    let author_name = string(r, limits)?;
+    let description = string(r, limits)?;
     let author_id = uuid(r)?;
 
     let host = None;
@@ -304,6 +389,11 @@ fn read_header1(r: &mut impl Read, version: Version) -> io::Result<Header1> {
     };
 
     let brick_count = r.read_i32::<LittleEndian>()?;
+    match usize::try_from(brick_count) {
+        Err(_) => return Err(invalid_data("Invalid brick count")),
+        Ok(count) if count > limits.max_brick_count => return Err(invalid_data("Too many bricks")),
+        Ok(_) => {}
+    }
 
     Ok(Header1 {
         map,
@@ -318,13 +408,13 @@ fn read_header1(r: &mut impl Read, version: Version) -> io::Result<Header1> {
     })
 }
 
-fn read_header2(r: &mut impl Read, version: Version) -> io::Result<Header2> {
-    let mods = array(r, string)?;
-    let brick_assets = array(r, string)?;
-    let colors = array(r, |r| r.read_u32::<LittleEndian>().map(Into::into))?;
+fn read_header2(r: &mut impl Read, version: Version, limits: &Limits) -> io::Result<Header2> {
+    let mods = array(r, limits, |r| string(r, limits))?;
+    let brick_assets = array(r, limits, |r| string(r, limits))?;
+    let colors = array(r, limits, |r| r.read_u32::<LittleEndian>().map(Into::into))?;
 
     let materials = if version >= Version::MaterialsStoredAsNames {
-        array(r, string)?
+        array(r, limits, |r| string(r, limits))?
     } else {
         vec!["BMC_Hologram", "BMC_Plastic", "BMC_Glow", "BMC_Metallic"]
             .into_iter()
@@ -333,7 +423,7 @@ fn read_header2(r: &mut impl Read, version: Version) -> io::Result<Header2> {
     };
 
     let brick_owners = if version >= Version::AddedOwnerData {
-        array(r, read_user)?
+        array(r, limits, |r| read_user(r, limits))?
     } else {
         Vec::new()
     };
@@ -375,6 +465,28 @@ fn read_bricks(
 }
 
 impl ReadBricks {
+    /// Read all remaining bricks into columns.
+    ///
+    /// Bricks are decoded one at a time and pushed onto the columns,
+    /// so no `Vec<Brick>` is built along the way.
+    ///
+    /// ```no_run
+    /// # let reader: brs::read::ReaderAfterHeader2<std::fs::File> = unimplemented!();
+    /// let columns = reader.iter_bricks()?.read_columns()?;
+    /// println!("{} bricks", columns.len());
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn read_columns(self) -> io::Result<BrickColumns> {
+        // Every brick starts on a new byte, which bounds the count
+        // regardless of what the header claims.
+        let remaining = (self.brick_count - self.index).max(0) as usize;
+        let mut columns = BrickColumns::with_capacity(remaining.min(self.r.remaining_bytes()));
+        for brick in self {
+            columns.push(&brick?);
+        }
+        Ok(columns)
+    }
+
     fn read_brick(&mut self) -> io::Result<Brick> {
         self.r.eat_byte_align();
         let asset_name_index = self.r.read_int(self.brick_asset_num.max(2));
@@ -408,7 +520,17 @@ impl ReadBricks {
             n => Some(n - 1),
         };
 
-        let (direction, rotation) = split_orientation(orientation);
+        if self.r.is_overflowed() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Brick data ended early",
+            ));
+        }
+
+        let Orientation {
+            direction,
+            rotation,
+        } = Orientation::from_packed(orientation);
 
         Ok(Brick {
             asset_name_index,
@@ -445,70 +567,88 @@ impl Iterator for ReadBricks {
     }
 }
 
-fn read_compressed(r: &mut impl Read) -> io::Result<impl Read> {
+fn read_compressed(r: &mut impl Read, limits: &Limits) -> io::Result<impl Read> {
     let uncompressed_size = r.read_i32::<LittleEndian>()?;
     let compressed_size = r.read_i32::<LittleEndian>()?;
-    if uncompressed_size < 0 || compressed_size < 0 || compressed_size >= uncompressed_size {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Invalid compressed section size",
-        ));
+    // Empty sections are stored with both sizes zero,
+    // and compressed sections are always smaller than their contents.
+    if uncompressed_size < 0
+        || compressed_size < 0
+        || (compressed_size != 0 && compressed_size >= uncompressed_size)
+    {
+        return Err(invalid_data("Invalid compressed section size"));
+    }
+    if uncompressed_size as usize > limits.max_section_size {
+        return Err(invalid_data("Section too large"));
     }
 
     // TODO: Don't read the entire thing into memory, somehow stream decode it
-    if compressed_size == 0 {
-        let mut uncompressed = vec![0; uncompressed_size as usize];
-        r.read_exact(&mut uncompressed)?;
-        Ok(Cursor::new(uncompressed))
+    let uncompressed = if compressed_size == 0 {
+        read_bytes(r, uncompressed_size as usize)?
     } else {
-        let mut compressed = vec![0; compressed_size as usize];
-        r.read_exact(&mut compressed)?;
+        let compressed = read_bytes(r, compressed_size as usize)?;
         let mut decoder = zlib::Decoder::new(&compressed[..])?;
-        let mut uncompressed = vec![0; uncompressed_size as usize];
-        decoder.read_exact(&mut uncompressed)?;
-        Ok(Cursor::new(uncompressed))
+        read_bytes(&mut decoder, uncompressed_size as usize)?
+    };
+    Ok(Cursor::new(uncompressed))
+}
+
+/// Read exactly `len` bytes. Unlike `read_exact` into a buffer of that size,
+/// this only allocates as much as is actually read, so a short file claiming
+/// a huge length can't allocate all of it.
+fn read_bytes(r: &mut impl Read, len: usize) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    r.take(len as u64).read_to_end(&mut data)?;
+    if data.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
     }
+    Ok(data)
 }
 
-fn array<T, E, R: Read>(r: &mut R, mut f: impl FnMut(&mut R) -> Result<T, E>) -> Result<Vec<T>, E>
-where
-    E: From<io::Error>,
-{
+fn array<T, R: Read>(
+    r: &mut R,
+    limits: &Limits,
+    mut f: impl FnMut(&mut R) -> io::Result<T>,
+) -> io::Result<Vec<T>> {
     let count = r.read_i32::<LittleEndian>()?;
-    let mut vec = Vec::with_capacity(count as usize);
+    let count = usize::try_from(count).map_err(|_| invalid_data("Invalid array length"))?;
+    if count > limits.max_table_len {
+        return Err(invalid_data("Array too long"));
+    }
+    // Elements take at least one byte each, so a count larger than the
+    // data is caught by running out of it rather than up front.
+    let mut vec = Vec::with_capacity(count.min(1024));
     for _ in 0..count {
         vec.push(f(r)?);
     }
     Ok(vec)
 }
 
-fn string(r: &mut impl Read) -> io::Result<String> {
-    let (size, is_ucs2) = match r.read_i32::<LittleEndian>()? {
-        s if s >= 0 => (s, false),
-        s => (-s, true),
-    };
+fn string(r: &mut impl Read, limits: &Limits) -> io::Result<String> {
+    let size = r.read_i32::<LittleEndian>()?;
+    let is_ucs2 = size < 0;
+    let size = size.unsigned_abs() as usize;
+    if size > limits.max_string_len {
+        return Err(invalid_data("String too long"));
+    }
 
     let mut s = if is_ucs2 {
         // TODO: Verify that UTF-16 is backwards compatible with UCS-2.
-        if size % 2 != 0 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Invalid UCS-2 data size",
-            ));
+        if !size.is_multiple_of(2) {
+            return Err(invalid_data("Invalid UCS-2 data size"));
         }
-        let mut data = vec![0; size as usize / 2];
-        r.read_u16_into::<LittleEndian>(&mut data)?;
-        String::from_utf16(data.as_slice())
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid UCS-2 data"))?
+        let data = read_bytes(r, size)?
+            .chunks_exact(2)
+            .map(LittleEndian::read_u16)
+            .collect::<Vec<_>>();
+        String::from_utf16(data.as_slice()).map_err(|_| invalid_data("Invalid UCS-2 data"))?
     } else {
         // TODO: Figure out the correct encoding.
         // 7-bit values should just be ASCII, so that part is fine,
         // but I don't know what 80h-FFh should be.
         // Hope that UTF-8 will error for now.
-        let mut data = vec![0; size as usize];
-        r.read_exact(&mut data)?;
-        String::from_utf8(data)
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid string data"))?
+        let data = read_bytes(r, size)?;
+        String::from_utf8(data).map_err(|_| invalid_data("Invalid string data"))?
     };
 
     s.pop();
@@ -531,16 +671,9 @@ fn date_time(r: &mut impl Read) -> io::Result<DateTime<Utc>> {
         + Duration::nanoseconds((ticks % 10) * 100))
 }
 
-fn read_user(r: &mut impl Read) -> io::Result<User> {
+fn read_user(r: &mut impl Read, limits: &Limits) -> io::Result<User> {
     Ok(User {
         id: uuid(r)?,
-        name: string(r)?,
+        name: string(r, limits)?,
     })
 }
-
-/// Splits a packed orientation into its corresponding direction and rotation.
-fn split_orientation(orientation: u8) -> (Direction, Rotation) {
-    let direction = ((orientation >> 2) % 6).try_into().unwrap();
-    let rotation = (orientation & 0b11).try_into().unwrap();
-    (direction, rotation)
-}
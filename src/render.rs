@@ -0,0 +1,376 @@
+//! Rendering saves to images on the CPU, for map views and thumbnails.
+//!
+//! Bricks are drawn as their bounding boxes with flat shading,
+//! using the painter's algorithm: far bricks are drawn first and
+//! nearer ones over them.
+//!
+//! Images are written as standalone PNG files. They can't be embedded in
+//! saves as previews, as none of the save versions this crate reads and
+//! writes have a preview image.
+
+use crate::{catalog::AssetCatalog, diff::resolve_color, Aabb, Color, WriteData};
+use byteorder::{BigEndian, WriteBytesExt};
+use libflate::zlib;
+use std::{
+    convert::TryFrom,
+    io::{self, Write},
+};
+
+/// The direction a save is viewed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum View {
+    /// Straight down, with X to the right and Y down the image.
+    /// Lower bricks are drawn darker.
+    TopDown,
+    /// From above at an angle, looking towards negative X and Y,
+    /// with the top and the sides facing positive X and Y visible.
+    Isometric,
+}
+
+/// What part of the save is shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    /// The point shown in the center of the image.
+    pub center: (f64, f64, f64),
+    /// Save units per pixel.
+    pub scale: f64,
+}
+
+/// Options for [`render`](fn.render.html).
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub width: u32,
+    pub height: u32,
+    pub view: View,
+    /// The camera to use, or `None` to fit the whole save in the image.
+    pub camera: Option<Camera>,
+    pub background: Color,
+    /// Used for the sizes of fixed size bricks.
+    pub catalog: AssetCatalog,
+    /// Whether to draw bricks with visibility turned off.
+    pub include_invisible: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            width: 512,
+            height: 512,
+            view: View::Isometric,
+            camera: None,
+            background: Color::from_rgba(0, 0, 0, 0),
+            catalog: AssetCatalog::default(),
+            include_invisible: false,
+        }
+    }
+}
+
+/// An image of RGBA pixels, row by row from the top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    fn new(width: u32, height: u32, background: Color) -> Self {
+        let pixel = [
+            background.r(),
+            background.g(),
+            background.b(),
+            background.a(),
+        ];
+        Self {
+            width,
+            height,
+            pixels: pixel.repeat(width as usize * height as usize),
+        }
+    }
+
+    /// The color of the pixel at `x`, `y`.
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let p = &self.pixels[i..i + 4];
+        Color::from_rgba(p[0], p[1], p[2], p[3])
+    }
+
+    /// Blend `color` over the pixel at `x`, `y`.
+    fn blend(&mut self, x: u32, y: u32, color: [f32; 4]) {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let p = &mut self.pixels[i..i + 4];
+        let a = color[3];
+        let under = f32::from(p[3]) / 255.0;
+        let out = a + under * (1.0 - a);
+        for c in 0..3 {
+            let value = if out > 0.0 {
+                (color[c] * a + f32::from(p[c]) / 255.0 * under * (1.0 - a)) / out
+            } else {
+                0.0
+            };
+            p[c] = (value * 255.0).round() as u8;
+        }
+        p[3] = (out * 255.0).round() as u8;
+    }
+
+    /// Fill the convex polygon with corners `points` in pixel coordinates,
+    /// covering pixels whose centers are inside.
+    fn fill(&mut self, points: &[(f64, f64)], color: [f32; 4]) {
+        let min_x = points.iter().map(|p| p.0).fold(f64::MAX, f64::min);
+        let max_x = points.iter().map(|p| p.0).fold(f64::MIN, f64::max);
+        let min_y = points.iter().map(|p| p.1).fold(f64::MAX, f64::min);
+        let max_y = points.iter().map(|p| p.1).fold(f64::MIN, f64::max);
+        let clamp = |v: f64, max: u32| v.clamp(0.0, f64::from(max)) as u32;
+        let (x0, x1) = (
+            clamp(min_x.floor(), self.width),
+            clamp(max_x.ceil(), self.width),
+        );
+        let (y0, y1) = (
+            clamp(min_y.floor(), self.height),
+            clamp(max_y.ceil(), self.height),
+        );
+
+        // Points are inside when on the same side of every edge.
+        let side = |x: f64, y: f64, a: (f64, f64), b: (f64, f64)| {
+            (b.0 - a.0) * (y - a.1) - (b.1 - a.1) * (x - a.0)
+        };
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let (px, py) = (f64::from(x) + 0.5, f64::from(y) + 0.5);
+                let mut positive = false;
+                let mut negative = false;
+                for (i, &a) in points.iter().enumerate() {
+                    let s = side(px, py, a, points[(i + 1) % points.len()]);
+                    positive |= s > 0.0;
+                    negative |= s < 0.0;
+                }
+                if !(positive && negative) {
+                    self.blend(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// Write the image as a PNG file to `w`.
+    ///
+    /// Returns an `InvalidInput` error if the image is empty, which PNG
+    /// doesn't allow, or if `pixels` doesn't match its size.
+    pub fn write_png(&self, w: &mut impl Write) -> io::Result<()> {
+        let invalid = |message| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        if self.width == 0 || self.height == 0 {
+            return invalid("Image is empty");
+        }
+        if self.pixels.len() as u64 != u64::from(self.width) * u64::from(self.height) * 4 {
+            return invalid("Image pixels don't match its size");
+        }
+
+        w.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+        let mut header = Vec::new();
+        header.write_u32::<BigEndian>(self.width)?;
+        header.write_u32::<BigEndian>(self.height)?;
+        // 8 bits per channel RGBA, no interlacing.
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+        write_chunk(w, b"IHDR", &header)?;
+
+        let mut encoder = zlib::Encoder::new(Vec::new())?;
+        for row in self.pixels.chunks(self.width as usize * 4) {
+            // Each row starts with its filter type, none.
+            encoder.write_all(&[0])?;
+            encoder.write_all(row)?;
+        }
+        write_chunk(w, b"IDAT", &encoder.finish().into_result()?)?;
+        write_chunk(w, b"IEND", &[])
+    }
+}
+
+fn write_chunk(w: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(data.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "PNG chunk too large"))?;
+    w.write_u32::<BigEndian>(len)?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    w.write_u32::<BigEndian>(crc32(&[kind, data]))
+}
+
+/// The CRC-32 of the concatenated `parts`, as used by PNG.
+fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+    for part in parts {
+        for &byte in *part {
+            crc ^= u32::from(byte);
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    0xedb8_8320 ^ (crc >> 1)
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+    }
+    !crc
+}
+
+/// A box to draw, with its color as channels from 0 to 1.
+struct Shape {
+    aabb: Aabb,
+    color: [f32; 4],
+}
+
+fn shapes(data: &WriteData, options: &RenderOptions) -> Vec<Shape> {
+    data.bricks
+        .iter()
+        .filter(|brick| brick.visibility || options.include_invisible)
+        .filter_map(|brick| {
+            let color = resolve_color(data, brick)?;
+            let aabb = brick.aabb_with(&data.brick_assets, &options.catalog);
+            let f = |v: u8| f32::from(v) / 255.0;
+            Some(Shape {
+                aabb,
+                color: [f(color.r()), f(color.g()), f(color.b()), f(color.a())],
+            })
+        })
+        .collect()
+}
+
+/// Project a point in save units for `view`, before applying the camera.
+fn project(view: View, (x, y, z): (f64, f64, f64)) -> (f64, f64) {
+    match view {
+        View::TopDown => (x, y),
+        View::Isometric => {
+            let (cos, sin) = (30f64.to_radians().cos(), 30f64.to_radians().sin());
+            ((x - y) * cos, (x + y) * sin - z)
+        }
+    }
+}
+
+fn corners(aabb: &Aabb) -> [(f64, f64, f64); 8] {
+    let (min, max) = (aabb.min, aabb.max);
+    let mut corners = [(0.0, 0.0, 0.0); 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        let pick = |bit: usize, a: i32, b: i32| f64::from(if i & bit != 0 { b } else { a });
+        *corner = (
+            pick(1, min.0, max.0),
+            pick(2, min.1, max.1),
+            pick(4, min.2, max.2),
+        );
+    }
+    corners
+}
+
+/// A camera showing all of `shapes` with a small margin.
+fn fit(shapes: &[Shape], options: &RenderOptions) -> Camera {
+    let mut min = (f64::MAX, f64::MAX);
+    let mut max = (f64::MIN, f64::MIN);
+    for shape in shapes {
+        for corner in &corners(&shape.aabb) {
+            let (u, v) = project(options.view, *corner);
+            min = (min.0.min(u), min.1.min(v));
+            max = (max.0.max(u), max.1.max(v));
+        }
+    }
+    if shapes.is_empty() {
+        return Camera {
+            center: (0.0, 0.0, 0.0),
+            scale: 1.0,
+        };
+    }
+
+    let width = f64::from(options.width.max(1));
+    let height = f64::from(options.height.max(1));
+    let scale = ((max.0 - min.0) / width).max((max.1 - min.1) / height) * 1.05;
+    let (u, v) = ((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0);
+    // Find a point projecting to the center of the projected bounds.
+    let center = match options.view {
+        View::TopDown => (u, v, 0.0),
+        View::Isometric => {
+            let cos = 30f64.to_radians().cos();
+            let sin = 30f64.to_radians().sin();
+            let (x_minus_y, x_plus_y) = (u / cos, v / sin);
+            (
+                (x_minus_y + x_plus_y) / 2.0,
+                (x_plus_y - x_minus_y) / 2.0,
+                0.0,
+            )
+        }
+    };
+    Camera {
+        center,
+        scale: if scale > 0.0 { scale } else { 1.0 },
+    }
+}
+
+/// Render `data` to an image.
+///
+/// ```no_run
+/// # use std::fs::File;
+/// use brs::render::{self, RenderOptions, View};
+/// # let data: brs::WriteData = unimplemented!();
+/// let options = RenderOptions {
+///     width: 256,
+///     height: 256,
+///     view: View::TopDown,
+///     ..Default::default()
+/// };
+/// render::render(&data, &options).write_png(&mut File::create("map.png")?)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn render(data: &WriteData, options: &RenderOptions) -> Image {
+    let mut image = Image::new(options.width, options.height, options.background);
+    let mut shapes = shapes(data, options);
+    let camera = options.camera.unwrap_or_else(|| fit(&shapes, options));
+
+    let (cu, cv) = project(options.view, camera.center);
+    let to_pixel = |point: (f64, f64, f64)| {
+        let (u, v) = project(options.view, point);
+        (
+            (u - cu) / camera.scale + f64::from(options.width) / 2.0,
+            (v - cv) / camera.scale + f64::from(options.height) / 2.0,
+        )
+    };
+    let shade = |color: [f32; 4], light: f32| {
+        [
+            color[0] * light,
+            color[1] * light,
+            color[2] * light,
+            color[3],
+        ]
+    };
+
+    match options.view {
+        View::TopDown => {
+            shapes.sort_by_key(|s| s.aabb.max.2);
+            let (low, high) = match (shapes.first(), shapes.last()) {
+                (Some(first), Some(last)) => (first.aabb.max.2, last.aabb.max.2),
+                _ => (0, 0),
+            };
+            for shape in &shapes {
+                let height = if high > low {
+                    (shape.aabb.max.2 - low) as f32 / (high - low) as f32
+                } else {
+                    1.0
+                };
+                let c = corners(&shape.aabb);
+                let top = [c[4], c[5], c[7], c[6]].map(to_pixel);
+                image.fill(&top, shade(shape.color, 0.6 + 0.4 * height));
+            }
+        }
+        View::Isometric => {
+            // Nearer boxes have larger coordinates. Sorting by the far corner
+            // first keeps large flat bricks like baseplates behind the
+            // bricks standing on them.
+            let sum = |(x, y, z): (i32, i32, i32)| i64::from(x) + i64::from(y) + i64::from(z);
+            shapes.sort_by_key(|s| (sum(s.aabb.min), sum(s.aabb.max)));
+            for shape in &shapes {
+                let c = corners(&shape.aabb);
+                let x_side = [c[1], c[3], c[7], c[5]].map(to_pixel);
+                let y_side = [c[2], c[3], c[7], c[6]].map(to_pixel);
+                let top = [c[4], c[5], c[7], c[6]].map(to_pixel);
+                image.fill(&x_side, shade(shape.color, 0.75));
+                image.fill(&y_side, shade(shape.color, 0.55));
+                image.fill(&top, shape.color);
+            }
+        }
+    }
+    image
+}
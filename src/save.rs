@@ -1,3 +1,4 @@
+use crate::Orientation;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use std::fmt;
 use uuid::Uuid;
@@ -11,6 +12,7 @@ use uuid::Uuid;
 /// `size` is used for procedural bricks. For fixed size brick assets, it's
 /// more efficient to use `(0, 0, 0)` (the file will be smaller).
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Brick {
     pub asset_name_index: u32,
     pub size: (u32, u32, u32),
@@ -24,8 +26,22 @@ pub struct Brick {
     pub owner_index: Option<u32>,
 }
 
+impl Brick {
+    /// The combined direction and rotation of the brick.
+    pub fn orientation(&self) -> Orientation {
+        Orientation::new(self.direction, self.rotation)
+    }
+
+    /// Set the direction and rotation of the brick from `orientation`.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.direction = orientation.direction;
+        self.rotation = orientation.rotation;
+    }
+}
+
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, IntoPrimitive, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Direction {
     XPositive,
     XNegative,
@@ -39,6 +55,7 @@ pub enum Direction {
 #[derive(
     Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, IntoPrimitive, TryFromPrimitive,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Rotation {
     Deg0,
     Deg90,
@@ -47,6 +64,7 @@ pub enum Rotation {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColorMode {
     /// A color from the color lookup table.
     Set(u32),
@@ -56,6 +74,11 @@ pub enum ColorMode {
 
 /// Represents a RGBA color.
 #[derive(Clone, Copy, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "[u8; 4]", into = "[u8; 4]")
+)]
 pub struct Color(u32);
 
 impl Color {
@@ -74,17 +97,45 @@ impl Color {
     pub fn a(self) -> u8 {
         ((self.0 >> 24) & 0xff) as u8
     }
+
+    /// The index of the color in `palette` closest to this one, comparing
+    /// all four channels. Returns `None` if `palette` is empty.
+    ///
+    /// ```
+    /// use brs::Color;
+    /// let palette = [Color::from_rgba(0, 0, 0, 255), Color::from_rgba(255, 0, 0, 255)];
+    /// assert_eq!(Color::from_rgba(200, 20, 10, 255).nearest(&palette), Some(1));
+    /// ```
+    pub fn nearest(self, palette: &[Color]) -> Option<usize> {
+        let distance = |c: &Color| {
+            let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+            d(c.r(), self.r()) + d(c.g(), self.g()) + d(c.b(), self.b()) + d(c.a(), self.a())
+        };
+        (0..palette.len()).min_by_key(|&i| distance(&palette[i]))
+    }
+}
+
+impl From<u32> for Color {
+    fn from(value: u32) -> Self {
+        Color(value)
+    }
+}
+
+impl From<Color> for u32 {
+    fn from(color: Color) -> Self {
+        color.0
+    }
 }
 
-impl Into<Color> for u32 {
-    fn into(self) -> Color {
-        Color(self)
+impl From<[u8; 4]> for Color {
+    fn from([r, g, b, a]: [u8; 4]) -> Self {
+        Color::from_rgba(r, g, b, a)
     }
 }
 
-impl Into<u32> for Color {
-    fn into(self) -> u32 {
-        self.0
+impl From<Color> for [u8; 4] {
+    fn from(color: Color) -> Self {
+        [color.r(), color.g(), color.b(), color.a()]
     }
 }
 
@@ -102,6 +153,7 @@ impl fmt::Debug for Color {
 }
 
 #[derive(Debug, Clone, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct User {
     pub id: Uuid,
     pub name: String,
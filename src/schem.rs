@@ -0,0 +1,582 @@
+//! Import of Sponge schematic (`.schem`) files, as used by WorldEdit.
+//!
+//! Blocks are mapped to bricks with a [`SchemMapping`](struct.SchemMapping.html)
+//! by their name, such as `minecraft:stone`. Air is skipped and other blocks
+//! without a mapping are counted in
+//! [`SchemImport::unmapped`](struct.SchemImport.html#structfield.unmapped).
+//!
+//! Minecraft's Y axis points up, so it is swapped with Z. North is towards
+//! negative Y and east towards positive X.
+
+use crate::{
+    save::{Brick, Color, ColorMode, Direction, Rotation, User},
+    transform::table_index,
+    WriteData,
+};
+use byteorder::{BigEndian, ReadBytesExt};
+use chrono::prelude::*;
+use libflate::gzip;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{self, prelude::*, BufReader},
+    path::Path,
+};
+use uuid::Uuid;
+
+/// A value in an NBT file.
+#[derive(Debug, Clone, PartialEq)]
+enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<u8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(HashMap<String, Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid schematic {}", what),
+    )
+}
+
+/// Read a length, which must fit in the remaining `r` with `item` bytes each.
+fn read_len(r: &mut &[u8], item: usize) -> io::Result<usize> {
+    let len = r.read_i32::<BigEndian>()?;
+    if len < 0 || len as usize > r.len() / item.max(1) {
+        return Err(invalid("NBT length"));
+    }
+    Ok(len as usize)
+}
+
+fn read_nbt_string(r: &mut &[u8]) -> io::Result<String> {
+    let len = r.read_u16::<BigEndian>()? as usize;
+    if len > r.len() {
+        return Err(invalid("NBT string"));
+    }
+    let (bytes, rest) = r.split_at(len);
+    *r = rest;
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn read_tag(r: &mut &[u8], kind: u8, depth: usize) -> io::Result<Tag> {
+    if depth > 512 {
+        return Err(invalid("NBT nesting"));
+    }
+    Ok(match kind {
+        1 => Tag::Byte(r.read_i8()?),
+        2 => Tag::Short(r.read_i16::<BigEndian>()?),
+        3 => Tag::Int(r.read_i32::<BigEndian>()?),
+        4 => Tag::Long(r.read_i64::<BigEndian>()?),
+        5 => Tag::Float(r.read_f32::<BigEndian>()?),
+        6 => Tag::Double(r.read_f64::<BigEndian>()?),
+        7 => {
+            let len = read_len(r, 1)?;
+            let (bytes, rest) = r.split_at(len);
+            *r = rest;
+            Tag::ByteArray(bytes.to_vec())
+        }
+        8 => Tag::String(read_nbt_string(r)?),
+        9 => {
+            let item = r.read_u8()?;
+            // Every item takes at least a byte, except in lists of End tags.
+            let len = read_len(r, 1)?;
+            let mut items = Vec::with_capacity(len);
+            for _ in 0..len {
+                items.push(if item == 0 {
+                    Tag::Byte(0)
+                } else {
+                    read_tag(r, item, depth + 1)?
+                });
+            }
+            Tag::List(items)
+        }
+        10 => {
+            let mut map = HashMap::new();
+            loop {
+                let kind = r.read_u8()?;
+                if kind == 0 {
+                    break;
+                }
+                let name = read_nbt_string(r)?;
+                map.insert(name, read_tag(r, kind, depth + 1)?);
+            }
+            Tag::Compound(map)
+        }
+        11 => {
+            let len = read_len(r, 4)?;
+            let mut values = vec![0; len];
+            r.read_i32_into::<BigEndian>(&mut values)?;
+            Tag::IntArray(values)
+        }
+        12 => {
+            let len = read_len(r, 8)?;
+            let mut values = vec![0; len];
+            r.read_i64_into::<BigEndian>(&mut values)?;
+            Tag::LongArray(values)
+        }
+        _ => return Err(invalid("NBT tag")),
+    })
+}
+
+impl Tag {
+    fn get(&self, name: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(map) => map.get(name),
+            _ => None,
+        }
+    }
+
+    fn as_int(&self) -> Option<i64> {
+        match *self {
+            Tag::Byte(v) => Some(i64::from(v)),
+            Tag::Short(v) => Some(i64::from(v)),
+            Tag::Int(v) => Some(i64::from(v)),
+            Tag::Long(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// Read the varints in `data`, as block indices are stored.
+fn read_varints(data: &[u8], count: usize) -> io::Result<Vec<u32>> {
+    let mut values = Vec::with_capacity(count.min(data.len()));
+    let mut value = 0u32;
+    let mut shift = 0;
+    for &byte in data {
+        if shift >= 32 {
+            return Err(invalid("block data"));
+        }
+        value |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            values.push(value);
+            value = 0;
+            shift = 0;
+        } else {
+            shift += 7;
+        }
+    }
+    if values.len() != count || shift != 0 {
+        return Err(invalid("block data length"));
+    }
+    Ok(values)
+}
+
+/// The brick a block is mapped to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockBrick {
+    pub asset: String,
+    /// The size of the brick, or `None` for a cube the size of a block.
+    pub size: Option<(u32, u32, u32)>,
+    pub color: Color,
+    pub material: String,
+}
+
+impl BlockBrick {
+    fn cube(color: (u8, u8, u8), material: &str) -> Self {
+        Self {
+            asset: "PB_DefaultBrick".to_string(),
+            size: None,
+            color: Color::from_rgba(color.0, color.1, color.2, 255),
+            material: material.to_string(),
+        }
+    }
+}
+
+const BUILTIN_BLOCKS: &[(&str, (u8, u8, u8), &str)] = &[
+    ("stone", (125, 125, 125), "BMC_Plastic"),
+    ("cobblestone", (122, 122, 122), "BMC_Plastic"),
+    ("stone_bricks", (122, 121, 122), "BMC_Plastic"),
+    ("smooth_stone", (159, 159, 159), "BMC_Plastic"),
+    ("andesite", (136, 136, 137), "BMC_Plastic"),
+    ("diorite", (189, 188, 189), "BMC_Plastic"),
+    ("granite", (149, 103, 86), "BMC_Plastic"),
+    ("deepslate", (80, 80, 82), "BMC_Plastic"),
+    ("dirt", (134, 96, 67), "BMC_Plastic"),
+    ("grass_block", (95, 159, 53), "BMC_Plastic"),
+    ("sand", (219, 207, 163), "BMC_Plastic"),
+    ("sandstone", (216, 203, 155), "BMC_Plastic"),
+    ("gravel", (136, 126, 126), "BMC_Plastic"),
+    ("clay", (160, 166, 179), "BMC_Plastic"),
+    ("snow_block", (249, 254, 254), "BMC_Plastic"),
+    ("ice", (145, 183, 253), "BMC_Hologram"),
+    ("oak_planks", (162, 130, 78), "BMC_Plastic"),
+    ("spruce_planks", (114, 84, 48), "BMC_Plastic"),
+    ("birch_planks", (192, 175, 121), "BMC_Plastic"),
+    ("jungle_planks", (160, 115, 80), "BMC_Plastic"),
+    ("acacia_planks", (168, 90, 50), "BMC_Plastic"),
+    ("dark_oak_planks", (66, 43, 20), "BMC_Plastic"),
+    ("oak_log", (109, 85, 50), "BMC_Plastic"),
+    ("spruce_log", (58, 37, 16), "BMC_Plastic"),
+    ("birch_log", (216, 215, 210), "BMC_Plastic"),
+    ("oak_leaves", (60, 120, 30), "BMC_Plastic"),
+    ("spruce_leaves", (50, 90, 50), "BMC_Plastic"),
+    ("birch_leaves", (90, 130, 60), "BMC_Plastic"),
+    ("bricks", (150, 97, 83), "BMC_Plastic"),
+    ("bookshelf", (117, 94, 59), "BMC_Plastic"),
+    ("obsidian", (15, 10, 24), "BMC_Plastic"),
+    ("glass", (200, 220, 230), "BMC_Hologram"),
+    ("water", (63, 118, 228), "BMC_Hologram"),
+    ("lava", (207, 92, 20), "BMC_Glow"),
+    ("glowstone", (171, 131, 84), "BMC_Glow"),
+    ("sea_lantern", (172, 199, 190), "BMC_Glow"),
+    ("iron_block", (220, 220, 220), "BMC_Metallic"),
+    ("gold_block", (246, 208, 61), "BMC_Metallic"),
+    ("diamond_block", (98, 237, 228), "BMC_Metallic"),
+    ("quartz_block", (235, 229, 222), "BMC_Plastic"),
+];
+
+const DYES: &[(&str, (u8, u8, u8))] = &[
+    ("white", (234, 236, 237)),
+    ("orange", (241, 118, 20)),
+    ("magenta", (190, 69, 180)),
+    ("light_blue", (58, 175, 217)),
+    ("yellow", (249, 198, 40)),
+    ("lime", (112, 185, 26)),
+    ("pink", (238, 141, 172)),
+    ("gray", (63, 68, 72)),
+    ("light_gray", (142, 142, 135)),
+    ("cyan", (21, 138, 145)),
+    ("purple", (122, 42, 173)),
+    ("blue", (53, 57, 157)),
+    ("brown", (114, 72, 41)),
+    ("green", (85, 110, 28)),
+    ("red", (161, 39, 35)),
+    ("black", (21, 21, 26)),
+];
+
+/// Blocks that are left out without being reported as unmapped.
+const AIR: &[&str] = &["minecraft:air", "minecraft:cave_air", "minecraft:void_air"];
+
+/// A lookup table from block names to bricks.
+///
+/// Blocks can also be mapped to `None`, leaving them out like air.
+///
+/// ```
+/// use brs::schem::SchemMapping;
+/// let mapping = SchemMapping::default();
+/// assert_eq!(mapping.get("minecraft:stone").unwrap().unwrap().asset, "PB_DefaultBrick");
+/// assert!(mapping.get("minecraft:red_wool").is_some());
+/// assert!(mapping.get("minecraft:redstone_wire").is_none());
+/// ```
+#[derive(Debug, Clone)]
+pub struct SchemMapping {
+    blocks: HashMap<String, Option<BlockBrick>>,
+}
+
+impl Default for SchemMapping {
+    fn default() -> Self {
+        let mut mapping = Self::empty();
+        for &(name, color, material) in BUILTIN_BLOCKS {
+            mapping.insert(
+                &format!("minecraft:{}", name),
+                Some(BlockBrick::cube(color, material)),
+            );
+        }
+        for &(dye, color) in DYES {
+            for block in &["wool", "concrete", "terracotta"] {
+                mapping.insert(
+                    &format!("minecraft:{}_{}", dye, block),
+                    Some(BlockBrick::cube(color, "BMC_Plastic")),
+                );
+            }
+            mapping.insert(
+                &format!("minecraft:{}_stained_glass", dye),
+                Some(BlockBrick::cube(color, "BMC_Hologram")),
+            );
+        }
+        for &air in AIR {
+            mapping.insert(air, None);
+        }
+        mapping
+    }
+}
+
+impl SchemMapping {
+    /// Create a mapping without any entries.
+    pub fn empty() -> Self {
+        Self {
+            blocks: HashMap::new(),
+        }
+    }
+
+    /// Add or replace the entry for the block `name`, without any properties.
+    pub fn insert(&mut self, name: &str, brick: Option<BlockBrick>) -> Option<Option<BlockBrick>> {
+        self.blocks.insert(name.to_string(), brick)
+    }
+
+    /// Look up the block `name`, without any properties. Returns `None` if the
+    /// block has no entry, and `Some(None)` if it's mapped to nothing.
+    pub fn get(&self, name: &str) -> Option<Option<&BlockBrick>> {
+        self.blocks.get(name).map(Option::as_ref)
+    }
+
+    /// Load additional entries from a text file at `path`.
+    /// See [`load`](#method.load) for the format.
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.load(BufReader::new(File::open(path)?))
+    }
+
+    /// Load additional entries from `r`, replacing existing entries
+    /// for the same blocks.
+    ///
+    /// Each line holds a block name, an asset, a size, the red, green
+    /// and blue components of a color and optionally a material, separated
+    /// by whitespace. The size is either `cube` or three numbers separated
+    /// by commas, like `5,5,2`. A line with only a block name and `none`
+    /// leaves the block out. Empty lines and lines starting with `#` are ignored.
+    ///
+    /// ```
+    /// use brs::schem::SchemMapping;
+    /// let mut mapping = SchemMapping::default();
+    /// mapping.load(&b"minecraft:oak_slab PB_DefaultBrick 5,5,2 162 130 78\nminecraft:torch none\n"[..])?;
+    /// assert_eq!(mapping.get("minecraft:oak_slab").unwrap().unwrap().size, Some((5, 5, 2)));
+    /// assert_eq!(mapping.get("minecraft:torch"), Some(None));
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn load(&mut self, r: impl BufRead) -> io::Result<()> {
+        for (number, line) in r.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid schematic mapping entry on line {}", number + 1),
+                )
+            };
+
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if let [name, "none"] = fields[..] {
+                self.insert(name, None);
+                continue;
+            }
+            if fields.len() != 6 && fields.len() != 7 {
+                return Err(invalid());
+            }
+            let size = match fields[2] {
+                "cube" => None,
+                size => {
+                    let parts = size
+                        .split(',')
+                        .map(str::parse)
+                        .collect::<Result<Vec<u32>, _>>()
+                        .map_err(|_| invalid())?;
+                    match parts[..] {
+                        [x, y, z] => Some((x, y, z)),
+                        _ => return Err(invalid()),
+                    }
+                }
+            };
+            let mut color = [0; 3];
+            for (value, field) in color.iter_mut().zip(&fields[3..6]) {
+                *value = field.parse().map_err(|_| invalid())?;
+            }
+
+            self.insert(
+                fields[0],
+                Some(BlockBrick {
+                    asset: fields[1].to_string(),
+                    size,
+                    color: Color::from_rgba(color[0], color[1], color[2], 255),
+                    material: fields.get(6).unwrap_or(&"BMC_Plastic").to_string(),
+                }),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Options for [`read`](fn.read.html).
+#[derive(Debug, Clone)]
+pub struct SchemOptions {
+    /// Half the edge length of each block in save units.
+    pub block_size: u32,
+    pub mapping: SchemMapping,
+}
+
+impl Default for SchemOptions {
+    fn default() -> Self {
+        Self {
+            block_size: 5,
+            mapping: SchemMapping::default(),
+        }
+    }
+}
+
+/// The result of reading a schematic.
+#[derive(Debug, Clone)]
+pub struct SchemImport {
+    pub data: WriteData,
+    /// Names of blocks without a mapping, with how many were skipped.
+    pub unmapped: BTreeMap<String, usize>,
+}
+
+/// The orientation of a block from its properties. Horizontal facings turn
+/// the brick around Z so that its positive X side faces that way, and
+/// vertical facings and axes point the brick's top in that direction.
+fn orientation(properties: &HashMap<&str, &str>) -> (Direction, Rotation) {
+    match properties.get("facing").copied() {
+        Some("east") => return (Direction::ZPositive, Rotation::Deg0),
+        Some("south") => return (Direction::ZPositive, Rotation::Deg90),
+        Some("west") => return (Direction::ZPositive, Rotation::Deg180),
+        Some("north") => return (Direction::ZPositive, Rotation::Deg270),
+        Some("up") => return (Direction::ZPositive, Rotation::Deg0),
+        Some("down") => return (Direction::ZNegative, Rotation::Deg0),
+        _ => (),
+    }
+    match properties.get("axis").copied() {
+        Some("x") => (Direction::XPositive, Rotation::Deg0),
+        Some("z") => (Direction::YPositive, Rotation::Deg0),
+        _ => (Direction::ZPositive, Rotation::Deg0),
+    }
+}
+
+/// Split a block state like `minecraft:oak_log[axis=x]` into its name and properties.
+fn parse_state(state: &str) -> (&str, HashMap<&str, &str>) {
+    match state.find('[') {
+        Some(start) => {
+            let properties = state[start + 1..]
+                .trim_end_matches(']')
+                .split(',')
+                .filter_map(|p| {
+                    let mut parts = p.splitn(2, '=');
+                    Some((parts.next()?.trim(), parts.next()?.trim()))
+                })
+                .collect();
+            (&state[..start], properties)
+        }
+        None => (state, HashMap::new()),
+    }
+}
+
+/// Read a Sponge schematic, version 1 to 3, from `r`.
+///
+/// ```no_run
+/// # use std::fs::File;
+/// use brs::schem::{self, SchemOptions};
+/// let import = schem::read(File::open("castle.schem")?, &SchemOptions::default())?;
+/// for (block, count) in &import.unmapped {
+///     println!("skipped {} blocks of {}", count, block);
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read(r: impl Read, options: &SchemOptions) -> io::Result<SchemImport> {
+    let mut r = BufReader::new(r);
+    // Schematics are gzipped, but plain NBT is read too.
+    let mut bytes = Vec::new();
+    if r.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+        gzip::Decoder::new(r)?.read_to_end(&mut bytes)?;
+    } else {
+        r.read_to_end(&mut bytes)?;
+    }
+
+    let mut slice = &bytes[..];
+    if slice.read_u8()? != 10 {
+        return Err(invalid("root tag"));
+    }
+    read_nbt_string(&mut slice)?;
+    let mut root = read_tag(&mut slice, 10, 0)?;
+    // Version 3 wraps everything in a compound named "Schematic".
+    if let Some(inner) = root.get("Schematic") {
+        root = inner.clone();
+    }
+
+    let dimension = |name: &str| {
+        root.get(name)
+            .and_then(Tag::as_int)
+            .map(|v| v as u16 as usize)
+            .ok_or_else(|| invalid(name))
+    };
+    let (width, height, length) = (
+        dimension("Width")?,
+        dimension("Height")?,
+        dimension("Length")?,
+    );
+
+    let blocks = root.get("Blocks");
+    let palette = blocks
+        .and_then(|b| b.get("Palette"))
+        .or_else(|| root.get("Palette"));
+    let data = blocks
+        .and_then(|b| b.get("Data"))
+        .or_else(|| root.get("BlockData"));
+    let (palette, data) = match (palette, data) {
+        (Some(Tag::Compound(palette)), Some(Tag::ByteArray(data))) => (palette, data),
+        _ => return Err(invalid("block palette")),
+    };
+
+    // Palette entries by index.
+    let mut states: HashMap<u32, &str> = HashMap::new();
+    for (state, index) in palette {
+        let index = index.as_int().ok_or_else(|| invalid("palette index"))?;
+        states.insert(index as u32, state);
+    }
+
+    let indices = read_varints(data, width * height * length)?;
+
+    let mut out = WriteData {
+        map: "Plate".to_string(),
+        author: User {
+            id: Uuid::nil(),
+            name: "Schematic".to_string(),
+        },
+        description: String::new(),
+        save_time: Utc::now(),
+        mods: Vec::new(),
+        brick_assets: Vec::new(),
+        colors: Vec::new(),
+        materials: Vec::new(),
+        brick_owners: Vec::new(),
+        bricks: Vec::new(),
+    };
+    let mut unmapped = BTreeMap::new();
+    let s = options.block_size;
+    let center = |v: usize| (v as i32) * 2 * s as i32 + s as i32;
+
+    for (i, index) in indices.into_iter().enumerate() {
+        let state = states.get(&index).ok_or_else(|| invalid("block index"))?;
+        let (name, properties) = parse_state(state);
+        let brick = match options.mapping.get(name) {
+            Some(Some(brick)) => brick,
+            Some(None) => continue,
+            None => {
+                *unmapped.entry(name.to_string()).or_insert(0) += 1;
+                continue;
+            }
+        };
+
+        let x = i % width;
+        let z = i / width % length;
+        let y = i / (width * length);
+        let (direction, rotation) = orientation(&properties);
+        out.bricks.push(Brick {
+            asset_name_index: table_index(&mut out.brick_assets, &brick.asset),
+            size: brick.size.unwrap_or((s, s, s)),
+            position: (center(x), center(z), center(y)),
+            direction,
+            rotation,
+            collision: true,
+            visibility: true,
+            material_index: table_index(&mut out.materials, &brick.material),
+            color: ColorMode::Set(table_index(&mut out.colors, &brick.color)),
+            owner_index: None,
+        });
+    }
+
+    Ok(SchemImport {
+        data: out,
+        unmapped,
+    })
+}
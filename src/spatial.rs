@@ -0,0 +1,343 @@
+//! A spatial index over brick bounding boxes.
+//!
+//! [`SpatialIndex`](struct.SpatialIndex.html) is a bounding volume hierarchy
+//! built once from all bricks of a save, answering box, point and
+//! nearest neighbor queries without comparing every pair of bricks.
+
+use crate::{catalog::AssetCatalog, read::ReadBricks, Aabb, Brick, WriteData};
+use std::{cmp::Ordering, collections::BinaryHeap, io};
+
+/// The most items stored in a single leaf node.
+const LEAF_SIZE: usize = 8;
+
+#[derive(Debug, Clone)]
+struct Node {
+    aabb: Aabb,
+    /// For leaves, the range in `items`. For inner nodes, `start` is the
+    /// index of the left child, and the right child follows it.
+    start: u32,
+    len: u32,
+    leaf: bool,
+}
+
+/// A bounding volume hierarchy over the bounding boxes of bricks.
+///
+/// Each item is identified by its index in the bricks it was built from.
+///
+/// ```
+/// # use brs::{spatial::SpatialIndex, Aabb};
+/// let index = SpatialIndex::from_aabbs(vec![
+///     Aabb::new((0, 0, 0), (10, 10, 12)),
+///     Aabb::new((10, 0, 0), (20, 10, 12)),
+///     Aabb::new((100, 100, 0), (110, 110, 12)),
+/// ]);
+/// assert_eq!(index.query(&Aabb::new((5, 5, 5), (15, 5, 5))), vec![0, 1]);
+/// assert_eq!(index.touching(0), vec![1]);
+/// assert_eq!(index.nearest((90, 90, 0)), Some(2));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SpatialIndex {
+    aabbs: Vec<Aabb>,
+    items: Vec<u32>,
+    nodes: Vec<Node>,
+}
+
+impl SpatialIndex {
+    /// Build an index over the bounding boxes of all bricks in `data`,
+    /// using the built-in [`AssetCatalog`](../catalog/struct.AssetCatalog.html).
+    pub fn new(data: &WriteData) -> Self {
+        Self::with_catalog(data, &AssetCatalog::default())
+    }
+
+    /// Build an index over the bounding boxes of all bricks in `data`,
+    /// using `catalog` for fixed size bricks.
+    pub fn with_catalog(data: &WriteData, catalog: &AssetCatalog) -> Self {
+        Self::from_aabbs(
+            data.bricks
+                .iter()
+                .map(|brick| brick.aabb_with(&data.brick_assets, catalog))
+                .collect(),
+        )
+    }
+
+    /// Build an index from a brick stream, without keeping the bricks around.
+    /// `brick_assets` is the asset table from the second header.
+    ///
+    /// ```no_run
+    /// # use brs::{catalog::AssetCatalog, spatial::SpatialIndex, HasHeader2};
+    /// # let reader: brs::read::ReaderAfterHeader2<std::fs::File> = unimplemented!();
+    /// let (reader, bricks) = reader.iter_bricks_and_reader()?;
+    /// let index = SpatialIndex::from_bricks(bricks, reader.brick_assets(), &AssetCatalog::default())?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn from_bricks(
+        bricks: ReadBricks,
+        brick_assets: &[String],
+        catalog: &AssetCatalog,
+    ) -> io::Result<Self> {
+        let aabbs = bricks
+            .map(|brick| brick.map(|brick: Brick| brick.aabb_with(brick_assets, catalog)))
+            .collect::<io::Result<_>>()?;
+        Ok(Self::from_aabbs(aabbs))
+    }
+
+    /// Build an index over arbitrary boxes.
+    pub fn from_aabbs(aabbs: Vec<Aabb>) -> Self {
+        assert!(aabbs.len() <= u32::MAX as usize);
+
+        let mut index = Self {
+            items: (0..aabbs.len() as u32).collect(),
+            aabbs,
+            nodes: Vec::new(),
+        };
+
+        if !index.items.is_empty() {
+            index.nodes.push(Node {
+                aabb: index.aabbs[0],
+                start: 0,
+                len: index.items.len() as u32,
+                leaf: true,
+            });
+            index.split(0);
+        }
+
+        index
+    }
+
+    /// Recursively split the leaf `node` until all leaves are small enough.
+    fn split(&mut self, node: usize) {
+        let mut stack = vec![node];
+
+        while let Some(node) = stack.pop() {
+            let start = self.nodes[node].start as usize;
+            let len = self.nodes[node].len as usize;
+            let items = &mut self.items[start..start + len];
+
+            let aabbs = &self.aabbs;
+            let aabb = items
+                .iter()
+                .map(|&i| aabbs[i as usize])
+                .reduce(|a, b| a.union(&b))
+                .unwrap();
+            self.nodes[node].aabb = aabb;
+
+            if len <= LEAF_SIZE {
+                continue;
+            }
+
+            // Split on the median center along the longest axis.
+            let size = aabb.size();
+            let axis = if size.0 >= size.1 && size.0 >= size.2 {
+                0
+            } else if size.1 >= size.2 {
+                1
+            } else {
+                2
+            };
+            let key = |i: &u32| {
+                let a = &aabbs[*i as usize];
+                match axis {
+                    0 => i64::from(a.min.0) + i64::from(a.max.0),
+                    1 => i64::from(a.min.1) + i64::from(a.max.1),
+                    _ => i64::from(a.min.2) + i64::from(a.max.2),
+                }
+            };
+            let mid = len / 2;
+            items.select_nth_unstable_by_key(mid, key);
+
+            let left = self.nodes.len();
+            self.nodes.push(Node {
+                aabb,
+                start: start as u32,
+                len: mid as u32,
+                leaf: true,
+            });
+            self.nodes.push(Node {
+                aabb,
+                start: (start + mid) as u32,
+                len: (len - mid) as u32,
+                leaf: true,
+            });
+            self.nodes[node].start = left as u32;
+            self.nodes[node].leaf = false;
+
+            stack.push(left);
+            stack.push(left + 1);
+        }
+    }
+
+    /// The number of boxes in the index.
+    pub fn len(&self) -> usize {
+        self.aabbs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aabbs.is_empty()
+    }
+
+    /// The bounding box of the brick at `index`.
+    pub fn aabb(&self, index: usize) -> Aabb {
+        self.aabbs[index]
+    }
+
+    /// All boxes in the index, in brick order.
+    pub fn aabbs(&self) -> &[Aabb] {
+        &self.aabbs
+    }
+
+    /// Call `f` with the index of every brick whose box passes `item`,
+    /// descending only into nodes whose box passes `node`.
+    fn visit(
+        &self,
+        node: impl Fn(&Aabb) -> bool,
+        item: impl Fn(&Aabb) -> bool,
+        mut f: impl FnMut(usize),
+    ) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            let n = &self.nodes[n];
+            if !node(&n.aabb) {
+                continue;
+            }
+            if n.leaf {
+                let start = n.start as usize;
+                for &i in &self.items[start..start + n.len as usize] {
+                    if item(&self.aabbs[i as usize]) {
+                        f(i as usize);
+                    }
+                }
+            } else {
+                stack.push(n.start as usize);
+                stack.push(n.start as usize + 1);
+            }
+        }
+    }
+
+    /// The indices of all bricks sharing volume with `aabb`, in ascending order.
+    pub fn query(&self, aabb: &Aabb) -> Vec<usize> {
+        let mut found = Vec::new();
+        self.visit(
+            |n| n.touches(aabb),
+            |a| a.intersects(aabb),
+            |i| found.push(i),
+        );
+        found.sort_unstable();
+        found
+    }
+
+    /// The indices of all bricks whose box contains `point`,
+    /// including on its surface, in ascending order.
+    pub fn query_point(&self, point: (i32, i32, i32)) -> Vec<usize> {
+        let mut found = Vec::new();
+        self.visit(
+            |n| n.contains_point(point),
+            |a| a.contains_point(point),
+            |i| found.push(i),
+        );
+        found.sort_unstable();
+        found
+    }
+
+    /// The indices of all other bricks that intersect or share a face, edge
+    /// or corner with the brick at `index`, in ascending order.
+    pub fn touching(&self, index: usize) -> Vec<usize> {
+        let aabb = self.aabbs[index];
+        let mut found = Vec::new();
+        self.visit(
+            |n| n.touches(&aabb),
+            |a| a.touches(&aabb),
+            |i| {
+                if i != index {
+                    found.push(i)
+                }
+            },
+        );
+        found.sort_unstable();
+        found
+    }
+
+    /// The index of the brick closest to `point`, with ties going to the
+    /// lowest index. Returns `None` if the index is empty.
+    pub fn nearest(&self, point: (i32, i32, i32)) -> Option<usize> {
+        self.nearest_n(point, 1).into_iter().next()
+    }
+
+    /// The indices of the `n` bricks closest to `point`, closest first.
+    pub fn nearest_n(&self, point: (i32, i32, i32), n: usize) -> Vec<usize> {
+        if self.nodes.is_empty() || n == 0 {
+            return Vec::new();
+        }
+
+        // Best-first search; nodes and items share the queue, ordered by
+        // their distance to `point`.
+        let mut queue = BinaryHeap::new();
+        queue.push(Entry {
+            distance: self.nodes[0].aabb.distance_squared(point),
+            node: true,
+            index: 0,
+        });
+
+        let mut found = Vec::with_capacity(n);
+        while let Some(entry) = queue.pop() {
+            if !entry.node {
+                found.push(entry.index);
+                if found.len() == n {
+                    break;
+                }
+                continue;
+            }
+
+            let node = &self.nodes[entry.index];
+            if node.leaf {
+                let start = node.start as usize;
+                for &i in &self.items[start..start + node.len as usize] {
+                    queue.push(Entry {
+                        distance: self.aabbs[i as usize].distance_squared(point),
+                        node: false,
+                        index: i as usize,
+                    });
+                }
+            } else {
+                for child in node.start as usize..node.start as usize + 2 {
+                    queue.push(Entry {
+                        distance: self.nodes[child].aabb.distance_squared(point),
+                        node: true,
+                        index: child,
+                    });
+                }
+            }
+        }
+
+        found
+    }
+}
+
+#[derive(PartialEq, Eq)]
+struct Entry {
+    distance: u64,
+    node: bool,
+    index: usize,
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed to make the heap pop the closest entry first. On equal
+        // distance, nodes are expanded before items are returned, so that
+        // ties are resolved by index.
+        other
+            .distance
+            .cmp(&self.distance)
+            .then(self.node.cmp(&other.node))
+            .then(other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
@@ -0,0 +1,209 @@
+//! Summaries of the contents of a save.
+
+use crate::{
+    catalog::AssetCatalog,
+    read::{Header2, ReaderAfterHeader2},
+    Aabb, Brick, Color, ColorMode, HasHeader2, User, WriteData,
+};
+use std::io::{self, Read};
+
+/// The volume of a 1x1 brick in cubic save units.
+const BRICK_VOLUME: f64 = 10.0 * 10.0 * 12.0;
+
+/// Counts of what a save is made of, computed in a single pass over its bricks.
+///
+/// The per-table counts line up with the lookup tables of the save, and
+/// include entries that no brick uses.
+///
+/// ```no_run
+/// # use std::fs::File;
+/// use brs::stats::Stats;
+/// let reader = brs::Reader::new(File::open("village.brs")?)?;
+/// let stats = Stats::from_reader(reader.read_header1()?.read_header2()?)?;
+/// println!("{} bricks, {} with custom colors", stats.brick_count, stats.custom_colors);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stats {
+    pub brick_count: usize,
+    /// Bricks per brick asset.
+    pub assets: Vec<(String, usize)>,
+    /// Bricks per material.
+    pub materials: Vec<(String, usize)>,
+    /// Bricks per color in the color lookup table.
+    pub colors: Vec<(Color, usize)>,
+    /// Bricks with a color from the color lookup table.
+    pub palette_colors: usize,
+    /// Bricks with a custom color.
+    pub custom_colors: usize,
+    /// Bricks per brick owner.
+    pub owners: Vec<(User, usize)>,
+    /// Bricks without an owner.
+    pub unowned: usize,
+    /// Bricks of procedural assets.
+    pub procedural: usize,
+    /// Bricks of fixed size assets.
+    pub fixed: usize,
+    /// Bricks with collision turned off.
+    pub no_collision: usize,
+    /// Bricks with visibility turned off.
+    pub invisible: usize,
+    /// Bricks with an asset, material, color or owner index
+    /// outside of their lookup table.
+    pub invalid_indices: usize,
+    /// The bounding box of all bricks, if there are any.
+    pub bounds: Option<Aabb>,
+}
+
+impl Stats {
+    /// Compute statistics for a save being read, consuming the reader.
+    /// Uses the built-in [`AssetCatalog`](../catalog/struct.AssetCatalog.html).
+    pub fn from_reader<R: Read>(reader: ReaderAfterHeader2<R>) -> io::Result<Self> {
+        let (reader, bricks) = reader.iter_bricks_and_reader()?;
+        Self::from_bricks(reader.header2(), bricks, &AssetCatalog::default())
+    }
+
+    /// Compute statistics for `bricks`, using the lookup tables in `header2`
+    /// and `catalog` for sizes and kinds of assets.
+    pub fn from_bricks(
+        header2: &Header2,
+        bricks: impl IntoIterator<Item = io::Result<Brick>>,
+        catalog: &AssetCatalog,
+    ) -> io::Result<Self> {
+        let mut stats = Self::new(
+            &header2.brick_assets,
+            &header2.materials,
+            &header2.colors,
+            &header2.brick_owners,
+        );
+        let procedural = procedural_assets(&header2.brick_assets, catalog);
+        for brick in bricks {
+            stats.add(&brick?, &header2.brick_assets, &procedural, catalog);
+        }
+        Ok(stats)
+    }
+
+    /// Compute statistics for the bricks of `data`.
+    /// Uses the built-in [`AssetCatalog`](../catalog/struct.AssetCatalog.html).
+    pub fn from_write_data(data: &WriteData) -> Self {
+        let catalog = AssetCatalog::default();
+        let mut stats = Self::new(
+            &data.brick_assets,
+            &data.materials,
+            &data.colors,
+            &data.brick_owners,
+        );
+        let procedural = procedural_assets(&data.brick_assets, &catalog);
+        for brick in &data.bricks {
+            stats.add(brick, &data.brick_assets, &procedural, &catalog);
+        }
+        stats
+    }
+
+    fn new(assets: &[String], materials: &[String], colors: &[Color], owners: &[User]) -> Self {
+        Self {
+            brick_count: 0,
+            assets: assets.iter().map(|a| (a.clone(), 0)).collect(),
+            materials: materials.iter().map(|m| (m.clone(), 0)).collect(),
+            colors: colors.iter().map(|&c| (c, 0)).collect(),
+            palette_colors: 0,
+            custom_colors: 0,
+            owners: owners.iter().map(|o| (o.clone(), 0)).collect(),
+            unowned: 0,
+            procedural: 0,
+            fixed: 0,
+            no_collision: 0,
+            invisible: 0,
+            invalid_indices: 0,
+            bounds: None,
+        }
+    }
+
+    fn add(
+        &mut self,
+        brick: &Brick,
+        assets: &[String],
+        procedural: &[bool],
+        catalog: &AssetCatalog,
+    ) {
+        self.brick_count += 1;
+
+        let mut valid = true;
+        let mut count = |table_len: usize, index: u32| {
+            if (index as usize) < table_len {
+                Some(index as usize)
+            } else {
+                valid = false;
+                None
+            }
+        };
+
+        if let Some(i) = count(self.assets.len(), brick.asset_name_index) {
+            self.assets[i].1 += 1;
+        }
+        if let Some(i) = count(self.materials.len(), brick.material_index) {
+            self.materials[i].1 += 1;
+        }
+        match brick.color {
+            ColorMode::Set(c) => {
+                self.palette_colors += 1;
+                if let Some(i) = count(self.colors.len(), c) {
+                    self.colors[i].1 += 1;
+                }
+            }
+            ColorMode::Custom(_) => self.custom_colors += 1,
+        }
+        match brick.owner_index {
+            Some(o) => {
+                if let Some(i) = count(self.owners.len(), o) {
+                    self.owners[i].1 += 1;
+                }
+            }
+            None => self.unowned += 1,
+        }
+        if !valid {
+            self.invalid_indices += 1;
+        }
+
+        let is_procedural = procedural
+            .get(brick.asset_name_index as usize)
+            .copied()
+            .unwrap_or(false)
+            || brick.size != (0, 0, 0);
+        if is_procedural {
+            self.procedural += 1;
+        } else {
+            self.fixed += 1;
+        }
+
+        if !brick.collision {
+            self.no_collision += 1;
+        }
+        if !brick.visibility {
+            self.invisible += 1;
+        }
+
+        let aabb = brick.aabb_with(assets, catalog);
+        self.bounds = Some(self.bounds.map_or(aabb, |b| b.union(&aabb)));
+    }
+
+    /// The number of bricks per 1x1 brick of volume in the bounding box.
+    /// Returns `None` if there are no bricks or the bounds have no volume.
+    pub fn density(&self) -> Option<f64> {
+        let volume = self.bounds?.volume();
+        if volume == 0 {
+            return None;
+        }
+        Some(self.brick_count as f64 / (volume as f64 / BRICK_VOLUME))
+    }
+}
+
+/// Whether each asset is known to be procedural. Bricks of unknown assets
+/// count as procedural only if they have a size.
+fn procedural_assets(assets: &[String], catalog: &AssetCatalog) -> Vec<bool> {
+    assets
+        .iter()
+        .map(|name| catalog.get(name).is_some_and(|info| info.is_procedural()))
+        .collect()
+}
@@ -0,0 +1,206 @@
+//! Generating terrain from heightmaps.
+
+use crate::{
+    optimize::{self, MergeOptions},
+    save::{Brick, Color, ColorMode, Direction, Rotation, User},
+    Aabb, WriteData,
+};
+use chrono::prelude::*;
+use std::{convert::TryFrom, io};
+use uuid::Uuid;
+
+/// A color for all terrain up to a height.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorBand {
+    /// The highest point of the band in save units,
+    /// starting where the band below it ends.
+    /// It is rounded down to a multiple of the terrain's step.
+    pub up_to: i32,
+    pub color: Color,
+}
+
+/// How the bricks of the terrain are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TerrainMode {
+    /// One brick per cell and color band, from the ground up.
+    Columns,
+    /// Neighboring cells merged into larger slabs where they line up,
+    /// using [`optimize::merge_bricks`](../optimize/fn.merge_bricks.html).
+    Slabs,
+}
+
+/// Options for [`build`](fn.build.html).
+#[derive(Debug, Clone)]
+pub struct TerrainOptions {
+    /// Half the width of each cell in save units, so the default of 5
+    /// makes cells the width of a 1x1 brick. At most
+    /// [`optimize::MAX_BRICK_SIZE`](../optimize/constant.MAX_BRICK_SIZE.html).
+    pub cell_size: u32,
+    /// Save units per unit of height in the heightmap.
+    pub vertical_scale: f32,
+    /// Heights are rounded to multiples of this many save units,
+    /// which defaults to the height of a plate. It must be even, and no more
+    /// than twice [`optimize::MAX_BRICK_SIZE`](../optimize/constant.MAX_BRICK_SIZE.html).
+    pub step: u32,
+    pub mode: TerrainMode,
+    pub material: String,
+    /// Colors from the lowest band up. Terrain above the last band
+    /// has the color of the last band.
+    pub bands: Vec<ColorBand>,
+}
+
+impl Default for TerrainOptions {
+    fn default() -> Self {
+        Self {
+            cell_size: 5,
+            vertical_scale: 1.0,
+            step: 4,
+            mode: TerrainMode::Slabs,
+            material: "BMC_Plastic".to_string(),
+            bands: vec![
+                ColorBand {
+                    up_to: 8,
+                    color: Color::from_rgba(194, 178, 128, 255),
+                },
+                ColorBand {
+                    up_to: 80,
+                    color: Color::from_rgba(76, 140, 60, 255),
+                },
+                ColorBand {
+                    up_to: 160,
+                    color: Color::from_rgba(120, 115, 110, 255),
+                },
+                ColorBand {
+                    up_to: i32::MAX,
+                    color: Color::from_rgba(240, 240, 245, 255),
+                },
+            ],
+        }
+    }
+}
+
+/// Build terrain from a heightmap of `width` by `depth` cells, with
+/// `heights` given row by row. Cells run along X and rows along Y.
+///
+/// Columns start on the ground, at a height of 0, and cells with a height
+/// of 0 or less are left empty. Columns taller than the largest brick are
+/// split into several bricks. The color bands become the color
+/// lookup table of the save.
+///
+/// ```
+/// use brs::terrain::{self, TerrainMode, TerrainOptions};
+/// let heights = [0.0, 4.0, 4.0, 12.0];
+/// let options = TerrainOptions {
+///     mode: TerrainMode::Columns,
+///     ..Default::default()
+/// };
+/// let data = terrain::build(2, 2, &heights, &options)?;
+/// // The last column reaches into the second color band.
+/// assert_eq!(data.bricks.len(), 4);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn build(
+    width: u32,
+    depth: u32,
+    heights: &[f32],
+    options: &TerrainOptions,
+) -> io::Result<WriteData> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message.to_string());
+    if heights.len() as u64 != u64::from(width) * u64::from(depth) {
+        return Err(invalid("Heightmap doesn't match its size"));
+    }
+    if heights.iter().any(|h| !h.is_finite()) {
+        return Err(invalid("Heightmap contains a height that isn't finite"));
+    }
+    if options.bands.is_empty() {
+        return Err(invalid("Terrain has no color bands"));
+    }
+    let max_size = optimize::MAX_BRICK_SIZE;
+    if options.step == 0 || options.step % 2 == 1 || options.step > 2 * max_size {
+        return Err(invalid(
+            "Terrain step must be a positive even number no larger than the largest brick",
+        ));
+    }
+    if options.cell_size == 0 || options.cell_size > max_size {
+        return Err(invalid(
+            "Terrain cell size must be positive and no larger than the largest brick",
+        ));
+    }
+    // Checking the far corner keeps every cell's coordinates in range.
+    let extent = |n: u32| i32::try_from(u64::from(n) * 2 * u64::from(options.cell_size));
+    if extent(width).is_err() || extent(depth).is_err() {
+        return Err(invalid("Terrain is too large"));
+    }
+
+    let step = options.step as i32;
+    let round_down = |z: i32| z - z.rem_euclid(step);
+    // The tallest brick, and the highest point, that are multiples of the step.
+    let max_height = round_down(2 * max_size as i32);
+    let max_top = round_down(i32::MAX);
+    let c = i64::from(options.cell_size);
+    let coord = |n: usize| (n as i64 * 2 * c) as i32;
+    let mut bricks = Vec::new();
+    for (i, &height) in heights.iter().enumerate() {
+        let scaled = f64::from(height) * f64::from(options.vertical_scale);
+        let top = ((scaled / f64::from(step)).round() * f64::from(step)).min(f64::from(max_top));
+        let top = top as i32;
+        if top <= 0 {
+            continue;
+        }
+        let (x, y) = (i % width as usize, i / width as usize);
+        let (x0, x1, y0, y1) = (coord(x), coord(x + 1), coord(y), coord(y + 1));
+
+        // Split the column where it crosses into the next band,
+        // and wherever it would be taller than the largest brick.
+        let mut bottom = 0;
+        for (index, band) in options.bands.iter().enumerate() {
+            let last = index == options.bands.len() - 1;
+            let end = if last {
+                top
+            } else {
+                top.min(round_down(band.up_to.max(0)))
+            };
+            while end > bottom {
+                let z = bottom + (end - bottom).min(max_height);
+                let aabb = Aabb::new((x0, y0, bottom), (x1, y1, z));
+                let (w, d, h) = aabb.size();
+                bricks.push(Brick {
+                    asset_name_index: 0,
+                    size: (w / 2, d / 2, h / 2),
+                    position: aabb.center(),
+                    direction: Direction::ZPositive,
+                    rotation: Rotation::Deg0,
+                    collision: true,
+                    visibility: true,
+                    material_index: 0,
+                    color: ColorMode::Set(index as u32),
+                    owner_index: None,
+                });
+                bottom = z;
+            }
+            if bottom >= top {
+                break;
+            }
+        }
+    }
+
+    let mut data = WriteData {
+        map: "Plate".to_string(),
+        author: User {
+            id: Uuid::nil(),
+            name: "Terrain".to_string(),
+        },
+        description: String::new(),
+        save_time: Utc::now(),
+        mods: Vec::new(),
+        brick_assets: vec!["PB_DefaultBrick".to_string()],
+        colors: options.bands.iter().map(|band| band.color).collect(),
+        materials: vec![options.material.clone()],
+        brick_owners: Vec::new(),
+        bricks,
+    };
+    if options.mode == TerrainMode::Slabs {
+        optimize::merge_bricks(&mut data, &MergeOptions::default());
+    }
+    Ok(data)
+}
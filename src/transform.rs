@@ -0,0 +1,132 @@
+use crate::{Brick, ColorMode, Orientation, WriteData};
+use std::io;
+
+impl Brick {
+    /// Rotate the brick around the origin by `orientation`,
+    /// turning both its position and its own orientation.
+    ///
+    /// Returns an `InvalidInput` error and leaves the brick unchanged
+    /// if its position would overflow.
+    pub fn rotate(&mut self, orientation: Orientation) -> io::Result<()> {
+        self.position = orientation
+            .checked_apply(self.position)
+            .ok_or_else(rotated_out_of_range)?;
+        self.set_orientation(orientation * self.orientation());
+        Ok(())
+    }
+}
+
+fn rotated_out_of_range() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "Rotated brick position out of range",
+    )
+}
+
+impl WriteData {
+    /// Move all bricks by `offset`.
+    ///
+    /// Returns an `InvalidInput` error and leaves the bricks unchanged
+    /// if any position would overflow.
+    pub fn translate(&mut self, offset: (i32, i32, i32)) -> io::Result<()> {
+        let moved = |p: (i32, i32, i32)| {
+            Some((
+                p.0.checked_add(offset.0)?,
+                p.1.checked_add(offset.1)?,
+                p.2.checked_add(offset.2)?,
+            ))
+        };
+        if self
+            .bricks
+            .iter()
+            .any(|brick| moved(brick.position).is_none())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Translated brick position out of range",
+            ));
+        }
+        for brick in &mut self.bricks {
+            brick.position = moved(brick.position).unwrap();
+        }
+        Ok(())
+    }
+
+    /// Rotate all bricks around the origin by `orientation`.
+    ///
+    /// Returns an `InvalidInput` error and leaves the bricks unchanged
+    /// if any position would overflow.
+    ///
+    /// ```no_run
+    /// # use brs::{Direction, Orientation, Rotation};
+    /// # let mut data: brs::WriteData = unimplemented!();
+    /// // Turn the build a quarter turn around the vertical axis.
+    /// data.rotate(Orientation::new(Direction::ZPositive, Rotation::Deg90))?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn rotate(&mut self, orientation: Orientation) -> io::Result<()> {
+        if self
+            .bricks
+            .iter()
+            .any(|brick| orientation.checked_apply(brick.position).is_none())
+        {
+            return Err(rotated_out_of_range());
+        }
+        for brick in &mut self.bricks {
+            brick.rotate(orientation)?;
+        }
+        Ok(())
+    }
+
+    /// Append the bricks of `other`, adding any assets, materials, colors,
+    /// owners and mods missing from the lookup tables and remapping the
+    /// indices of the appended bricks to match.
+    ///
+    /// Returns an `InvalidData` error and leaves `self` unchanged if a brick
+    /// of `other` refers to an entry missing from its lookup tables,
+    /// see [`check_indices`](#method.check_indices).
+    pub fn merge(&mut self, other: &WriteData) -> io::Result<()> {
+        other.check_indices()?;
+
+        let assets = merge_table(&mut self.brick_assets, &other.brick_assets);
+        let materials = merge_table(&mut self.materials, &other.materials);
+        let colors = merge_table(&mut self.colors, &other.colors);
+        let owners = merge_table(&mut self.brick_owners, &other.brick_owners);
+        merge_table(&mut self.mods, &other.mods);
+
+        // All indices were checked above.
+        let remap = |map: &[u32], index: u32| map[index as usize];
+
+        self.bricks.extend(other.bricks.iter().map(|brick| Brick {
+            asset_name_index: remap(&assets, brick.asset_name_index),
+            material_index: remap(&materials, brick.material_index),
+            color: match brick.color {
+                ColorMode::Set(i) => ColorMode::Set(remap(&colors, i)),
+                custom => custom,
+            },
+            owner_index: brick.owner_index.map(|i| remap(&owners, i)),
+            ..brick.clone()
+        }));
+        Ok(())
+    }
+}
+
+/// Add the entries of `other` missing from `table`, returning
+/// the index in `table` of each entry of `other`.
+pub(crate) fn merge_table<T: PartialEq + Clone>(table: &mut Vec<T>, other: &[T]) -> Vec<u32> {
+    other
+        .iter()
+        .map(|value| table_index(table, value))
+        .collect()
+}
+
+/// The index of `value` in `table`, adding it if missing.
+pub(crate) fn table_index<T: PartialEq + Clone>(table: &mut Vec<T>, value: &T) -> u32 {
+    match table.iter().position(|v| v == value) {
+        Some(i) => i as u32,
+        None => {
+            table.push(value.clone());
+            (table.len() - 1) as u32
+        }
+    }
+}
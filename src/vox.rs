@@ -0,0 +1,519 @@
+//! Import of MagicaVoxel `.vox` models.
+//!
+//! Every voxel becomes a cube of `PB_DefaultBrick`, colored with a color
+//! from the color lookup table, which holds the palette of the model.
+//! Files with several models are placed using their scene graph when present,
+//! and otherwise all models are placed at the origin.
+//!
+//! The Y axis is mirrored, as MagicaVoxel uses a right-handed coordinate system.
+
+use crate::{
+    save::{Brick, Color, ColorMode, Direction, Rotation, User},
+    Matrix, WriteData,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use chrono::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    io::{self, Read},
+};
+use uuid::Uuid;
+
+/// Options for importing a `.vox` model.
+#[derive(Debug, Clone)]
+pub struct VoxOptions {
+    /// Half the edge length of each voxel in save units,
+    /// so the default of 5 makes voxels the width of a 1x1 brick.
+    pub voxel_size: u32,
+    /// Merge runs of voxels of the same color along the X axis into one brick.
+    pub merge_runs: bool,
+    /// The material of all bricks.
+    pub material: String,
+    /// The most voxels to place, counting each node of the scene graph
+    /// visited as one more. Shared nodes are placed once for each path to
+    /// them, so a small file can describe a huge scene. Larger scenes are
+    /// rejected with an `InvalidData` error. Defaults to `1 << 24`,
+    /// a full 256 x 256 x 256 model.
+    pub max_voxels: usize,
+}
+
+impl Default for VoxOptions {
+    fn default() -> Self {
+        Self {
+            voxel_size: 5,
+            merge_runs: true,
+            material: "BMC_Plastic".to_string(),
+            max_voxels: 1 << 24,
+        }
+    }
+}
+
+/// The largest model size MagicaVoxel supports along each axis.
+const MAX_MODEL_SIZE: i32 = 256;
+
+/// The largest translation accepted in a transform node, which with the
+/// depth limit of the scene graph keeps voxel positions far from overflowing.
+const MAX_TRANSLATION: i32 = 1 << 20;
+
+/// A model from a `SIZE` and `XYZI` chunk pair.
+struct Model {
+    size: [i32; 3],
+    /// Positions and color indices of the voxels.
+    voxels: Vec<([i32; 3], u8)>,
+}
+
+/// A node of the scene graph.
+enum Node {
+    Transform {
+        child: i32,
+        rotation: Matrix,
+        translation: [i32; 3],
+    },
+    Group(Vec<i32>),
+    Shape(Vec<i32>),
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid .vox {}", what))
+}
+
+fn read_count(r: &mut &[u8]) -> io::Result<usize> {
+    let count = r.read_i32::<LittleEndian>()?;
+    if count < 0 || count as usize > r.len() {
+        return Err(invalid("count"));
+    }
+    Ok(count as usize)
+}
+
+fn read_string(r: &mut &[u8]) -> io::Result<String> {
+    let len = read_count(r)?;
+    let (bytes, rest) = r.split_at(len);
+    *r = rest;
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn read_dict(r: &mut &[u8]) -> io::Result<HashMap<String, String>> {
+    let count = read_count(r)?;
+    let mut dict = HashMap::new();
+    for _ in 0..count {
+        let key = read_string(r)?;
+        let value = read_string(r)?;
+        dict.insert(key, value);
+    }
+    Ok(dict)
+}
+
+fn read_node_ids(r: &mut &[u8]) -> io::Result<Vec<i32>> {
+    let count = read_count(r)?;
+    (0..count).map(|_| r.read_i32::<LittleEndian>()).collect()
+}
+
+/// Decode a rotation stored as the positions and signs
+/// of the non-zero entries in each row.
+fn decode_rotation(packed: u8) -> Option<Matrix> {
+    let first = (packed & 3) as usize;
+    let second = ((packed >> 2) & 3) as usize;
+    if first > 2 || second > 2 || first == second {
+        return None;
+    }
+    let third = 3 - first - second;
+    let mut matrix = [[0; 3]; 3];
+    for (row, &column) in [first, second, third].iter().enumerate() {
+        matrix[row][column] = if packed & (16 << row) != 0 { -1 } else { 1 };
+    }
+    Some(matrix)
+}
+
+fn parse_transform(dict: &HashMap<String, String>) -> io::Result<(Matrix, [i32; 3])> {
+    let rotation = match dict.get("_r") {
+        Some(r) => r
+            .parse()
+            .ok()
+            .and_then(decode_rotation)
+            .ok_or_else(|| invalid("rotation"))?,
+        None => [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+    };
+    let mut translation = [0; 3];
+    if let Some(t) = dict.get("_t") {
+        let parts = t
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<i32>, _>>()
+            .map_err(|_| invalid("translation"))?;
+        if parts.len() != 3 || parts.iter().any(|t| t.abs() > MAX_TRANSLATION) {
+            return Err(invalid("translation"));
+        }
+        translation.copy_from_slice(&parts);
+    }
+    Ok((rotation, translation))
+}
+
+/// Rotate the voxel at `v`, mapping the cell rather than its corner,
+/// so that mirrored axes map cell `a` to cell `-a - 1`.
+fn rotate_cell(m: &Matrix, v: [i32; 3]) -> [i32; 3] {
+    let mut out = [0; 3];
+    for (i, row) in m.iter().enumerate() {
+        out[i] = row[0] * v[0] + row[1] * v[1] + row[2] * v[2];
+        if row.iter().any(|&x| x < 0) {
+            out[i] -= 1;
+        }
+    }
+    out
+}
+
+fn mat_mul(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut out = [[0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+/// A parsed `.vox` file.
+struct VoxFile {
+    models: Vec<Model>,
+    palette: Vec<Color>,
+    nodes: HashMap<i32, Node>,
+}
+
+impl VoxFile {
+    fn parse(bytes: &[u8]) -> io::Result<Self> {
+        let mut r = bytes;
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != b"VOX " {
+            return Err(invalid("magic"));
+        }
+        let _version = r.read_i32::<LittleEndian>()?;
+
+        let mut file = VoxFile {
+            models: Vec::new(),
+            palette: default_palette(),
+            nodes: HashMap::new(),
+        };
+        let mut size = None;
+
+        // The children of MAIN follow its header directly,
+        // so all chunks can be read in a flat list.
+        while !r.is_empty() {
+            let mut id = [0; 4];
+            r.read_exact(&mut id)?;
+            let content_len = r.read_i32::<LittleEndian>()?;
+            let _children_len = r.read_i32::<LittleEndian>()?;
+            let mut content = usize::try_from(content_len)
+                .ok()
+                .and_then(|len| r.get(..len))
+                .ok_or_else(|| invalid("chunk"))?;
+            r = &r[content.len()..];
+            let c = &mut content;
+
+            match &id {
+                b"SIZE" => {
+                    let mut s = [0; 3];
+                    for value in &mut s {
+                        *value = c.read_i32::<LittleEndian>()?;
+                        if !(0..=MAX_MODEL_SIZE).contains(value) {
+                            return Err(invalid("model size"));
+                        }
+                    }
+                    size = Some(s);
+                }
+                b"XYZI" => {
+                    let size = size
+                        .take()
+                        .ok_or_else(|| invalid("XYZI chunk without SIZE"))?;
+                    let count = c.read_i32::<LittleEndian>()?;
+                    if count < 0 || count as usize > c.len() / 4 {
+                        return Err(invalid("voxel count"));
+                    }
+                    let mut voxels = Vec::with_capacity(count as usize);
+                    for _ in 0..count {
+                        let mut v = [0; 4];
+                        c.read_exact(&mut v)?;
+                        voxels.push(([i32::from(v[0]), i32::from(v[1]), i32::from(v[2])], v[3]));
+                    }
+                    file.models.push(Model { size, voxels });
+                }
+                b"RGBA" => {
+                    // Entry `i` of the chunk is color index `i + 1`.
+                    file.palette = vec![Color::from_rgba(0, 0, 0, 0)];
+                    for _ in 0..255 {
+                        let mut v = [0; 4];
+                        c.read_exact(&mut v)?;
+                        file.palette.push(Color::from_rgba(v[0], v[1], v[2], v[3]));
+                    }
+                }
+                b"nTRN" => {
+                    let id = c.read_i32::<LittleEndian>()?;
+                    read_dict(c)?;
+                    let child = c.read_i32::<LittleEndian>()?;
+                    let _reserved = c.read_i32::<LittleEndian>()?;
+                    let _layer = c.read_i32::<LittleEndian>()?;
+                    let frames = read_count(c)?;
+                    let (rotation, translation) = if frames > 0 {
+                        parse_transform(&read_dict(c)?)?
+                    } else {
+                        parse_transform(&HashMap::new())?
+                    };
+                    file.nodes.insert(
+                        id,
+                        Node::Transform {
+                            child,
+                            rotation,
+                            translation,
+                        },
+                    );
+                }
+                b"nGRP" => {
+                    let id = c.read_i32::<LittleEndian>()?;
+                    read_dict(c)?;
+                    file.nodes.insert(id, Node::Group(read_node_ids(c)?));
+                }
+                b"nSHP" => {
+                    let id = c.read_i32::<LittleEndian>()?;
+                    read_dict(c)?;
+                    let count = read_count(c)?;
+                    let mut models = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        models.push(c.read_i32::<LittleEndian>()?);
+                        read_dict(c)?;
+                    }
+                    file.nodes.insert(id, Node::Shape(models));
+                }
+                _ => (),
+            }
+        }
+
+        Ok(file)
+    }
+
+    /// Collect the voxels of all models in world space, later models
+    /// overwriting earlier ones.
+    fn voxels(&self, max_voxels: usize) -> io::Result<BTreeMap<[i32; 3], u8>> {
+        let mut out = BTreeMap::new();
+        let mut budget = max_voxels;
+        if self.nodes.contains_key(&0) {
+            let identity = [[1, 0, 0], [0, 1, 0], [0, 0, 1]];
+            self.visit(0, &identity, [0; 3], 0, &mut budget, &mut out)?;
+        } else {
+            for model in &self.models {
+                spend(&mut budget, model.voxels.len())?;
+                out.extend(model.voxels.iter().copied());
+            }
+        }
+        Ok(out)
+    }
+
+    fn visit(
+        &self,
+        id: i32,
+        rotation: &Matrix,
+        translation: [i32; 3],
+        depth: usize,
+        budget: &mut usize,
+        out: &mut BTreeMap<[i32; 3], u8>,
+    ) -> io::Result<()> {
+        // Deep enough for any real scene, and stops cycles.
+        if depth > 256 {
+            return Err(invalid("scene graph"));
+        }
+        spend(budget, 1)?;
+        match self.nodes.get(&id) {
+            Some(Node::Transform {
+                child,
+                rotation: r,
+                translation: t,
+            }) => {
+                let offset = rotation_apply(rotation, *t);
+                let translation = [
+                    translation[0] + offset[0],
+                    translation[1] + offset[1],
+                    translation[2] + offset[2],
+                ];
+                self.visit(
+                    *child,
+                    &mat_mul(rotation, r),
+                    translation,
+                    depth + 1,
+                    budget,
+                    out,
+                )?;
+            }
+            Some(Node::Group(children)) => {
+                for &child in children {
+                    self.visit(child, rotation, translation, depth + 1, budget, out)?;
+                }
+            }
+            Some(Node::Shape(models)) => {
+                for &model in models {
+                    let model = self
+                        .models
+                        .get(model as usize)
+                        .ok_or_else(|| invalid("model index"))?;
+                    spend(budget, model.voxels.len())?;
+                    // Models are centered on their translation.
+                    let half = [model.size[0] / 2, model.size[1] / 2, model.size[2] / 2];
+                    for &(v, color) in &model.voxels {
+                        let local = [v[0] - half[0], v[1] - half[1], v[2] - half[2]];
+                        let w = rotate_cell(rotation, local);
+                        out.insert(
+                            [
+                                w[0] + translation[0],
+                                w[1] + translation[1],
+                                w[2] + translation[2],
+                            ],
+                            color,
+                        );
+                    }
+                }
+            }
+            None => return Err(invalid("node reference")),
+        }
+        Ok(())
+    }
+}
+
+/// Take `amount` from `budget`, failing if there isn't enough left.
+fn spend(budget: &mut usize, amount: usize) -> io::Result<()> {
+    *budget = budget.checked_sub(amount).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Too many voxels in .vox scene")
+    })?;
+    Ok(())
+}
+
+fn rotation_apply(m: &Matrix, v: [i32; 3]) -> [i32; 3] {
+    let mut out = [0; 3];
+    for (i, row) in m.iter().enumerate() {
+        out[i] = row[0] * v[0] + row[1] * v[1] + row[2] * v[2];
+    }
+    out
+}
+
+/// The palette used by models without an `RGBA` chunk, indexed by color index.
+fn default_palette() -> Vec<Color> {
+    const LEVELS: [u8; 6] = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    const RAMP: [u8; 10] = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+
+    let mut palette = vec![Color::from_rgba(0, 0, 0, 0)];
+    for &r in &LEVELS {
+        for &g in &LEVELS {
+            for &b in &LEVELS {
+                palette.push(Color::from_rgba(r, g, b, 255));
+            }
+        }
+    }
+    // The cube ends with black, which is left out.
+    palette.pop();
+    for &v in &RAMP {
+        palette.push(Color::from_rgba(v, 0, 0, 255));
+    }
+    for &v in &RAMP {
+        palette.push(Color::from_rgba(0, v, 0, 255));
+    }
+    for &v in &RAMP {
+        palette.push(Color::from_rgba(0, 0, v, 255));
+    }
+    for &v in &RAMP {
+        palette.push(Color::from_rgba(v, v, v, 255));
+    }
+    palette
+}
+
+/// Read a MagicaVoxel model from `r` and build a save from its voxels.
+///
+/// The color lookup table holds palette entries 1 through 255, so a voxel
+/// of color index `i` uses color `i - 1`.
+///
+/// ```no_run
+/// # use std::fs::File;
+/// use brs::vox::{self, VoxOptions};
+/// let options = VoxOptions {
+///     voxel_size: 1,
+///     ..Default::default()
+/// };
+/// let data = vox::read(File::open("chr_knight.vox")?, &options)?;
+/// brs::write_save(&mut File::create("knight.brs")?, &data)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read(mut r: impl Read, options: &VoxOptions) -> io::Result<WriteData> {
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes)?;
+    let file = VoxFile::parse(&bytes)?;
+    let voxels = file.voxels(options.max_voxels)?;
+
+    let s = i64::from(options.voxel_size);
+    let mut bricks = Vec::new();
+    let mut push = |start: [i32; 3], length: i32, color: u8| -> io::Result<()> {
+        let out_of_range = |_| invalid("voxel position");
+        let length = s * i64::from(length);
+        let coord = |v: i64| i32::try_from(v).map_err(out_of_range);
+        let start = [
+            i64::from(start[0]),
+            i64::from(start[1]),
+            i64::from(start[2]),
+        ];
+        bricks.push(Brick {
+            asset_name_index: 0,
+            size: (
+                u32::try_from(length).map_err(out_of_range)?,
+                options.voxel_size,
+                options.voxel_size,
+            ),
+            position: (
+                coord(start[0] * 2 * s + length)?,
+                coord(-(start[1] * 2 * s + s))?,
+                coord(start[2] * 2 * s + s)?,
+            ),
+            direction: Direction::ZPositive,
+            rotation: Rotation::Deg0,
+            collision: true,
+            visibility: true,
+            material_index: 0,
+            color: ColorMode::Set(u32::from(color.max(1)) - 1),
+            owner_index: None,
+        });
+        Ok(())
+    };
+
+    // Voxels are sorted by X last, so runs along X are consecutive.
+    let mut voxels = voxels.into_iter().collect::<Vec<_>>();
+    voxels.sort_by_key(|&(v, _)| (v[2], v[1], v[0]));
+    let mut run: Option<([i32; 3], i32, u8)> = None;
+    for (v, color) in voxels {
+        if let Some((start, length, run_color)) = run {
+            let continues = options.merge_runs
+                && run_color == color
+                && start[1] == v[1]
+                && start[2] == v[2]
+                && start[0] + length == v[0];
+            if continues {
+                run = Some((start, length + 1, color));
+                continue;
+            }
+            push(start, length, run_color)?;
+        }
+        run = Some((v, 1, color));
+    }
+    if let Some((start, length, color)) = run {
+        push(start, length, color)?;
+    }
+
+    Ok(WriteData {
+        map: "Plate".to_string(),
+        author: User {
+            id: Uuid::nil(),
+            name: "MagicaVoxel".to_string(),
+        },
+        description: String::new(),
+        save_time: Utc::now(),
+        mods: Vec::new(),
+        brick_assets: vec!["PB_DefaultBrick".to_string()],
+        colors: file.palette[1..].to_vec(),
+        materials: vec![options.material.clone()],
+        brick_owners: Vec::new(),
+        bricks,
+    })
+}
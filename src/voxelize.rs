@@ -0,0 +1,508 @@
+//! Voxelizing triangle meshes from STL and OBJ files into bricks.
+//!
+//! Every voxel becomes a cube of `PB_DefaultBrick`. Voxels are found by
+//! testing each triangle against the cells of a grid fitted around the mesh,
+//! so even thin features end up as a closed shell of bricks.
+//!
+//! Meshes use right-handed coordinates, so the Y axis is mirrored unless
+//! [`VoxelizeOptions::y_up`](struct.VoxelizeOptions.html#structfield.y_up)
+//! swaps it with Z instead.
+
+use crate::{
+    optimize::{self, MergeOptions},
+    save::{Brick, Color, ColorMode, Direction, Rotation, User},
+    WriteData,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use chrono::prelude::*;
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Read},
+};
+use uuid::Uuid;
+
+/// A triangle mesh.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    /// The color of each vertex, if the mesh has vertex colors.
+    pub colors: Option<Vec<Color>>,
+    /// Indices into `positions` of the corners of each triangle.
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl Mesh {
+    /// Read an ASCII or binary STL file from `r`.
+    ///
+    /// STL files have no vertex colors, and every triangle has its own vertices.
+    pub fn read_stl(mut r: impl Read) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        r.read_to_end(&mut bytes)?;
+
+        // Binary files may also start with "solid", so the size decides.
+        let binary = bytes.len() >= 84 && {
+            let count = (&bytes[80..84]).read_u32::<LittleEndian>()?;
+            bytes.len() as u64 == 84 + 50 * u64::from(count)
+        };
+        if binary {
+            return Self::read_binary_stl(&bytes[84..]);
+        }
+        if !bytes.starts_with(b"solid") {
+            return Err(invalid("STL header"));
+        }
+
+        let text = String::from_utf8_lossy(&bytes);
+        let mut mesh = Mesh::default();
+        let mut tokens = text.split_whitespace();
+        while let Some(token) = tokens.next() {
+            if token != "vertex" {
+                continue;
+            }
+            let mut position = [0.0; 3];
+            for value in &mut position {
+                *value = tokens
+                    .next()
+                    .and_then(|t| t.parse().ok())
+                    .ok_or_else(|| invalid("STL vertex"))?;
+            }
+            mesh.positions.push(position);
+        }
+        if mesh.positions.len() % 3 != 0 {
+            return Err(invalid("STL facet"));
+        }
+        mesh.triangles = (0..mesh.positions.len() as u32 / 3)
+            .map(|t| [t * 3, t * 3 + 1, t * 3 + 2])
+            .collect();
+        Ok(mesh)
+    }
+
+    fn read_binary_stl(mut r: &[u8]) -> io::Result<Self> {
+        let mut mesh = Mesh::default();
+        while !r.is_empty() {
+            let mut values = [0.0; 12];
+            r.read_f32_into::<LittleEndian>(&mut values)?;
+            let _attributes = r.read_u16::<LittleEndian>()?;
+            // The first three values are the normal.
+            let first = mesh.positions.len() as u32;
+            for v in values[3..].chunks(3) {
+                mesh.positions.push([v[0], v[1], v[2]]);
+            }
+            mesh.triangles.push([first, first + 1, first + 2]);
+        }
+        Ok(mesh)
+    }
+
+    /// Read a Wavefront OBJ file from `r`.
+    ///
+    /// Polygons are split into triangles. Vertex colors are read from
+    /// `v x y z r g b` lines, as written by many tools, and vertices
+    /// without one are white.
+    pub fn read_obj(r: impl BufRead) -> io::Result<Self> {
+        let mut mesh = Mesh::default();
+        let mut colors = Vec::new();
+        let mut has_colors = false;
+
+        for (number, line) in r.lines().enumerate() {
+            let line = line?;
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid OBJ data on line {}", number + 1),
+                )
+            };
+            let mut parts = line.split_whitespace();
+            match parts.next() {
+                Some("v") => {
+                    let values = parts
+                        .map(str::parse)
+                        .collect::<Result<Vec<f32>, _>>()
+                        .map_err(|_| invalid())?;
+                    match values[..] {
+                        [x, y, z] | [x, y, z, _] => {
+                            mesh.positions.push([x, y, z]);
+                            colors.push(Color::from_rgba(255, 255, 255, 255));
+                        }
+                        [x, y, z, r, g, b] => {
+                            let c = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+                            mesh.positions.push([x, y, z]);
+                            colors.push(Color::from_rgba(c(r), c(g), c(b), 255));
+                            has_colors = true;
+                        }
+                        _ => return Err(invalid()),
+                    }
+                }
+                Some("f") => {
+                    let count = mesh.positions.len() as i64;
+                    let corners = parts
+                        .map(|corner| {
+                            // Only the position index before any `/` matters.
+                            let index: i64 = corner
+                                .split('/')
+                                .next()
+                                .and_then(|i| i.parse().ok())
+                                .ok_or_else(invalid)?;
+                            let index = if index < 0 { count + index } else { index - 1 };
+                            if index < 0 || index >= count {
+                                return Err(invalid());
+                            }
+                            Ok(index as u32)
+                        })
+                        .collect::<io::Result<Vec<_>>>()?;
+                    if corners.len() < 3 {
+                        return Err(invalid());
+                    }
+                    for i in 1..corners.len() - 1 {
+                        mesh.triangles
+                            .push([corners[0], corners[i], corners[i + 1]]);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        if has_colors {
+            mesh.colors = Some(colors);
+        }
+        Ok(mesh)
+    }
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid {}", what))
+}
+
+/// Which voxels are filled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fill {
+    /// Only voxels touching the surface of the mesh.
+    Surface,
+    /// Voxels on the surface and all voxels enclosed by it.
+    Solid,
+}
+
+/// How voxels are colored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoxelColor {
+    /// One color for all voxels, from the color lookup table.
+    Single(Color),
+    /// Custom colors from the vertex colors of the mesh, averaged over each
+    /// triangle. Meshes without vertex colors are white.
+    Vertex,
+}
+
+/// Options for [`voxelize`](fn.voxelize.html).
+#[derive(Debug, Clone)]
+pub struct VoxelizeOptions {
+    /// The number of voxels along the longest side of the mesh.
+    pub resolution: u32,
+    /// Half the edge length of each voxel in save units.
+    pub voxel_size: u32,
+    pub fill: Fill,
+    pub color: VoxelColor,
+    pub material: String,
+    /// Treat Y as the up axis of the mesh, as is usual for OBJ files,
+    /// rather than Z, as is usual for STL files.
+    pub y_up: bool,
+    /// Merge voxels into larger bricks with
+    /// [`optimize::merge_bricks`](../optimize/fn.merge_bricks.html).
+    pub merge: bool,
+}
+
+impl Default for VoxelizeOptions {
+    fn default() -> Self {
+        Self {
+            resolution: 64,
+            voxel_size: 5,
+            fill: Fill::Surface,
+            color: VoxelColor::Single(Color::from_rgba(255, 255, 255, 255)),
+            material: "BMC_Plastic".to_string(),
+            y_up: false,
+            merge: true,
+        }
+    }
+}
+
+type Vec3 = [f64; 3];
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// Whether the triangle `t` overlaps the box centered on the origin with
+/// half extents `h`, by the separating axis theorem.
+fn triangle_overlaps_box(t: [Vec3; 3], h: Vec3) -> bool {
+    let separated = |axis: Vec3| {
+        let p = [dot(t[0], axis), dot(t[1], axis), dot(t[2], axis)];
+        let r = h[0] * axis[0].abs() + h[1] * axis[1].abs() + h[2] * axis[2].abs();
+        let min = p[0].min(p[1]).min(p[2]);
+        let max = p[0].max(p[1]).max(p[2]);
+        min > r || max < -r
+    };
+
+    let edges = [sub(t[1], t[0]), sub(t[2], t[1]), sub(t[0], t[2])];
+    let box_axes = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    // The box axes, the triangle normal and the cross products of both edges.
+    if box_axes.iter().any(|&a| separated(a)) {
+        return false;
+    }
+    if separated(cross(edges[0], edges[1])) {
+        return false;
+    }
+    for &edge in &edges {
+        for &axis in &box_axes {
+            let axis = cross(edge, axis);
+            if axis != [0.0; 3] && separated(axis) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// A dense grid of voxel states.
+struct Grid {
+    size: [usize; 3],
+    cells: Vec<u8>,
+}
+
+const EMPTY: u8 = 0;
+const SURFACE: u8 = 1;
+const OUTSIDE: u8 = 2;
+
+impl Grid {
+    fn index(&self, [x, y, z]: [usize; 3]) -> usize {
+        (z * self.size[1] + y) * self.size[0] + x
+    }
+
+    /// Mark all empty cells reachable from the border as outside.
+    fn flood_outside(&mut self) {
+        let [sx, sy, sz] = self.size;
+        let mut stack = Vec::new();
+        for z in 0..sz {
+            for y in 0..sy {
+                for x in 0..sx {
+                    let border =
+                        x == 0 || y == 0 || z == 0 || x == sx - 1 || y == sy - 1 || z == sz - 1;
+                    if border {
+                        stack.push([x, y, z]);
+                    }
+                }
+            }
+        }
+        while let Some(cell) = stack.pop() {
+            let i = self.index(cell);
+            if self.cells[i] != EMPTY {
+                continue;
+            }
+            self.cells[i] = OUTSIDE;
+            for axis in 0..3 {
+                if cell[axis] > 0 {
+                    let mut next = cell;
+                    next[axis] -= 1;
+                    stack.push(next);
+                }
+                if cell[axis] + 1 < self.size[axis] {
+                    let mut next = cell;
+                    next[axis] += 1;
+                    stack.push(next);
+                }
+            }
+        }
+    }
+}
+
+/// Voxelize `mesh` into a save.
+///
+/// ```no_run
+/// # use std::{fs::File, io::BufReader};
+/// use brs::voxelize::{self, Fill, Mesh, VoxelizeOptions};
+/// let mesh = Mesh::read_stl(File::open("teapot.stl")?)?;
+/// let options = VoxelizeOptions {
+///     resolution: 100,
+///     fill: Fill::Solid,
+///     ..Default::default()
+/// };
+/// let data = voxelize::voxelize(&mesh, &options)?;
+/// brs::write_save(&mut File::create("teapot.brs")?, &data)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn voxelize(mesh: &Mesh, options: &VoxelizeOptions) -> io::Result<WriteData> {
+    if options.resolution == 0 || options.voxel_size == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Voxel resolution and size must be positive",
+        ));
+    }
+
+    // Convert to the coordinates of the game.
+    let positions = mesh
+        .positions
+        .iter()
+        .map(|&[x, y, z]| {
+            let (x, y, z) = (f64::from(x), f64::from(y), f64::from(z));
+            if options.y_up {
+                [x, z, y]
+            } else {
+                [x, -y, z]
+            }
+        })
+        .collect::<Vec<Vec3>>();
+    if positions.iter().flatten().any(|v| !v.is_finite()) {
+        return Err(invalid("mesh vertex"));
+    }
+
+    let mut min = [f64::MAX; 3];
+    let mut max = [f64::MIN; 3];
+    for triangle in &mesh.triangles {
+        for &corner in triangle {
+            let p = positions
+                .get(corner as usize)
+                .ok_or_else(|| invalid("mesh triangle"))?;
+            for axis in 0..3 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+            }
+        }
+    }
+
+    let mut bricks = Vec::new();
+    let mut colors = Vec::new();
+    if let VoxelColor::Single(color) = options.color {
+        colors.push(color);
+    }
+
+    if !mesh.triangles.is_empty() {
+        let longest = (0..3).map(|a| max[a] - min[a]).fold(0.0, f64::max);
+        let cell = if longest > 0.0 {
+            longest / f64::from(options.resolution)
+        } else {
+            1.0
+        };
+        // One empty cell of padding on each side lets the outside flood around the mesh.
+        let mut size = [0; 3];
+        for axis in 0..3 {
+            size[axis] = ((max[axis] - min[axis]) / cell).floor() as usize + 3;
+            min[axis] -= cell;
+        }
+        let mut grid = Grid {
+            size,
+            cells: vec![EMPTY; size[0] * size[1] * size[2]],
+        };
+        let mut surface_colors = HashMap::new();
+
+        for triangle in &mesh.triangles {
+            let t = triangle.map(|i| positions[i as usize]);
+            let color = match (&options.color, &mesh.colors) {
+                (VoxelColor::Vertex, Some(vertex_colors)) => {
+                    let c = triangle.map(|i| vertex_colors.get(i as usize).copied());
+                    let avg = |f: fn(Color) -> u8| {
+                        let sum: u32 = c.iter().map(|c| u32::from(c.map_or(255, f))).sum();
+                        (sum / 3) as u8
+                    };
+                    Color::from_rgba(avg(Color::r), avg(Color::g), avg(Color::b), 255)
+                }
+                _ => Color::from_rgba(255, 255, 255, 255),
+            };
+
+            let mut lo = [0; 3];
+            let mut hi = [0; 3];
+            for axis in 0..3 {
+                let values = t.iter().map(|p| (p[axis] - min[axis]) / cell);
+                let (a, b) = values.fold((f64::MAX, f64::MIN), |(a, b), v| (a.min(v), b.max(v)));
+                lo[axis] = (a.floor() as usize).saturating_sub(1).max(1);
+                hi[axis] = (b.floor() as usize + 1).min(size[axis] - 2);
+            }
+            for z in lo[2]..=hi[2] {
+                for y in lo[1]..=hi[1] {
+                    for x in lo[0]..=hi[0] {
+                        let center = [
+                            min[0] + (x as f64 + 0.5) * cell,
+                            min[1] + (y as f64 + 0.5) * cell,
+                            min[2] + (z as f64 + 0.5) * cell,
+                        ];
+                        let local = t.map(|p| sub(p, center));
+                        if triangle_overlaps_box(local, [cell / 2.0; 3]) {
+                            let i = grid.index([x, y, z]);
+                            grid.cells[i] = SURFACE;
+                            surface_colors.entry(i).or_insert(color);
+                        }
+                    }
+                }
+            }
+        }
+
+        if options.fill == Fill::Solid {
+            grid.flood_outside();
+        }
+
+        let s = options.voxel_size as i32;
+        for x in 0..size[0] {
+            for y in 0..size[1] {
+                // Enclosed cells take the color of the surface below them.
+                let mut below = Color::from_rgba(255, 255, 255, 255);
+                for z in 0..size[2] {
+                    let i = grid.index([x, y, z]);
+                    let filled = match grid.cells[i] {
+                        SURFACE => {
+                            below = surface_colors[&i];
+                            true
+                        }
+                        EMPTY => options.fill == Fill::Solid,
+                        _ => false,
+                    };
+                    if !filled {
+                        continue;
+                    }
+                    let voxel = |v: usize| (v as i32 - 1) * 2 * s + s;
+                    bricks.push(Brick {
+                        asset_name_index: 0,
+                        size: (options.voxel_size, options.voxel_size, options.voxel_size),
+                        position: (voxel(x), voxel(y), voxel(z)),
+                        direction: Direction::ZPositive,
+                        rotation: Rotation::Deg0,
+                        collision: true,
+                        visibility: true,
+                        material_index: 0,
+                        color: match options.color {
+                            VoxelColor::Single(_) => ColorMode::Set(0),
+                            VoxelColor::Vertex => ColorMode::Custom(below),
+                        },
+                        owner_index: None,
+                    });
+                }
+            }
+        }
+    }
+
+    let mut data = WriteData {
+        map: "Plate".to_string(),
+        author: User {
+            id: Uuid::nil(),
+            name: "Voxelizer".to_string(),
+        },
+        description: String::new(),
+        save_time: Utc::now(),
+        mods: Vec::new(),
+        brick_assets: vec!["PB_DefaultBrick".to_string()],
+        colors,
+        materials: vec![options.material.clone()],
+        brick_owners: Vec::new(),
+        bricks,
+    };
+    if options.merge {
+        optimize::merge_bricks(&mut data, &MergeOptions::default());
+    }
+    Ok(data)
+}
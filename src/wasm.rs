@@ -0,0 +1,432 @@
+//! JavaScript bindings for WebAssembly through `wasm-bindgen`,
+//! enabled by the `wasm-bindgen` feature.
+//!
+//! A `SaveReader` reads the headers of a save in a `Uint8Array` when it's
+//! created, and its bricks in batches with `readBricks`. `writeSave` turns
+//! objects of the same shape back into a save file.
+//!
+//! Headers are plain objects:
+//!
+//! ```text
+//! {
+//!     version, map, description, brickCount,
+//!     author: { id, name }, host: { id, name } | null, saveTime: Date | null,
+//!     mods: string[], brickAssets: string[], materials: string[],
+//!     colors: Uint32Array, brickOwners: { id, name }[],
+//! }
+//! ```
+//!
+//! Bricks are an object of typed arrays with one entry per brick, or three
+//! for `sizes` and `positions`. Colors are `0xAARRGGBB`.
+//!
+//! ```text
+//! {
+//!     count,
+//!     assetIndices: Uint32Array, sizes: Uint32Array, positions: Int32Array,
+//!     // Packed orientations, see `Orientation::packed`.
+//!     orientations: Uint8Array,
+//!     // 1 for collision, plus 2 for visibility.
+//!     flags: Uint8Array,
+//!     materialIndices: Uint32Array,
+//!     // An index into the color table, or -1 for a custom color.
+//!     colorIndices: Int32Array,
+//!     // The color of each brick, looked up in the color table if needed.
+//!     colors: Uint32Array,
+//!     // An index into the owner table, or -1 for none.
+//!     ownerIndices: Int32Array,
+//! }
+//! ```
+
+use crate::{
+    read::{ReadBricks, ReaderAfterHeader2},
+    save::{Brick, Color, ColorMode, User},
+    BrickColumns, HasHeader1, HasHeader2, Orientation, Reader, WriteData,
+};
+use chrono::prelude::*;
+use js_sys::{Array, Date, Int32Array, Object, Reflect, Uint32Array, Uint8Array};
+use std::io::{self, Cursor};
+use uuid::Uuid;
+use wasm_bindgen::prelude::*;
+
+fn js_error(error: io::Error) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+fn set(object: &Object, key: &str, value: impl Into<JsValue>) {
+    Reflect::set(object, &key.into(), &value.into()).unwrap();
+}
+
+fn get(object: &JsValue, key: &str) -> JsValue {
+    Reflect::get(object, &key.into()).unwrap_or(JsValue::UNDEFINED)
+}
+
+fn user_object(user: &User) -> Object {
+    let object = Object::new();
+    set(&object, "id", user.id.to_string());
+    set(&object, "name", user.name.as_str());
+    object
+}
+
+fn string_array(strings: &[String]) -> Array {
+    strings.iter().map(|s| JsValue::from(s.as_str())).collect()
+}
+
+enum Bricks {
+    Pending(Box<ReaderAfterHeader2<Cursor<Vec<u8>>>>),
+    Reading(ReadBricks),
+    Done,
+}
+
+/// A save being read from a `Uint8Array`.
+#[wasm_bindgen]
+pub struct SaveReader {
+    headers: Object,
+    colors: Vec<Color>,
+    bricks: Bricks,
+}
+
+#[wasm_bindgen]
+impl SaveReader {
+    /// Read the headers of the save in `data`, which is copied.
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: &Uint8Array) -> Result<SaveReader, JsError> {
+        let reader = Reader::new(Cursor::new(data.to_vec())).map_err(js_error)?;
+        let version = reader.version();
+        let reader = reader
+            .read_header1()
+            .and_then(|r| r.read_header2())
+            .map_err(js_error)?;
+
+        let headers = Object::new();
+        set(&headers, "version", u16::from(version));
+        set(&headers, "map", reader.map());
+        set(&headers, "description", reader.description());
+        set(&headers, "brickCount", reader.brick_count());
+        set(&headers, "author", user_object(reader.author()));
+        let host = reader.header1().host.as_ref();
+        set(
+            &headers,
+            "host",
+            host.map_or(JsValue::NULL, |h| user_object(h).into()),
+        );
+        let save_time = reader.save_time().map_or(JsValue::NULL, |t| {
+            Date::new(&JsValue::from_f64(t.timestamp_millis() as f64)).into()
+        });
+        set(&headers, "saveTime", save_time);
+        set(&headers, "mods", string_array(reader.mods()));
+        set(&headers, "brickAssets", string_array(reader.brick_assets()));
+        set(&headers, "materials", string_array(reader.materials()));
+        let colors = reader
+            .colors()
+            .iter()
+            .map(|&c| u32::from(c))
+            .collect::<Vec<_>>();
+        set(&headers, "colors", Uint32Array::from(&colors[..]));
+        let owners = reader
+            .brick_owners()
+            .iter()
+            .map(|o| JsValue::from(user_object(o)))
+            .collect::<Array>();
+        set(&headers, "brickOwners", owners);
+
+        Ok(SaveReader {
+            headers,
+            colors: reader.colors().to_vec(),
+            bricks: Bricks::Pending(Box::new(reader)),
+        })
+    }
+
+    /// The headers of the save.
+    #[wasm_bindgen(getter)]
+    pub fn headers(&self) -> Object {
+        self.headers.clone()
+    }
+
+    /// Read up to `max` more bricks, or all remaining bricks if `max`
+    /// isn't given. Returns no bricks once all have been read.
+    #[wasm_bindgen(js_name = readBricks)]
+    pub fn read_bricks(&mut self, max: Option<u32>) -> Result<Object, JsError> {
+        if let Bricks::Pending(_) = self.bricks {
+            if let Bricks::Pending(pending) = std::mem::replace(&mut self.bricks, Bricks::Done) {
+                self.bricks = Bricks::Reading(pending.iter_bricks().map_err(js_error)?);
+            }
+        }
+
+        let mut bricks = BrickColumns::new();
+        if let Bricks::Reading(iter) = &mut self.bricks {
+            let max = max.map_or(usize::MAX, |max| max as usize);
+            while bricks.len() < max {
+                match iter.next() {
+                    Some(Ok(brick)) => bricks.push(&brick),
+                    Some(Err(error)) => {
+                        self.bricks = Bricks::Done;
+                        return Err(js_error(error));
+                    }
+                    None => {
+                        self.bricks = Bricks::Done;
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(bricks_object(&bricks, &self.colors))
+    }
+}
+
+fn bricks_object(bricks: &BrickColumns, palette: &[Color]) -> Object {
+    let sizes = bricks
+        .sizes
+        .iter()
+        .flat_map(|s| [s.0, s.1, s.2])
+        .collect::<Vec<_>>();
+    let positions = bricks
+        .positions
+        .iter()
+        .flat_map(|p| [p.0, p.1, p.2])
+        .collect::<Vec<_>>();
+    let orientations = bricks
+        .orientations
+        .iter()
+        .map(|o| o.packed())
+        .collect::<Vec<_>>();
+    let flags = bricks
+        .flags
+        .iter()
+        .map(|f| f & (BrickColumns::COLLISION | BrickColumns::VISIBILITY))
+        .collect::<Vec<_>>();
+    let colors = (0..bricks.len()).map(|i| bricks.color(i));
+    let color_indices = colors
+        .clone()
+        .map(|c| match c {
+            ColorMode::Set(index) => index as i32,
+            ColorMode::Custom(_) => -1,
+        })
+        .collect::<Vec<_>>();
+    let colors = colors
+        .map(|c| match c {
+            ColorMode::Set(index) => palette.get(index as usize).map_or(0, |&c| u32::from(c)),
+            ColorMode::Custom(color) => u32::from(color),
+        })
+        .collect::<Vec<_>>();
+    let owner_indices = (0..bricks.len())
+        .map(|i| bricks.owner_index(i).map_or(-1, |i| i as i32))
+        .collect::<Vec<_>>();
+
+    let object = Object::new();
+    set(&object, "count", bricks.len() as u32);
+    set(
+        &object,
+        "assetIndices",
+        Uint32Array::from(&bricks.asset_name_indices[..]),
+    );
+    set(&object, "sizes", Uint32Array::from(&sizes[..]));
+    set(&object, "positions", Int32Array::from(&positions[..]));
+    set(&object, "orientations", Uint8Array::from(&orientations[..]));
+    set(&object, "flags", Uint8Array::from(&flags[..]));
+    set(
+        &object,
+        "materialIndices",
+        Uint32Array::from(&bricks.material_indices[..]),
+    );
+    set(
+        &object,
+        "colorIndices",
+        Int32Array::from(&color_indices[..]),
+    );
+    set(&object, "colors", Uint32Array::from(&colors[..]));
+    set(
+        &object,
+        "ownerIndices",
+        Int32Array::from(&owner_indices[..]),
+    );
+    object
+}
+
+fn get_string(object: &JsValue, key: &str) -> Result<String, JsError> {
+    let value = get(object, key);
+    if value.is_undefined() {
+        return Ok(String::new());
+    }
+    value
+        .as_string()
+        .ok_or_else(|| JsError::new(&format!("`{}` must be a string", key)))
+}
+
+fn get_strings(object: &JsValue, key: &str) -> Result<Vec<String>, JsError> {
+    let value = get(object, key);
+    if value.is_undefined() {
+        return Ok(Vec::new());
+    }
+    Array::from(&value)
+        .iter()
+        .map(|s| {
+            s.as_string()
+                .ok_or_else(|| JsError::new(&format!("`{}` must only contain strings", key)))
+        })
+        .collect()
+}
+
+fn get_user(value: &JsValue, key: &str) -> Result<User, JsError> {
+    if value.is_undefined() || value.is_null() {
+        return Ok(User {
+            id: Uuid::nil(),
+            name: String::new(),
+        });
+    }
+    let id = get_string(value, "id")?;
+    Ok(User {
+        id: if id.is_empty() {
+            Uuid::nil()
+        } else {
+            Uuid::parse_str(&id)
+                .map_err(|_| JsError::new(&format!("`{}.id` must be a UUID", key)))?
+        },
+        name: get_string(value, "name")?,
+    })
+}
+
+/// The typed array at `key`, which must have `len` entries. Returns `None`
+/// if it's missing.
+fn get_array<T>(
+    object: &JsValue,
+    key: &str,
+    len: usize,
+    to_vec: impl FnOnce(&JsValue) -> Vec<T>,
+) -> Result<Option<Vec<T>>, JsError> {
+    let value = get(object, key);
+    if value.is_undefined() {
+        return Ok(None);
+    }
+    let values = to_vec(&value);
+    if values.len() != len {
+        return Err(JsError::new(&format!(
+            "`bricks.{}` must have {} entries",
+            key, len
+        )));
+    }
+    Ok(Some(values))
+}
+
+fn get_bricks(object: &JsValue) -> Result<Vec<Brick>, JsError> {
+    if object.is_undefined() {
+        return Ok(Vec::new());
+    }
+    let u32s = |v: &JsValue| Uint32Array::new(v).to_vec();
+    let i32s = |v: &JsValue| Int32Array::new(v).to_vec();
+    let u8s = |v: &JsValue| Uint8Array::new(v).to_vec();
+
+    let missing = |key: &str| JsError::new(&format!("`bricks.{}` is missing", key));
+    let assets = get(object, "assetIndices");
+    if assets.is_undefined() {
+        return Err(missing("assetIndices"));
+    }
+    let assets = u32s(&assets);
+    let n = assets.len();
+
+    let sizes = get_array(object, "sizes", n * 3, u32s)?.ok_or_else(|| missing("sizes"))?;
+    let positions =
+        get_array(object, "positions", n * 3, i32s)?.ok_or_else(|| missing("positions"))?;
+    let orientations = get_array(object, "orientations", n, u8s)?;
+    let flags = get_array(object, "flags", n, u8s)?;
+    let materials = get_array(object, "materialIndices", n, u32s)?;
+    let color_indices = get_array(object, "colorIndices", n, i32s)?;
+    let colors = get_array(object, "colors", n, u32s)?;
+    let owners = get_array(object, "ownerIndices", n, i32s)?;
+
+    (0..n)
+        .map(|i| {
+            let color = match (color_indices.as_ref().map(|c| c[i]), &colors) {
+                (Some(index), _) if index >= 0 => ColorMode::Set(index as u32),
+                (_, Some(colors)) => ColorMode::Custom(Color::from(colors[i])),
+                (Some(_), None) => return Err(missing("colors")),
+                (None, None) => ColorMode::Set(0),
+            };
+            let orientation = match &orientations {
+                Some(o) => Orientation::try_from_packed(o[i]).ok_or_else(|| {
+                    JsError::new("`bricks.orientations` must only contain values below 24")
+                })?,
+                None => Orientation::default(),
+            };
+            let flags = flags.as_ref().map_or(3, |f| f[i]);
+            Ok(Brick {
+                asset_name_index: assets[i],
+                size: (sizes[i * 3], sizes[i * 3 + 1], sizes[i * 3 + 2]),
+                position: (positions[i * 3], positions[i * 3 + 1], positions[i * 3 + 2]),
+                direction: orientation.direction,
+                rotation: orientation.rotation,
+                collision: flags & 1 != 0,
+                visibility: flags & 2 != 0,
+                material_index: materials.as_ref().map_or(0, |m| m[i]),
+                color,
+                owner_index: owners.as_ref().and_then(|o| {
+                    if o[i] >= 0 {
+                        Some(o[i] as u32)
+                    } else {
+                        None
+                    }
+                }),
+            })
+        })
+        .collect()
+}
+
+/// Write a save from `data`, an object with the same shape as
+/// `SaveReader.headers` with its bricks in `bricks`. Only `bricks.assetIndices`,
+/// `bricks.sizes` and `bricks.positions` are required, and bricks without
+/// `bricks.colorIndices` use `bricks.colors` as custom colors. Missing strings and
+/// tables are left empty. `saveTime` can be anything the `Date` constructor
+/// accepts, and defaults to the current time.
+///
+/// Missing `bricks.materialIndices`, and colors when both `bricks.colorIndices`
+/// and `bricks.colors` are missing, default to index 0, so `materials` and
+/// `colors` need at least one entry then. Every index must refer to an entry
+/// of its table, and the error names the first brick that doesn't.
+#[wasm_bindgen(js_name = writeSave)]
+pub fn write_save(data: &JsValue) -> Result<Uint8Array, JsError> {
+    let save_time = get(data, "saveTime");
+    let save_time = if save_time.is_undefined() || save_time.is_null() {
+        Utc::now()
+    } else {
+        let millis = Date::new(&save_time).get_time();
+        if !millis.is_finite() {
+            return Err(JsError::new("`saveTime` must be a valid date"));
+        }
+        Utc.timestamp_millis_opt(millis as i64)
+            .single()
+            .ok_or_else(|| JsError::new("`saveTime` is out of range"))?
+    };
+    let colors = get(data, "colors");
+    let colors = if colors.is_undefined() {
+        Vec::new()
+    } else {
+        Uint32Array::new(&colors).to_vec()
+    };
+    let owners = get(data, "brickOwners");
+    let owners = if owners.is_undefined() {
+        Vec::new()
+    } else {
+        Array::from(&owners)
+            .iter()
+            .map(|o| get_user(&o, "brickOwners[]"))
+            .collect::<Result<_, _>>()?
+    };
+
+    let data = WriteData {
+        map: get_string(data, "map")?,
+        author: get_user(&get(data, "author"), "author")?,
+        description: get_string(data, "description")?,
+        save_time,
+        mods: get_strings(data, "mods")?,
+        brick_assets: get_strings(data, "brickAssets")?,
+        colors: colors.into_iter().map(Color::from).collect(),
+        materials: get_strings(data, "materials")?,
+        brick_owners: owners,
+        bricks: get_bricks(&get(data, "bricks"))?,
+    };
+
+    data.check_indices().map_err(js_error)?;
+
+    let mut bytes = Vec::new();
+    crate::write_save(&mut bytes, &data).map_err(js_error)?;
+    Ok(Uint8Array::from(&bytes[..]))
+}
@@ -1,7 +1,7 @@
 use crate::{
     bit_writer::BitWriter,
-    save::{Brick, Color, ColorMode, Direction, Rotation, User},
-    ue4_date_time_base, MAGIC,
+    save::{Brick, Color, ColorMode, User},
+    ue4_date_time_base, BrickColumns, Orientation, MAGIC,
 };
 use byteorder::{BigEndian, ByteOrder, LittleEndian, WriteBytesExt};
 use chrono::prelude::*;
@@ -15,6 +15,8 @@ use uuid::Uuid;
 const LATEST_VERSION: u16 = 4;
 
 /// Data written to save files by [`write_save`](fn.write_save.html).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WriteData {
     // Header 1
     /// The name of the map that the save file was created on.
@@ -49,9 +51,161 @@ pub struct WriteData {
     pub bricks: Vec<Brick>,
 }
 
+impl WriteData {
+    /// Check that all bricks only refer to entries in the lookup tables.
+    /// Returns an `InvalidData` error naming the first brick that doesn't.
+    ///
+    /// ```
+    /// # use brs::{Brick, ColorMode, Direction, Rotation};
+    /// let mut data = brs::WriteData {
+    ///     map: "Plate".to_string(),
+    ///     author: brs::User {
+    ///         id: brs::uuid::Uuid::nil(),
+    ///         name: "Jensen".to_string(),
+    ///     },
+    ///     description: String::new(),
+    ///     save_time: chrono::Utc::now(),
+    ///     mods: Vec::new(),
+    ///     brick_assets: vec!["PB_DefaultBrick".to_string()],
+    ///     colors: Vec::new(),
+    ///     materials: vec!["BMC_Plastic".to_string()],
+    ///     brick_owners: Vec::new(),
+    ///     bricks: Vec::new(),
+    /// };
+    /// data.bricks.push(Brick {
+    ///     asset_name_index: 0,
+    ///     size: (5, 5, 6),
+    ///     position: (0, 0, 6),
+    ///     direction: Direction::ZPositive,
+    ///     rotation: Rotation::Deg0,
+    ///     collision: true,
+    ///     visibility: true,
+    ///     material_index: 0,
+    ///     color: ColorMode::Set(0),
+    ///     owner_index: None,
+    /// });
+    /// let error = data.check_indices().unwrap_err();
+    /// assert_eq!(error.to_string(), "Brick 0 color index 0 out of range (0 entries)");
+    /// ```
+    pub fn check_indices(&self) -> io::Result<()> {
+        for (i, brick) in self.bricks.iter().enumerate() {
+            let check = |what: &str, index: u32, len: usize| {
+                if index as usize >= len {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Brick {} {} index {} out of range ({} entries)",
+                            i, what, index, len
+                        ),
+                    ));
+                }
+                Ok(())
+            };
+            check("asset", brick.asset_name_index, self.brick_assets.len())?;
+            check("material", brick.material_index, self.materials.len())?;
+            if let ColorMode::Set(c) = brick.color {
+                check("color", c, self.colors.len())?;
+            }
+            if let Some(owner) = brick.owner_index {
+                check("owner", owner, self.brick_owners.len())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The fields of a brick as the writer needs them, so that bricks can be
+/// written from either `Brick`s or `BrickColumns` without converting.
+pub(crate) trait BrickFields {
+    fn asset_name_index(&self) -> u32;
+    fn size(&self) -> (u32, u32, u32);
+    fn position(&self) -> (i32, i32, i32);
+    fn orientation(&self) -> Orientation;
+    fn collision(&self) -> bool;
+    fn visibility(&self) -> bool;
+    fn material_index(&self) -> u32;
+    fn color(&self) -> ColorMode;
+    fn owner_index(&self) -> Option<u32>;
+}
+
+impl BrickFields for &Brick {
+    fn asset_name_index(&self) -> u32 {
+        self.asset_name_index
+    }
+
+    fn size(&self) -> (u32, u32, u32) {
+        self.size
+    }
+
+    fn position(&self) -> (i32, i32, i32) {
+        self.position
+    }
+
+    fn orientation(&self) -> Orientation {
+        Brick::orientation(self)
+    }
+
+    fn collision(&self) -> bool {
+        self.collision
+    }
+
+    fn visibility(&self) -> bool {
+        self.visibility
+    }
+
+    fn material_index(&self) -> u32 {
+        self.material_index
+    }
+
+    fn color(&self) -> ColorMode {
+        self.color
+    }
+
+    fn owner_index(&self) -> Option<u32> {
+        self.owner_index
+    }
+}
+
 /// Write a save file consisting of `data` to `w`.
 pub fn write_save(w: &mut impl Write, data: &WriteData) -> io::Result<()> {
-    if data.bricks.len() > i32::max_value() as usize {
+    write_save_bricks(w, data, data.bricks.len(), data.bricks.iter())
+}
+
+/// Write a save file consisting of `data` to `w`, with the bricks in
+/// `bricks` instead of `data.bricks`, which is ignored.
+///
+/// ```no_run
+/// # use std::fs::File;
+/// # let reader: brs::read::ReaderAfterHeader2<File> = unimplemented!();
+/// let mut data = reader.into_write_data()?;
+/// let mut columns = brs::BrickColumns::from(std::mem::take(&mut data.bricks));
+/// for position in &mut columns.positions {
+///     position.2 += 100;
+/// }
+/// brs::write_save_columns(&mut File::create("raised.brs")?, &data, &columns)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn write_save_columns(
+    w: &mut impl Write,
+    data: &WriteData,
+    bricks: &BrickColumns,
+) -> io::Result<()> {
+    bricks.check_lengths()?;
+    write_save_bricks(
+        w,
+        data,
+        bricks.len(),
+        (0..bricks.len()).map(|i| bricks.row(i)),
+    )
+}
+
+fn write_save_bricks<B: BrickFields>(
+    w: &mut impl Write,
+    data: &WriteData,
+    brick_count: usize,
+    bricks: impl Iterator<Item = B>,
+) -> io::Result<()> {
+    if brick_count > i32::MAX as usize {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
             "Brick count out of range",
@@ -67,7 +221,7 @@ pub fn write_save(w: &mut impl Write, data: &WriteData) -> io::Result<()> {
     write_string(&mut s, &data.description)?;
     write_uuid(&mut s, &data.author.id)?;
     write_date_time(&mut s, data.save_time)?;
-    s.write_i32::<LittleEndian>(data.bricks.len() as i32)?;
+    s.write_i32::<LittleEndian>(brick_count as i32)?;
     s.finish(w)?;
 
     let mut s = Compressed::new();
@@ -89,28 +243,29 @@ pub fn write_save(w: &mut impl Write, data: &WriteData) -> io::Result<()> {
     )?;
     s.finish(w)?;
 
-    assert!(data.brick_assets.len() <= u32::max_value() as usize);
-    assert!(data.colors.len() <= u32::max_value() as usize);
+    assert!(data.brick_assets.len() <= u32::MAX as usize);
+    assert!(data.colors.len() <= u32::MAX as usize);
 
     let mut s = BitWriter::new(Compressed::new());
-    for brick in &data.bricks {
+    for brick in bricks {
         s.byte_align()?;
         s.write_int(
-            brick.asset_name_index,
+            brick.asset_name_index(),
             data.brick_assets.len().max(2) as u32,
         )?;
-        if s.write_bit(brick.size != (0, 0, 0))? {
-            s.write_positive_int_vector_packed(brick.size)?;
+        let size = brick.size();
+        if s.write_bit(size != (0, 0, 0))? {
+            s.write_positive_int_vector_packed(size)?;
         }
-        s.write_int_vector_packed(brick.position)?;
-        let orientation = combine_orientation(brick.direction, brick.rotation);
-        s.write_int(u32::from(orientation), 24)?;
-        s.write_bit(brick.collision)?;
-        s.write_bit(brick.visibility)?;
-        if s.write_bit(brick.material_index != 1)? {
-            s.write_int_packed(brick.material_index)?;
+        s.write_int_vector_packed(brick.position())?;
+        s.write_int(u32::from(brick.orientation().packed()), 24)?;
+        s.write_bit(brick.collision())?;
+        s.write_bit(brick.visibility())?;
+        let material_index = brick.material_index();
+        if s.write_bit(material_index != 1)? {
+            s.write_int_packed(material_index)?;
         }
-        match brick.color {
+        match brick.color() {
             ColorMode::Set(i) => {
                 s.write_bit(false)?;
                 s.write_int(i, data.colors.len() as u32)?;
@@ -121,7 +276,7 @@ pub fn write_save(w: &mut impl Write, data: &WriteData) -> io::Result<()> {
             }
         }
 
-        s.write_int_packed(match brick.owner_index {
+        s.write_int_packed(match brick.owner_index() {
             None => 0,
             Some(i) => i + 1,
         })?;
@@ -151,14 +306,14 @@ impl Compressed {
         let uncompressed_size = self.uncompressed.len();
         let compressed_size = compressed.len();
 
-        if uncompressed_size >= i32::max_value() as usize {
+        if uncompressed_size >= i32::MAX as usize {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 "uncompressed_size out of range",
             ));
         }
 
-        if compressed_size >= i32::max_value() as usize {
+        if compressed_size >= i32::MAX as usize {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 "compressed_size out of range",
@@ -197,7 +352,7 @@ fn write_array<T, W: Write>(
     write: impl Fn(&mut W, &T) -> io::Result<()>,
     values: &[T],
 ) -> io::Result<()> {
-    if values.len() > i32::max_value() as usize {
+    if values.len() > i32::MAX as usize {
         return Err(io::Error::from(io::ErrorKind::Other));
     }
 
@@ -220,13 +375,13 @@ fn write_string(w: &mut impl Write, s: impl AsRef<str>) -> io::Result<()> {
 
     if s.is_ascii() {
         let len = s.len() + 1;
-        assert!(len <= i32::max_value() as usize);
+        assert!(len <= i32::MAX as usize);
         w.write_i32::<LittleEndian>(len as i32)?;
         w.write_all(s.as_bytes())?;
         w.write_u8(0)?;
     } else {
         let len = -(((s.len() + 1) * 2) as isize);
-        assert!(len >= i32::min_value() as isize);
+        assert!(len >= i32::MIN as isize);
         w.write_i32::<LittleEndian>(len as i32)?;
 
         for character in s.chars() {
@@ -264,8 +419,3 @@ fn write_date_time(w: &mut impl Write, date_time: DateTime<Utc>) -> io::Result<(
     let ticks_nanos = i64::from(duration.subsec_nanos() / 100);
     w.write_i64::<LittleEndian>(ticks_secs + ticks_nanos)
 }
-
-/// Combines a direction and rotation into their corresponding packed orientation.
-fn combine_orientation(direction: Direction, rotation: Rotation) -> u8 {
-    (u8::from(direction) << 2) | u8::from(rotation)
-}
@@ -0,0 +1,139 @@
+#![cfg(feature = "arrow")]
+
+mod common;
+
+use arrow_array::{
+    cast::AsArray,
+    types::{Int32Type, UInt32Type, UInt8Type},
+    Array, RecordBatch,
+};
+use arrow_schema::DataType;
+use brs::{arrow, Color, ColorMode, Direction, Rotation, WriteData};
+use bytes::Bytes;
+use common::{brick, save};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+fn data() -> WriteData {
+    let mut red = brick((5, 5, 6));
+    red.color = ColorMode::Set(1);
+
+    let mut custom = brick((15, 5, 6));
+    custom.asset_name_index = 2;
+    custom.direction = Direction::XPositive;
+    custom.rotation = Rotation::Deg90;
+    custom.collision = false;
+    custom.material_index = 1;
+    custom.color = ColorMode::Custom(Color::from_rgba(1, 2, 3, 255));
+    custom.owner_index = Some(1);
+
+    let mut owned = brick((25, 5, 6));
+    owned.owner_index = Some(0);
+
+    save(vec![red, custom, owned])
+}
+
+/// The keys and values of the dictionary column `name`.
+fn dictionary(batch: &RecordBatch, name: &str) -> (Vec<Option<u32>>, Vec<String>) {
+    let column = batch
+        .column_by_name(name)
+        .unwrap()
+        .as_dictionary::<UInt32Type>();
+    let values = column.values().as_string::<i32>();
+    (
+        column.keys().iter().collect(),
+        values.iter().map(|v| v.unwrap().to_string()).collect(),
+    )
+}
+
+/// The values of the dictionary column `name`, looked up by key.
+fn strings(batch: &RecordBatch, name: &str) -> Vec<Option<String>> {
+    let (keys, values) = dictionary(batch, name);
+    keys.into_iter()
+        .map(|key| key.map(|k| values[k as usize].clone()))
+        .collect()
+}
+
+fn u32s(batch: &RecordBatch, name: &str) -> Vec<Option<u32>> {
+    let column = batch.column_by_name(name).unwrap();
+    column.as_primitive::<UInt32Type>().iter().collect()
+}
+
+#[test]
+fn schema_and_columns() {
+    let data = data();
+    let batch = arrow::from_write_data(&data).unwrap();
+    assert_eq!(batch.schema(), arrow::schema());
+    assert_eq!(batch.num_rows(), 3);
+
+    let (keys, values) = dictionary(&batch, "asset");
+    assert_eq!(keys, [Some(0), Some(2), Some(0)]);
+    assert_eq!(values, data.brick_assets);
+    let (keys, values) = dictionary(&batch, "material");
+    assert_eq!(keys, [Some(0), Some(1), Some(0)]);
+    assert_eq!(values, data.materials);
+
+    let x = batch
+        .column_by_name("x")
+        .unwrap()
+        .as_primitive::<Int32Type>();
+    assert_eq!(x.values().to_vec(), [5, 15, 25]);
+    let direction = batch.column_by_name("direction").unwrap();
+    let direction = direction.as_primitive::<UInt8Type>().values().to_vec();
+    assert_eq!(direction, [4, 0, 4]);
+    let collision = batch.column_by_name("collision").unwrap().as_boolean();
+    assert_eq!(
+        collision.iter().collect::<Vec<_>>(),
+        [Some(true), Some(false), Some(true)]
+    );
+
+    assert_eq!(
+        u32s(&batch, "color"),
+        [Some(0xffff0000), Some(0xff010203), Some(0xffffffff)]
+    );
+    assert_eq!(u32s(&batch, "color_index"), [Some(1), None, Some(0)]);
+
+    let (keys, values) = dictionary(&batch, "owner");
+    assert_eq!(keys, [None, Some(1), Some(0)]);
+    assert_eq!(values, ["Alice", "Bob"]);
+    let (keys, values) = dictionary(&batch, "owner_id");
+    assert_eq!(keys, [None, Some(1), Some(0)]);
+    assert_eq!(
+        values[1],
+        data.brick_owners[1].id.to_hyphenated().to_string()
+    );
+    assert_eq!(batch.column_by_name("owner").unwrap().null_count(), 1);
+}
+
+#[test]
+fn rejects_out_of_range_indices() {
+    let mut data = data();
+    data.bricks[0].color = ColorMode::Set(2);
+    assert!(arrow::from_write_data(&data).is_err());
+}
+
+#[test]
+fn parquet_round_trip() {
+    let batch = arrow::from_write_data(&data()).unwrap();
+    let mut bytes = Vec::new();
+    arrow::write_parquet(&mut bytes, &[batch.clone(), batch.clone()]).unwrap();
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(bytes))
+        .unwrap()
+        .with_batch_size(3)
+        .build()
+        .unwrap();
+    let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+    let rows = batches.iter().map(|b| b.num_rows()).sum::<usize>();
+    assert_eq!(rows, 6);
+    for read in batches {
+        assert_eq!(read.schema(), arrow::schema());
+        // Dictionaries may be encoded differently, but hold the same strings.
+        for (i, field) in arrow::schema().fields().iter().enumerate() {
+            if let DataType::Dictionary(..) = field.data_type() {
+                assert_eq!(strings(&read, field.name()), strings(&batch, field.name()));
+            } else {
+                assert_eq!(&read.column(i), &batch.column(i), "{}", field.name());
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! Helpers for building small saves in tests.
+
+#![allow(dead_code)]
+
+use brs::{uuid::Uuid, Brick, Color, ColorMode, Direction, Rotation, User, WriteData};
+use chrono::prelude::*;
+
+/// A user with an id derived from `name`.
+pub fn user(name: &str) -> User {
+    let id = name
+        .bytes()
+        .fold(0u128, |h, b| h.wrapping_mul(31).wrapping_add(u128::from(b)));
+    User {
+        id: Uuid::from_u128(id),
+        name: name.to_string(),
+    }
+}
+
+/// A save with a few entries in each lookup table and `bricks`.
+pub fn save(bricks: Vec<Brick>) -> WriteData {
+    WriteData {
+        map: "Plate".to_string(),
+        author: user("Jensen"),
+        description: "A test save.".to_string(),
+        save_time: Utc.timestamp_opt(1_600_000_000, 0).unwrap(),
+        mods: Vec::new(),
+        brick_assets: vec![
+            "PB_DefaultBrick".to_string(),
+            "PB_DefaultTile".to_string(),
+            "PB_DefaultRamp".to_string(),
+        ],
+        colors: vec![
+            Color::from_rgba(255, 255, 255, 255),
+            Color::from_rgba(255, 0, 0, 255),
+        ],
+        materials: vec!["BMC_Plastic".to_string(), "BMC_Glow".to_string()],
+        brick_owners: vec![user("Alice"), user("Bob")],
+        bricks,
+    }
+}
+
+/// A 1x1 brick of the first asset, material and color, centered at `position`.
+pub fn brick(position: (i32, i32, i32)) -> Brick {
+    Brick {
+        asset_name_index: 0,
+        size: (5, 5, 6),
+        position,
+        direction: Direction::ZPositive,
+        rotation: Rotation::Deg0,
+        collision: true,
+        visibility: true,
+        material_index: 0,
+        color: ColorMode::Set(0),
+        owner_index: None,
+    }
+}
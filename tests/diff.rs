@@ -0,0 +1,154 @@
+mod common;
+
+use brs::{
+    diff::{BrickChange, BrickField, HeaderField},
+    Brick, Color, ColorMode,
+};
+use common::{brick, save, user};
+
+#[test]
+fn identical_saves() {
+    let data = save(vec![brick((5, 5, 6)), brick((15, 5, 6))]);
+    assert!(brs::diff(&data, &data).is_empty());
+}
+
+#[test]
+fn header_fields() {
+    let old = save(Vec::new());
+    let mut new = old.clone();
+    new.map = "Studio".to_string();
+    new.author = user("Someone else");
+    new.description.push('!');
+    new.save_time += chrono::Duration::seconds(1);
+    let diff = brs::diff(&old, &new);
+    assert_eq!(
+        diff.header,
+        [
+            HeaderField::Map,
+            HeaderField::Author,
+            HeaderField::Description,
+            HeaderField::SaveTime
+        ]
+    );
+    assert!(diff.tables_unchanged());
+    assert!(diff.bricks_unchanged());
+}
+
+#[test]
+fn table_changes() {
+    let old = save(Vec::new());
+    let mut new = old.clone();
+    new.materials.remove(1);
+    new.colors.push(Color::from_rgba(0, 0, 255, 255));
+    new.brick_owners.reverse();
+    let diff = brs::diff(&old, &new);
+    assert_eq!(diff.materials.added, Vec::<String>::new());
+    assert_eq!(diff.materials.removed, ["BMC_Glow"]);
+    assert_eq!(diff.colors.added, [Color::from_rgba(0, 0, 255, 255)]);
+    assert!(diff.colors.removed.is_empty());
+    // Reordering a table isn't a change.
+    assert!(diff.brick_owners.is_empty());
+    assert!(diff.brick_assets.is_empty());
+}
+
+#[test]
+fn added_and_removed_bricks() {
+    let old = save(vec![brick((5, 5, 6)), brick((15, 5, 6))]);
+    let new = save(vec![
+        brick((15, 5, 6)),
+        brick((25, 5, 6)),
+        brick((35, 5, 6)),
+    ]);
+    let diff = brs::diff(&old, &new);
+    assert_eq!(diff.removed, [0]);
+    assert_eq!(diff.added, [1, 2]);
+    assert!(diff.modified.is_empty());
+}
+
+#[test]
+fn bricks_differing_in_key_are_replaced() {
+    let old = save(vec![brick((5, 5, 6))]);
+    let mut new = old.clone();
+    new.bricks[0].asset_name_index = 1;
+    let diff = brs::diff(&old, &new);
+    assert_eq!((diff.added, diff.removed), (vec![0], vec![0]));
+}
+
+#[test]
+fn modified_fields() {
+    let base = brick((5, 5, 6));
+    type Change = fn(&mut Brick);
+    let changes: [(BrickField, Change); 6] = [
+        (BrickField::Size, |b| b.size = (5, 5, 2)),
+        (BrickField::Collision, |b| b.collision = false),
+        (BrickField::Visibility, |b| b.visibility = false),
+        (BrickField::Material, |b| b.material_index = 1),
+        (BrickField::Color, |b| b.color = ColorMode::Set(1)),
+        (BrickField::Owner, |b| b.owner_index = Some(0)),
+    ];
+    for (field, change) in changes {
+        let old = save(vec![base.clone()]);
+        let mut new = old.clone();
+        change(&mut new.bricks[0]);
+        let diff = brs::diff(&old, &new);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert_eq!(
+            diff.modified,
+            [BrickChange {
+                old: 0,
+                new: 0,
+                fields: vec![field],
+            }],
+        );
+    }
+}
+
+#[test]
+fn fields_are_compared_by_value() {
+    // The same color as a table entry and as a custom color,
+    // with the tables of the new save reordered.
+    let old = save(vec![brick((5, 5, 6))]);
+    let mut new = old.clone();
+    new.colors.reverse();
+    new.bricks[0].color = ColorMode::Set(1);
+    assert!(brs::diff(&old, &new).bricks_unchanged());
+    new.bricks[0].color = ColorMode::Custom(Color::from_rgba(255, 255, 255, 255));
+    assert!(brs::diff(&old, &new).bricks_unchanged());
+}
+
+#[test]
+fn duplicate_bricks_are_matched_to_identical_ones() {
+    let mut red = brick((5, 5, 6));
+    red.color = ColorMode::Set(1);
+    let white = brick((5, 5, 6));
+
+    // Reordered bricks sharing a position aren't changes.
+    let old = save(vec![red.clone(), white.clone(), white.clone()]);
+    let new = save(vec![white.clone(), white.clone(), red.clone()]);
+    assert!(brs::diff(&old, &new).bricks_unchanged());
+
+    // Without an identical brick, the first unmatched one is used.
+    let mut glow = white.clone();
+    glow.material_index = 1;
+    let new = save(vec![glow, white.clone(), red]);
+    let diff = brs::diff(&old, &new);
+    assert!(diff.added.is_empty() && diff.removed.is_empty());
+    assert_eq!(
+        diff.modified,
+        [BrickChange {
+            old: 2,
+            new: 0,
+            fields: vec![BrickField::Material],
+        }],
+    );
+
+    // Extra duplicates are added or removed.
+    let new = save(vec![white.clone(); 4]);
+    let diff = brs::diff(&old, &new);
+    assert_eq!(diff.removed, Vec::<usize>::new());
+    assert_eq!(diff.added, [3]);
+    assert_eq!(diff.modified.len(), 1);
+    let new = save(vec![white]);
+    let diff = brs::diff(&old, &new);
+    assert_eq!(diff.removed, [0, 2]);
+}
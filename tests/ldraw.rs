@@ -0,0 +1,48 @@
+use std::io;
+
+use brs::ldraw::{self, LdrawOptions};
+
+const HOUSE: &[u8] = b"0 FILE house.ldr\n\
+    1 4 0 0 0 1 0 0 0 1 0 0 0 1 wall.ldr\n\
+    1 16 0 -24 0 0 0 1 0 1 0 -1 0 0 wall.ldr\n\
+    0 FILE wall.ldr\n\
+    1 16 0 0 0 1 0 0 0 1 0 0 0 1 3001.dat\n";
+
+#[test]
+fn counts_parts_in_submodels() {
+    // Two walls and a brick in each.
+    let options = LdrawOptions {
+        max_parts: 4,
+        ..Default::default()
+    };
+    let import = ldraw::read(HOUSE, &options).unwrap();
+    assert_eq!(import.data.bricks.len(), 2);
+
+    let options = LdrawOptions {
+        max_parts: 3,
+        ..Default::default()
+    };
+    let err = ldraw::read(HOUSE, &options).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn rejects_shared_submodel_fan_out() {
+    // Each submodel uses the next one twice, so the last one is placed
+    // 2^60 times, well within the nesting limit.
+    let mut model = String::new();
+    for level in 0..60 {
+        model += &format!("0 FILE {}.ldr\n", level);
+        for _ in 0..2 {
+            model += &format!("1 16 0 0 0 1 0 0 0 1 0 0 0 1 {}.ldr\n", level + 1);
+        }
+    }
+    model += "0 FILE 60.ldr\n1 16 0 0 0 1 0 0 0 1 0 0 0 1 3001.dat\n";
+
+    let options = LdrawOptions {
+        max_parts: 1 << 16,
+        ..Default::default()
+    };
+    let err = ldraw::read(model.as_bytes(), &options).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use brs::{
+    optimize::{self, MergeOptions, MAX_BRICK_SIZE},
+    Brick, ColorMode,
+};
+use common::{brick, save};
+
+type Change = fn(&mut Brick);
+
+/// The unit brick at grid cell `(x, y, z)`.
+fn cell(x: i32, y: i32, z: i32) -> Brick {
+    brick((5 + 10 * x, 5 + 10 * y, 6 + 12 * z))
+}
+
+#[test]
+fn merges_a_block_into_one_brick() {
+    let mut bricks = Vec::new();
+    for z in 0..2 {
+        for y in 0..2 {
+            for x in 0..2 {
+                bricks.push(cell(x, y, z));
+            }
+        }
+    }
+    let mut data = save(bricks);
+    let report = optimize::merge_bricks(&mut data, &MergeOptions::default());
+    assert_eq!((report.before, report.after, report.removed()), (8, 1, 7));
+    assert_eq!(data.bricks[0].size, (10, 10, 12));
+    assert_eq!(data.bricks[0].position, (10, 10, 12));
+}
+
+#[test]
+fn keeps_different_bricks_apart() {
+    let changes: &[Change] = &[
+        |b| b.color = ColorMode::Set(1),
+        |b| b.material_index = 1,
+        |b| b.owner_index = Some(1),
+        |b| b.collision = false,
+        |b| b.visibility = false,
+    ];
+    for change in changes {
+        let mut other = cell(1, 0, 0);
+        change(&mut other);
+        let mut data = save(vec![cell(0, 0, 0), other]);
+        let report = optimize::merge_bricks(&mut data, &MergeOptions::default());
+        assert_eq!(report.after, 2);
+    }
+}
+
+#[test]
+fn keeps_non_box_assets() {
+    // Ramps aren't box-shaped, and unknown assets could be anything.
+    let mut data = save(vec![cell(0, 0, 0), cell(1, 0, 0)]);
+    data.brick_assets[0] = "PB_DefaultRamp".to_string();
+    let report = optimize::merge_bricks(&mut data, &MergeOptions::default());
+    assert_eq!(report.after, 2);
+
+    data.brick_assets[0] = "B_Unknown".to_string();
+    let report = optimize::merge_bricks(&mut data, &MergeOptions::default());
+    assert_eq!(report.after, 2);
+}
+
+#[test]
+fn caps_merged_size() {
+    let mut data = save((0..10).map(|x| cell(x, 0, 0)).collect());
+    let options = MergeOptions {
+        max_size: 20,
+        ..Default::default()
+    };
+    let report = optimize::merge_bricks(&mut data, &options);
+    assert_eq!(report.after, 3);
+    let sizes = data.bricks.iter().map(|b| b.size.0).collect::<Vec<_>>();
+    assert_eq!(sizes, [20, 20, 10]);
+
+    let count = 2 * MAX_BRICK_SIZE as i32 / 10 + 1;
+    let mut data = save((0..count).map(|x| cell(x, 0, 0)).collect());
+    optimize::merge_bricks(&mut data, &MergeOptions::default());
+    assert_eq!(data.bricks.len(), 2);
+    assert!(data.bricks.iter().all(|b| b.size.0 <= MAX_BRICK_SIZE));
+}
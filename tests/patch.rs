@@ -0,0 +1,124 @@
+mod common;
+
+use brs::{patch::Patch, Brick, Color, ColorMode, WriteData};
+use common::{brick, save, user};
+
+/// Check that the patch from `old` to `new` turns `old` into `new`.
+fn assert_round_trip(old: &WriteData, new: &WriteData) {
+    let patch = Patch::new(old, new).unwrap();
+    assert_eq!(patch.is_empty(), brs::diff(old, new).is_empty());
+    let mut patched = old.clone();
+    patch.apply(&mut patched).unwrap();
+    let diff = brs::diff(&patched, new);
+    assert!(diff.is_empty(), "{:?}", diff);
+    assert_eq!(patched.bricks.len(), new.bricks.len());
+    patched.check_indices().unwrap();
+}
+
+#[test]
+fn empty_patch() {
+    let data = save(vec![brick((5, 5, 6))]);
+    let patch = Patch::new(&data, &data).unwrap();
+    assert!(patch.is_empty());
+    let mut patched = data.clone();
+    patch.apply(&mut patched).unwrap();
+    assert_eq!(patched.bricks, data.bricks);
+}
+
+#[test]
+fn header_and_brick_changes() {
+    let mut red = brick((15, 5, 6));
+    red.color = ColorMode::Set(1);
+    let old = save(vec![brick((5, 5, 6)), red.clone(), brick((25, 5, 6))]);
+
+    let mut new = old.clone();
+    new.description = "Changed".to_string();
+    new.author = user("Someone else");
+    // Remove the first brick, modify the second and add two more.
+    new.bricks.remove(0);
+    new.bricks[0].material_index = 1;
+    new.bricks[0].owner_index = Some(1);
+    new.bricks[0].size = (5, 5, 2);
+    new.bricks.push(red);
+    let mut custom = brick((45, 5, 6));
+    custom.color = ColorMode::Custom(Color::from_rgba(1, 2, 3, 4));
+    new.bricks.push(custom);
+
+    assert_round_trip(&old, &new);
+}
+
+#[test]
+fn changed_tables() {
+    let old = save(vec![brick((5, 5, 6)), brick((15, 5, 6))]);
+    let mut new = old.clone();
+    new.colors.insert(0, Color::from_rgba(0, 0, 255, 255));
+    for brick in &mut new.bricks {
+        if let ColorMode::Set(i) = &mut brick.color {
+            *i += 1;
+        }
+    }
+    new.brick_assets.push("PB_DefaultMicroBrick".to_string());
+    new.bricks.push(Brick {
+        asset_name_index: 3,
+        color: ColorMode::Set(0),
+        ..brick((25, 5, 6))
+    });
+    new.brick_owners.push(user("Carol"));
+    new.bricks[1].owner_index = Some(2);
+
+    assert_round_trip(&old, &new);
+    let patch = Patch::new(&old, &new).unwrap();
+    let mut patched = old.clone();
+    patch.apply(&mut patched).unwrap();
+    assert_eq!(patched.colors, new.colors);
+    assert_eq!(patched.brick_assets, new.brick_assets);
+}
+
+#[test]
+fn apply_to_wrong_save_fails() {
+    let old = save(vec![brick((5, 5, 6))]);
+    let new = save(Vec::new());
+    let patch = Patch::new(&old, &new).unwrap();
+    let mut other = save(vec![brick((15, 5, 6))]);
+    let before = other.bricks.clone();
+    assert!(patch.apply(&mut other).is_err());
+    assert_eq!(other.bricks, before);
+}
+
+#[test]
+fn random_round_trips() {
+    let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+    let mut random = |n: u32| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % u64::from(n)) as u32
+    };
+
+    for _ in 0..200 {
+        let mut saves = Vec::new();
+        for _ in 0..2 {
+            let mut data = save(Vec::new());
+            if random(2) == 0 {
+                data.colors.reverse();
+                data.materials.reverse();
+            }
+            for _ in 0..random(40) {
+                // Few distinct positions, so that many bricks share a key.
+                let mut b = brick((random(3) as i32 * 10, 0, 6));
+                b.asset_name_index = random(2);
+                b.size.2 = 2 + 4 * random(2);
+                b.collision = random(2) == 0;
+                b.material_index = random(2);
+                b.color = match random(3) {
+                    2 => ColorMode::Custom(Color::from_rgba(0, 0, 255, 255)),
+                    i => ColorMode::Set(i),
+                };
+                b.owner_index = [None, Some(0), Some(1)][random(3) as usize];
+                data.bricks.push(b);
+            }
+            saves.push(data);
+        }
+        assert_round_trip(&saves[0], &saves[1]);
+    }
+}
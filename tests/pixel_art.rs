@@ -0,0 +1,44 @@
+use std::io;
+
+use brs::pixel_art::{self, PixelArtOptions};
+
+#[test]
+fn rejects_bad_sizes() {
+    let pixels = [255; 4];
+    let build = |width, height, pixels: &[u8], options| {
+        pixel_art::build(width, height, pixels, &options)
+            .unwrap_err()
+            .kind()
+    };
+    for options in [
+        PixelArtOptions {
+            pixel_size: 0,
+            ..Default::default()
+        },
+        PixelArtOptions {
+            thickness: 0,
+            ..Default::default()
+        },
+        PixelArtOptions {
+            thickness: u32::MAX,
+            ..Default::default()
+        },
+        PixelArtOptions {
+            pixel_size: 1 << 30,
+            ..Default::default()
+        },
+    ] {
+        assert_eq!(build(1, 1, &pixels, options), io::ErrorKind::InvalidInput);
+    }
+
+    // A tall, thin image whose top would be out of range.
+    let pixels = vec![255; 4 << 20];
+    let options = PixelArtOptions {
+        pixel_size: 1 << 10,
+        ..Default::default()
+    };
+    assert_eq!(
+        build(1, 1 << 20, &pixels, options),
+        io::ErrorKind::InvalidInput
+    );
+}
@@ -0,0 +1,171 @@
+mod common;
+
+use brs::{read::Limits, Reader};
+use common::{brick, save};
+use std::{
+    convert::TryInto,
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+/// Read `bytes` as a save through both headers and all bricks.
+fn read(bytes: &[u8], limits: Limits) -> io::Result<usize> {
+    let reader = Reader::with_limits(bytes, limits)?;
+    let reader = reader.read_header1()?.read_header2()?;
+    let mut count = 0;
+    for brick in reader.iter_bricks()? {
+        brick?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn read_err(bytes: &[u8], limits: Limits) -> io::ErrorKind {
+    read(bytes, limits).unwrap_err().kind()
+}
+
+/// The decompressed sections of a save of three bricks: the two headers
+/// and the bricks.
+fn sections() -> Vec<Vec<u8>> {
+    let data = save(vec![brick((5, 5, 6)), brick((15, 5, 6)), brick((25, 5, 6))]);
+    let mut bytes = Vec::new();
+    brs::write_save(&mut bytes, &data).unwrap();
+
+    let mut r = &bytes[5..];
+    let int = |r: &mut &[u8]| {
+        let (value, rest) = r.split_at(4);
+        *r = rest;
+        i32::from_le_bytes(value.try_into().unwrap()) as usize
+    };
+    let mut sections = Vec::new();
+    while !r.is_empty() {
+        let (uncompressed, compressed) = (int(&mut r), int(&mut r));
+        let mut section = Vec::new();
+        if compressed == 0 {
+            section.extend_from_slice(&r[..uncompressed]);
+            r = &r[uncompressed..];
+        } else {
+            let mut decoder = libflate::zlib::Decoder::new(&r[..compressed]).unwrap();
+            decoder.read_to_end(&mut section).unwrap();
+            r = &r[compressed..];
+        }
+        assert_eq!(section.len(), uncompressed);
+        sections.push(section);
+    }
+    assert_eq!(sections.len(), 3);
+    sections
+}
+
+/// A version 4 save of uncompressed `sections`.
+fn file(sections: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = b"BRS".to_vec();
+    out.extend_from_slice(&4u16.to_le_bytes());
+    for section in sections {
+        out.extend_from_slice(&(section.len() as i32).to_le_bytes());
+        out.extend_from_slice(&0i32.to_le_bytes());
+        out.extend_from_slice(section);
+    }
+    out
+}
+
+#[test]
+fn reads_uncompressed_sections() {
+    assert_eq!(read(&file(&sections()), Limits::default()).unwrap(), 3);
+}
+
+#[test]
+fn rejects_negative_array_count() {
+    let mut sections = sections();
+    // The mods come first in the second header.
+    sections[1][..4].copy_from_slice(&(-1i32).to_le_bytes());
+    let err = read_err(&file(&sections), Limits::default());
+    assert_eq!(err, io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn rejects_huge_string_length() {
+    let mut sections = sections();
+    // The map name comes first in the first header.
+    sections[0][..4].copy_from_slice(&i32::MAX.to_le_bytes());
+    let bytes = file(&sections);
+    assert_eq!(
+        read_err(&bytes, Limits::default()),
+        io::ErrorKind::InvalidData
+    );
+    // Without a limit, the length is checked against the data that's there
+    // rather than allocated up front.
+    assert_eq!(
+        read_err(&bytes, Limits::unlimited()),
+        io::ErrorKind::UnexpectedEof
+    );
+}
+
+#[test]
+fn rejects_table_over_max_table_len() {
+    // There are three brick assets.
+    let bytes = file(&sections());
+    let limits = |max_table_len| Limits {
+        max_table_len,
+        ..Default::default()
+    };
+    assert_eq!(read(&bytes, limits(3)).unwrap(), 3);
+    assert_eq!(read_err(&bytes, limits(2)), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn rejects_section_over_max_section_size() {
+    let sections = sections();
+    let limits = Limits {
+        max_section_size: sections[0].len() - 1,
+        ..Default::default()
+    };
+    assert_eq!(
+        read_err(&file(&sections), limits),
+        io::ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn rejects_bricks_cut_mid_brick() {
+    let mut sections = sections();
+    let len = sections[2].len();
+    for cut in 1..len {
+        sections[2].truncate(len - cut);
+        let err = read_err(&file(&sections), Limits::default());
+        assert_eq!(err, io::ErrorKind::UnexpectedEof, "cut {} bytes", cut);
+    }
+}
+
+#[test]
+fn reads_empty_sections() {
+    // A save without bricks may store its brick section with both sizes zero.
+    let mut sections = sections();
+    let count = sections[0].len() - 4;
+    sections[0][count..].copy_from_slice(&0i32.to_le_bytes());
+    sections[2].clear();
+    assert_eq!(read(&file(&sections), Limits::default()).unwrap(), 0);
+
+    // Empty headers end early.
+    sections[0].clear();
+    let err = read_err(&file(&sections), Limits::default());
+    assert_eq!(err, io::ErrorKind::UnexpectedEof);
+    let mut sections = self::sections();
+    sections[1].clear();
+    let err = read_err(&file(&sections), Limits::default());
+    assert_eq!(err, io::ErrorKind::UnexpectedEof);
+}
+
+/// Inputs that once crashed the fuzz target, which must now be read
+/// without panicking.
+#[test]
+fn reads_fuzz_regressions() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/regressions/read");
+    let mut count = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let bytes = fs::read(entry.unwrap().path()).unwrap();
+        let _ = read(&bytes, Limits::default());
+        count += 1;
+    }
+    assert!(count > 0);
+}
@@ -0,0 +1,89 @@
+use std::io;
+
+use brs::{
+    optimize::MAX_BRICK_SIZE,
+    terrain::{self, ColorBand, TerrainMode, TerrainOptions},
+    Color,
+};
+
+fn columns() -> TerrainOptions {
+    TerrainOptions {
+        mode: TerrainMode::Columns,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn splits_tall_columns() {
+    for mode in [TerrainMode::Columns, TerrainMode::Slabs] {
+        let options = TerrainOptions {
+            mode,
+            bands: vec![ColorBand {
+                up_to: i32::MAX,
+                color: Color::from_rgba(255, 255, 255, 255),
+            }],
+            ..Default::default()
+        };
+        let data = terrain::build(1, 1, &[2500.0], &options).unwrap();
+        assert_eq!(data.bricks.len(), 3);
+        assert!(data.bricks.iter().all(|b| b.size.2 <= MAX_BRICK_SIZE));
+        let height: u32 = data.bricks.iter().map(|b| 2 * b.size.2).sum();
+        assert_eq!(height, 2500);
+    }
+}
+
+#[test]
+fn rounds_bands_to_the_step() {
+    let white = Color::from_rgba(255, 255, 255, 255);
+    let options = TerrainOptions {
+        bands: vec![
+            ColorBand {
+                up_to: 7,
+                color: white,
+            },
+            ColorBand {
+                up_to: i32::MAX,
+                color: white,
+            },
+        ],
+        ..columns()
+    };
+    let data = terrain::build(1, 1, &[12.0], &options).unwrap();
+    let sizes = data.bricks.iter().map(|b| b.size.2).collect::<Vec<_>>();
+    assert_eq!(sizes, [2, 4]);
+}
+
+#[test]
+fn rejects_bad_sizes() {
+    let bad = [
+        TerrainOptions {
+            cell_size: 0,
+            ..columns()
+        },
+        TerrainOptions {
+            cell_size: MAX_BRICK_SIZE + 1,
+            ..columns()
+        },
+        TerrainOptions {
+            step: 3,
+            ..columns()
+        },
+        TerrainOptions {
+            step: 4 * MAX_BRICK_SIZE,
+            ..columns()
+        },
+    ];
+    for options in &bad {
+        let err = terrain::build(1, 1, &[4.0], options).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    // Wide enough that the far edge is out of range.
+    let options = TerrainOptions {
+        cell_size: MAX_BRICK_SIZE,
+        ..columns()
+    };
+    let heights = vec![4.0; 1 << 22];
+    let err = terrain::build(1 << 22, 1, &heights, &options).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
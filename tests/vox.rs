@@ -0,0 +1,94 @@
+use std::io;
+
+use brs::vox::{self, VoxOptions};
+
+fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+    let mut out = id.to_vec();
+    out.extend_from_slice(&(content.len() as i32).to_le_bytes());
+    out.extend_from_slice(&(children.len() as i32).to_le_bytes());
+    out.extend_from_slice(content);
+    out.extend_from_slice(children);
+    out
+}
+
+fn ints(values: &[i32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect()
+}
+
+/// A file with one model of three voxels, plus the given scene graph chunks.
+fn file(nodes: &[u8]) -> Vec<u8> {
+    let mut children = chunk(b"SIZE", &ints(&[4, 4, 4]), &[]);
+    let mut xyzi = ints(&[3]);
+    xyzi.extend_from_slice(&[0, 0, 0, 1, 1, 0, 0, 2, 3, 3, 3, 5]);
+    children.extend(chunk(b"XYZI", &xyzi, &[]));
+    children.extend_from_slice(nodes);
+
+    let mut out = b"VOX ".to_vec();
+    out.extend_from_slice(&ints(&[150]));
+    out.extend(chunk(b"MAIN", &[], &children));
+    out
+}
+
+/// A transform node with an empty attribute dictionary and one empty frame.
+fn transform(id: i32, child: i32) -> Vec<u8> {
+    chunk(b"nTRN", &ints(&[id, 0, child, -1, 0, 1, 0]), &[])
+}
+
+fn group(id: i32, children: &[i32]) -> Vec<u8> {
+    let mut content = ints(&[id, 0, children.len() as i32]);
+    content.extend(ints(children));
+    chunk(b"nGRP", &content, &[])
+}
+
+fn shape(id: i32, model: i32) -> Vec<u8> {
+    chunk(b"nSHP", &ints(&[id, 0, 1, model, 0]), &[])
+}
+
+#[test]
+fn reads_models() {
+    let options = VoxOptions {
+        merge_runs: false,
+        ..Default::default()
+    };
+    let data = vox::read(&file(&[])[..], &options).unwrap();
+    assert_eq!(data.bricks.len(), 3);
+    data.check_indices().unwrap();
+
+    let mut nodes = transform(0, 1);
+    nodes.extend(group(1, &[2]));
+    nodes.extend(transform(2, 3));
+    nodes.extend(shape(3, 0));
+    let data = vox::read(&file(&nodes)[..], &options).unwrap();
+    assert_eq!(data.bricks.len(), 3);
+}
+
+#[test]
+fn rejects_scenes_over_max_voxels() {
+    let options = VoxOptions {
+        max_voxels: 2,
+        ..Default::default()
+    };
+    let err = vox::read(&file(&[])[..], &options).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn rejects_shared_node_fan_out() {
+    // Each group points at the next transform twice, so the model is
+    // reached through 2^127 paths while the file stays tiny.
+    let levels = 127;
+    let mut nodes = Vec::new();
+    for level in 0..levels {
+        let id = level * 2;
+        nodes.extend(transform(id, id + 1));
+        nodes.extend(group(id + 1, &[id + 2, id + 2]));
+    }
+    nodes.extend(transform(levels * 2, levels * 2 + 1));
+    nodes.extend(shape(levels * 2 + 1, 0));
+
+    let err = vox::read(&file(&nodes)[..], &VoxOptions::default()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}